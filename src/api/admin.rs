@@ -0,0 +1,292 @@
+// The admin api, for controlling the fetcher of a running server on match day.
+//
+// Every route requires the admin token of the config as a bearer token, and the api is disabled
+// if no token is configured.
+use std::collections::HashMap;
+use std::time::{Duration, UNIX_EPOCH};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, State},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::fetcher::FetchSettings;
+use crate::structs;
+use crate::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/fetcher", get(get_fetcher).patch(patch_fetcher))
+        .route("/fetcher/pause", post(post_pause))
+        .route("/fetcher/resume", post(post_resume))
+        .route("/refetch", post(post_refetch))
+        .route("/refresh-static", post(post_refresh_static))
+        .route("/endpoints", get(get_endpoints))
+}
+
+// Rejects requests without the admin token
+pub struct Admin;
+
+#[async_trait]
+impl FromRequestParts<AppState> for Admin {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Response> {
+        let config = state.config.borrow().clone();
+        let Some(token) = &config.admin_token else {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "The admin api is disabled, set admin_token in the config to enable it",
+            )
+                .into_response());
+        };
+        let given = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        match given {
+            Some(given) if constant_time_eq(given.as_bytes(), token.expose().as_bytes()) => {
+                Ok(Admin)
+            }
+            _ => Err((
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                "Missing or wrong admin token",
+            )
+                .into_response()),
+        }
+    }
+}
+
+// Compares without returning early, so the time taken does not tell how much of a guess is right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct FetcherStatus {
+    pub fetch_interval_secs: u64,
+    pub static_info_interval_secs: u64,
+    pub paused: bool,
+    // The end of the latest fetch in seconds since the unix epoch, none before the first fetch
+    pub last_fetch: Option<u64>,
+}
+
+// The settings to change, the rest are kept
+#[derive(Deserialize, Debug, Default)]
+pub struct FetcherUpdate {
+    pub fetch_interval_secs: Option<u64>,
+    pub static_info_interval_secs: Option<u64>,
+    pub paused: Option<bool>,
+}
+
+fn fetcher_status(state: &AppState) -> FetcherStatus {
+    let settings = state.fetcher.settings();
+    FetcherStatus {
+        fetch_interval_secs: settings.fetch_interval.as_secs(),
+        static_info_interval_secs: settings.static_info_interval.as_secs(),
+        paused: settings.paused,
+        last_fetch: state
+            .fetcher
+            .last_fetch()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs()),
+    }
+}
+
+async fn get_fetcher(_: Admin, State(state): State<AppState>) -> Json<FetcherStatus> {
+    Json(fetcher_status(&state))
+}
+
+async fn patch_fetcher(
+    _: Admin,
+    State(state): State<AppState>,
+    Json(update): Json<FetcherUpdate>,
+) -> Result<Json<FetcherStatus>, (StatusCode, String)> {
+    let intervals = [
+        ("fetch_interval_secs", update.fetch_interval_secs),
+        (
+            "static_info_interval_secs",
+            update.static_info_interval_secs,
+        ),
+    ];
+    for (name, secs) in intervals {
+        if secs == Some(0) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("{} must be at least 1", name),
+            ));
+        }
+    }
+
+    state
+        .fetcher
+        .update_settings(|settings: &mut FetchSettings| {
+            if let Some(secs) = update.fetch_interval_secs {
+                settings.fetch_interval = Duration::from_secs(secs);
+            }
+            if let Some(secs) = update.static_info_interval_secs {
+                settings.static_info_interval = Duration::from_secs(secs);
+            }
+            if let Some(paused) = update.paused {
+                settings.paused = paused;
+            }
+        });
+    tracing::info!("Fetcher settings changed: {:?}", state.fetcher.settings());
+    Ok(Json(fetcher_status(&state)))
+}
+
+async fn post_pause(_: Admin, State(state): State<AppState>) -> Json<FetcherStatus> {
+    state.fetcher.update_settings(|s| s.paused = true);
+    tracing::info!("Fetcher paused");
+    Json(fetcher_status(&state))
+}
+
+async fn post_resume(_: Admin, State(state): State<AppState>) -> Json<FetcherStatus> {
+    state.fetcher.update_settings(|s| s.paused = false);
+    tracing::info!("Fetcher resumed");
+    Json(fetcher_status(&state))
+}
+
+// The fetch happens in the background, poll GET /admin/fetcher for when it is done
+async fn post_refetch(_: Admin, State(state): State<AppState>) -> StatusCode {
+    state.fetcher.refetch();
+    StatusCode::ACCEPTED
+}
+
+async fn post_refresh_static(_: Admin, State(state): State<AppState>) -> StatusCode {
+    state.fetcher.refresh_static_info();
+    StatusCode::ACCEPTED
+}
+
+// The endpoints as fetched, without the lookup tables built from them
+#[derive(Serialize)]
+struct EndpointsDump<'a> {
+    details: &'a structs::Details,
+    game: &'a structs::Game,
+    live: &'a structs::Live,
+    static_info: &'a structs::StaticInfo,
+    teams_gws: &'a HashMap<u32, structs::TeamGw>,
+    teams_infos: &'a HashMap<u32, structs::TeamInfo>,
+}
+
+async fn get_endpoints(_: Admin, State(state): State<AppState>) -> Response {
+    let endpoints = state.endpoints.borrow().clone();
+    let dump = EndpointsDump {
+        details: &endpoints.details,
+        game: &endpoints.game,
+        live: &endpoints.live,
+        static_info: &endpoints.static_info,
+        teams_gws: &endpoints.teams_gws,
+        teams_infos: &endpoints.teams_infos,
+    };
+    Json(dump).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::client::Endpoint;
+    use crate::mockapi::{self, MockFpl, Pipeline};
+    use crate::testdata;
+
+    #[tokio::test]
+    async fn admin_api_controls_the_fetcher() {
+        let mut endpoints = testdata::endpoints(2, false);
+        let mock = MockFpl::from_endpoints(&endpoints, mockapi::LEAGUE_ID).await;
+        let pipeline = Pipeline::start_with_admin(&mock, Some("secret")).await;
+        let admin = |method, path: &str| {
+            let url = format!("{}/admin{}", pipeline.base_url, path);
+            pipeline.http.request(method, url).bearer_auth("secret")
+        };
+        let live = Endpoint::Live {
+            gw: testdata::CURRENT_GW,
+        };
+
+        let response = pipeline
+            .http
+            .get(format!("{}/admin/fetcher", pipeline.base_url))
+            .bearer_auth("wrong")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        // Nothing is fetched while paused, once a fetch in progress is done
+        let response = admin(reqwest::Method::POST, "/fetcher/pause").send().await;
+        assert!(
+            response
+                .unwrap()
+                .json::<FetcherStatus>()
+                .await
+                .unwrap()
+                .paused
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let requests = mock.requests(&live);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(mock.requests(&live), requests);
+
+        // A refetch is done even when paused
+        let before = mockapi::gw_points(&pipeline.table().await, 1);
+        let player = testdata::player_id(1, 1);
+        let player_before = endpoints.live.elements[&player].stats.total_points;
+        testdata::set_player_points(&mut endpoints, player, player_before + 5, 90);
+        mock.set_endpoints(&endpoints, mockapi::LEAGUE_ID);
+        let response = admin(reqwest::Method::POST, "/refetch")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+        pipeline
+            .wait_for(|t| mockapi::gw_points(t, 1) == before + 5)
+            .await;
+
+        let static_requests = mock.requests(&Endpoint::Static);
+        admin(reqwest::Method::POST, "/refresh-static")
+            .send()
+            .await
+            .unwrap();
+        let start = Instant::now();
+        while mock.requests(&Endpoint::Static) == static_requests {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let response = admin(reqwest::Method::PATCH, "/fetcher")
+            .json(&serde_json::json!({"fetch_interval_secs": 0}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+        let status: FetcherStatus = admin(reqwest::Method::PATCH, "/fetcher")
+            .json(&serde_json::json!({"fetch_interval_secs": 300, "paused": false}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(status.fetch_interval_secs, 300);
+        assert!(!status.paused);
+        assert!(status.last_fetch.is_some());
+
+        let dump: serde_json::Value = admin(reqwest::Method::GET, "/endpoints")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(dump["game"]["current_event"], testdata::CURRENT_GW);
+        assert_eq!(dump["teams_gws"].as_object().unwrap().len(), 2);
+    }
+}
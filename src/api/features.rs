@@ -0,0 +1,43 @@
+// Routes of features that are turned off in the config answer 404, checked on every request so
+// that a reloaded config applies right away.
+use std::marker::PhantomData;
+
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts, http::StatusCode};
+
+use crate::initializer::Features;
+use crate::AppState;
+
+pub trait Feature {
+    fn enabled(features: &Features) -> bool;
+}
+
+pub struct Graphql;
+
+impl Feature for Graphql {
+    fn enabled(features: &Features) -> bool {
+        features.graphql
+    }
+}
+
+pub struct Debug;
+
+impl Feature for Debug {
+    fn enabled(features: &Features) -> bool {
+        features.debug
+    }
+}
+
+// Extracted by the handlers of a feature
+pub struct Enabled<F>(PhantomData<F>);
+
+#[async_trait]
+impl<F: Feature> FromRequestParts<AppState> for Enabled<F> {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(_: &mut Parts, state: &AppState) -> Result<Self, StatusCode> {
+        match F::enabled(&state.config.borrow().features) {
+            true => Ok(Enabled(PhantomData)),
+            false => Err(StatusCode::NOT_FOUND),
+        }
+    }
+}
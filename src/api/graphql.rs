@@ -0,0 +1,282 @@
+// GraphQL schema over the computed league table, the static player data, the fixtures of the
+// current gameweek and the H2H match history, so clients can request exactly the fields they need.
+//
+// Every query is resolved against the snapshot of the endpoints and the table that was current
+// when the request arrived.
+use std::sync::{Arc, OnceLock};
+
+use async_graphql::{
+    ComplexObject, Context, EmptyMutation, EmptySubscription, Object, Schema, SimpleObject,
+};
+
+use crate::fixtures::{self, FixtureCentre};
+use crate::players::{self, PlayerInfo};
+use crate::propcomp;
+use crate::storage::table::{Entry, H2HMatch, LeagueTable, PlayStatus, Player};
+use crate::storage::FplEndpoints;
+
+pub type ApiSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+// The data a query is resolved against
+pub struct Snapshot {
+    pub endpoints: Arc<FplEndpoints>,
+    pub table: Arc<LeagueTable>,
+}
+
+// Limits on the queries the schema accepts, so a single request can not make the server resolve
+// an arbitrarily large response. The types of the schema itself nest only a few levels, but the
+// introspection types can be nested without end.
+const MAX_DEPTH: usize = 10;
+const MAX_COMPLEXITY: usize = 500;
+
+pub fn schema() -> &'static ApiSchema {
+    static SCHEMA: OnceLock<ApiSchema> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+            .limit_depth(MAX_DEPTH)
+            .limit_complexity(MAX_COMPLEXITY)
+            .finish()
+    })
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// The live league table
+    async fn table<'ctx>(&self, ctx: &Context<'ctx>) -> &'ctx LeagueTable {
+        &ctx.data_unchecked::<Snapshot>().table
+    }
+
+    /// A team of the league table by its team code
+    async fn entry<'ctx>(&self, ctx: &Context<'ctx>, team_code: u32) -> Option<&'ctx Entry> {
+        let snapshot = ctx.data_unchecked::<Snapshot>();
+        snapshot
+            .table
+            .entries
+            .iter()
+            .find(|e| e.team_code == team_code)
+    }
+
+    /// Premier League players from bootstrap-static. Either the players with the given IDs, the
+    /// players owned by a league team or all players.
+    async fn players(
+        &self,
+        ctx: &Context<'_>,
+        ids: Option<Vec<u32>>,
+        #[graphql(default = false)] owned_only: bool,
+    ) -> Vec<PlayerInfo> {
+        let snapshot = ctx.data_unchecked::<Snapshot>();
+        let ids: Vec<u32> = match ids {
+            Some(ids) => ids,
+            None => snapshot
+                .endpoints
+                .static_info
+                .elements
+                .iter()
+                .filter_map(|e| e.id.map(|id| id as u32))
+                .collect(),
+        };
+        ids.into_iter()
+            .filter_map(|id| players::compute_player_info(&snapshot.endpoints, &snapshot.table, id))
+            .filter(|p| !owned_only || p.owner.is_some())
+            .collect()
+    }
+
+    /// A Premier League player from bootstrap-static
+    async fn player(&self, ctx: &Context<'_>, id: u32) -> Option<PlayerInfo> {
+        let snapshot = ctx.data_unchecked::<Snapshot>();
+        players::compute_player_info(&snapshot.endpoints, &snapshot.table, id)
+    }
+
+    /// The Premier League fixtures of the current gameweek
+    async fn fixtures(&self, ctx: &Context<'_>) -> FixtureCentre {
+        let snapshot = ctx.data_unchecked::<Snapshot>();
+        fixtures::compute_fixture_centre(&snapshot.endpoints, &snapshot.table)
+    }
+
+    /// The results of the H2H matches of the league, optionally of a single team. Empty for
+    /// classic leagues.
+    async fn history(&self, ctx: &Context<'_>, team_code: Option<u32>) -> Vec<GwResult> {
+        compute_history(ctx.data_unchecked::<Snapshot>(), team_code)
+    }
+}
+
+#[ComplexObject]
+impl LeagueTable {
+    /// The H2H matches of the league, optionally of a single gameweek. Empty for classic leagues.
+    async fn matches(&self, gw: Option<u32>) -> Vec<&H2HMatch> {
+        let mut matches: Vec<&H2HMatch> = self
+            .matches
+            .iter()
+            .flat_map(|m| m.values())
+            .flatten()
+            .filter(|m| gw.is_none_or(|gw| m.gw == gw))
+            .collect();
+        matches.sort_by_key(|m| (m.gw, m.league_entry_1));
+        matches
+    }
+}
+
+#[ComplexObject]
+impl Player {
+    /// Indicates whether or not the player is playing (or may play), benched (or may play)
+    async fn play_status(&self) -> PlayStatusInfo {
+        let (kind, subbed_with) = match self.play_status {
+            PlayStatus::Playing => ("playing", None),
+            PlayStatus::Benched => ("benched", None),
+            PlayStatus::SubbedIn { subbed_with } => ("subbed_in", Some(subbed_with)),
+            PlayStatus::SubbedOff { subbed_with } => ("subbed_off", Some(subbed_with)),
+            PlayStatus::Unknown => ("none", None),
+        };
+        PlayStatusInfo {
+            kind: String::from(kind),
+            subbed_with,
+        }
+    }
+}
+
+/// The play status of a player, with the same values as in the json api
+#[derive(SimpleObject)]
+pub struct PlayStatusInfo {
+    /// One of playing, benched, subbed_in, subbed_off or none
+    #[graphql(name = "type")]
+    pub kind: String,
+
+    /// The player that is subbed in or off for this player
+    pub subbed_with: Option<u32>,
+}
+
+#[derive(SimpleObject)]
+pub struct GwResult {
+    pub gw: u32,
+    pub team_code: u32,
+    pub points: i32,
+    pub opponent: u32,
+    pub opponent_points: i32,
+    pub finished: bool,
+}
+
+// Finished matches use the official points, the ongoing gameweek uses the live points of the table
+fn compute_history(snapshot: &Snapshot, team_code: Option<u32>) -> Vec<GwResult> {
+    let endpoints = &snapshot.endpoints;
+    let live_points = |entry_id: u32| {
+        snapshot
+            .table
+            .entries
+            .iter()
+            .find(|e| e.team_code == entry_id)
+            .map_or(0, |e| e.gw_points)
+    };
+
+    let mut results = Vec::new();
+    for m in endpoints
+        .details
+        .matches
+        .iter()
+        .flatten()
+        .filter(|m| m.started)
+    {
+        let entry_1 = propcomp::get_entry_id_from_team_id(endpoints, m.league_entry_1);
+        let entry_2 = propcomp::get_entry_id_from_team_id(endpoints, m.league_entry_2);
+        let (points_1, points_2) = if m.finished {
+            (
+                m.league_entry_1_points as i32,
+                m.league_entry_2_points as i32,
+            )
+        } else {
+            (live_points(entry_1), live_points(entry_2))
+        };
+
+        for (team, points, opponent, opponent_points) in [
+            (entry_1, points_1, entry_2, points_2),
+            (entry_2, points_2, entry_1, points_1),
+        ] {
+            if team_code.is_none_or(|code| code == team) {
+                results.push(GwResult {
+                    gw: m.event,
+                    team_code: team,
+                    points,
+                    opponent,
+                    opponent_points,
+                    finished: m.finished,
+                });
+            }
+        }
+    }
+    results.sort_by_key(|r| (r.gw, r.team_code));
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer;
+    use crate::testdata;
+
+    #[tokio::test]
+    async fn query_selected_fields() {
+        let endpoints = testdata::endpoints(4, true);
+        let table = computer::compute_new_league_table(&endpoints).unwrap();
+        let snapshot = Snapshot {
+            endpoints: Arc::new(endpoints),
+            table: Arc::new(table),
+        };
+        let team_code = testdata::entry_id(1);
+        let query = format!(
+            "{{
+                table {{ scoring entries {{ teamCode players {{ id playStatus {{ type }} }} }} }}
+                players(ownedOnly: true) {{ id owner }}
+                history(teamCode: {team_code}) {{ gw points opponent }}
+                fixtures {{ fixtures {{ id status }} }}
+            }}"
+        );
+
+        let response = schema()
+            .execute(async_graphql::Request::new(query).data(snapshot))
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        assert_eq!(data["table"]["scoring"], "H2H");
+        assert_eq!(data["table"]["entries"].as_array().unwrap().len(), 4);
+        assert_eq!(
+            data["table"]["entries"][0]["players"][0]["playStatus"]["type"],
+            "playing"
+        );
+        assert_eq!(
+            data["players"].as_array().unwrap().len(),
+            4 * testdata::SQUAD_SIZE as usize
+        );
+        assert_eq!(
+            data["history"].as_array().unwrap().len(),
+            testdata::CURRENT_GW as usize
+        );
+        assert_eq!(
+            data["fixtures"]["fixtures"].as_array().unwrap().len(),
+            (testdata::N_CLUBS / 2) as usize
+        );
+    }
+
+    #[tokio::test]
+    async fn deep_queries_are_rejected() {
+        let endpoints = testdata::endpoints(2, false);
+        let table = computer::compute_new_league_table(&endpoints).unwrap();
+        let snapshot = Snapshot {
+            endpoints: Arc::new(endpoints),
+            table: Arc::new(table),
+        };
+        let query = format!(
+            "{{ __schema {{ types {{ fields {{ type {}name{} }} }} }} }}",
+            "{ ofType ".repeat(MAX_DEPTH),
+            " }".repeat(MAX_DEPTH)
+        );
+
+        let response = schema()
+            .execute(async_graphql::Request::new(query).data(snapshot))
+            .await;
+
+        assert_eq!(response.errors.len(), 1);
+        assert_eq!(response.errors[0].message, "Query is nested too deep.");
+    }
+}
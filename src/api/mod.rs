@@ -0,0 +1,139 @@
+// The http api of the server.
+//
+// The api is served under /api/v1 and described by the OpenAPI document at /api/v1/openapi.json.
+// The routes from before the api was versioned are kept as aliases of the v1 routes so the
+// frontend and existing scripts keep working. The table, trade and compare aliases serve the
+// shapes from before the api was versioned instead of the v1 response types. The operational
+// routes, /metrics, /debug/* and the authenticated /admin/*, are not part of the versioned api.
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+
+use crate::api::features::{Debug, Enabled};
+use crate::client::{EndpointDrift, EndpointKind};
+use crate::compare::Comparison;
+use crate::metrics;
+use crate::propcomp;
+use crate::trade::{TradeAnalysis, TradeProposal};
+use crate::AppState;
+
+pub mod admin;
+mod features;
+mod graphql;
+mod openapi;
+pub mod types;
+pub mod v1;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .nest("/api/v1", v1::router())
+        .nest("/admin", admin::router())
+        .route("/fpl/player/:id", get(get_player))
+        .route("/table", get(get_table))
+        .route("/table/changes", get(v1::get_table_changes))
+        .route("/awards/gw/:gw", get(v1::get_gw_awards))
+        .route("/awards/gw/:gw/markdown", get(v1::get_gw_awards_markdown))
+        .route("/trade", post(post_trade))
+        .route("/standings/all-play", get(v1::get_all_play_standings))
+        .route("/fixtures", get(v1::get_fixtures))
+        .route("/compare/:entry_1/:entry_2", get(get_comparison))
+        .route("/metrics", get(get_metrics))
+        .route("/debug/schema-drift", get(get_schema_drift))
+}
+
+async fn get_table(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let table_json = state.table_json.borrow().clone();
+    table_json.legacy.to_response(&headers)
+}
+
+async fn post_trade(
+    State(state): State<AppState>,
+    Json(proposal): Json<TradeProposal>,
+) -> Result<Json<TradeAnalysis>, (StatusCode, String)> {
+    v1::analyze_trade(&state, &proposal).map(Json)
+}
+
+async fn get_comparison(
+    State(state): State<AppState>,
+    Path((entry_1, entry_2)): Path<(u32, u32)>,
+) -> Result<Json<Comparison>, (StatusCode, String)> {
+    v1::compare_entries(&state, entry_1, entry_2).map(Json)
+}
+
+async fn get_metrics(_: Enabled<Debug>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(),
+    )
+}
+
+// The fields of the FPL api that the endpoint structs do not match, by endpoint
+async fn get_schema_drift(
+    _: Enabled<Debug>,
+    State(state): State<AppState>,
+) -> Json<BTreeMap<EndpointKind, EndpointDrift>> {
+    Json(state.client.schema_drift().report())
+}
+
+// Only available as a plain text route from before the api was versioned
+async fn get_player(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> Result<String, (StatusCode, String)> {
+    let ep = state.endpoints.borrow().clone();
+    let full_name = propcomp::get_player_full_name(&ep, id);
+    Ok(format!("Player: {} with id {}\n", full_name, id))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mockapi::{self, MockFpl, Pipeline};
+    use crate::testdata;
+
+    async fn get(pipeline: &Pipeline, path: &str) -> serde_json::Value {
+        let url = format!("{}{}", pipeline.base_url, path);
+        let response = pipeline.http.get(url).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        response.json().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn aliases_keep_the_shapes_from_before_the_versioned_api() {
+        let endpoints = testdata::endpoints(2, true);
+        let mock = MockFpl::from_endpoints(&endpoints, mockapi::LEAGUE_ID).await;
+        let pipeline = Pipeline::start(&mock).await;
+
+        let legacy = get(&pipeline, "/table").await;
+        assert_eq!(legacy["scoring"], "H2H");
+        let player = &legacy["entries"][0]["players"][0];
+        assert_eq!(player["team_pos"], "GK");
+        assert_eq!(player["play_status"], serde_json::json!({"type": "playing"}));
+
+        let v1 = get(&pipeline, "/api/v1/table").await;
+        assert_eq!(v1["scoring"], "h2h");
+        let player = &v1["entries"][0]["players"][0];
+        assert_eq!(player["team_pos"], "gk");
+        assert_eq!(player["play_status"], serde_json::json!({"kind": "playing"}));
+
+        let proposal = serde_json::json!({
+            "entry_1": testdata::entry_id(1),
+            "entry_2": testdata::entry_id(2),
+            "entry_1_gives": [testdata::player_id(1, 6)],
+            "entry_2_gives": [testdata::player_id(2, 7)],
+        });
+        let trade = |path: &str| {
+            let url = format!("{}{}", pipeline.base_url, path);
+            pipeline.http.post(url).json(&proposal).send()
+        };
+        let legacy: serde_json::Value = trade("/trade").await.unwrap().json().await.unwrap();
+        assert_eq!(legacy["entry_1"]["gives"][0]["team_pos"], "MID");
+        let v1: serde_json::Value = trade("/api/v1/trade").await.unwrap().json().await.unwrap();
+        assert_eq!(v1["entry_1"]["gives"][0]["team_pos"], "mid");
+    }
+}
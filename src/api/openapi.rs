@@ -0,0 +1,116 @@
+use utoipa::OpenApi;
+
+use crate::api::{types, v1};
+use crate::storage::table;
+use crate::{awards, changes, compare, fixtures, lineup, news, standings, trade};
+
+// The OpenAPI document of the v1 api, served at /api/v1/openapi.json. Every schema that is
+// referenced by another schema has to be listed in the components.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Draught of FPL",
+        description = "Live league table and statistics of a Fantasy Premier League Draft league.\n\n\
+            Every enum is a snake_case string, and enums with data are objects tagged with `kind`."
+    ),
+    paths(
+        v1::get_table,
+        v1::get_table_changes,
+        v1::get_gw_awards,
+        v1::get_gw_awards_markdown,
+        v1::post_trade,
+        v1::get_all_play_standings,
+        v1::get_fixtures,
+        v1::get_comparison,
+        v1::get_news,
+        v1::get_lineup_warnings,
+    ),
+    components(schemas(
+        types::LeagueTable,
+        types::Entry,
+        table::ProjectedPointsExplanation,
+        types::Player,
+        table::Team,
+        types::Position,
+        table::PointSource,
+        types::Scoring,
+        table::H2HMatch,
+        table::H2HInfo,
+        table::RankInfo,
+        table::RankMovement,
+        table::InjuryStatus,
+        types::PlayStatus,
+        changes::ChangeSet,
+        awards::GwAwards,
+        awards::TeamAward,
+        awards::H2HLossAward,
+        awards::PlayerAward,
+        trade::TradeProposal,
+        types::TradeAnalysis,
+        types::TradeSide,
+        trade::SquadValue,
+        types::TradePlayer,
+        trade::UpcomingFixture,
+        standings::AllPlayStandings,
+        standings::AllPlayEntry,
+        fixtures::FixtureCentre,
+        fixtures::FixtureInfo,
+        fixtures::FixtureStatus,
+        fixtures::FixtureTeam,
+        fixtures::BpsEntry,
+        fixtures::LeaguePlayer,
+        types::Comparison,
+        types::ComparisonSide,
+        types::RemainingPlayer,
+        compare::SharedFixture,
+        news::LeagueNews,
+        news::LeagueNewsChange,
+        news::PlayerNews,
+        lineup::LineupWarnings,
+        lineup::TeamWarnings,
+        lineup::LineupWarning,
+    ))
+)]
+pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Collects the schema names of all $ref in the document
+    fn collect_refs(value: &serde_json::Value, refs: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map.iter() {
+                    match (key.as_str(), value.as_str()) {
+                        ("$ref", Some(r)) => refs.push(r.replace("#/components/schemas/", "")),
+                        _ => collect_refs(value, refs),
+                    }
+                }
+            }
+            serde_json::Value::Array(values) => {
+                values.iter().for_each(|v| collect_refs(v, refs));
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn openapi_document_is_complete() {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        assert!(doc["paths"]["/api/v1/table"]["get"].is_object());
+        assert!(doc["paths"]["/api/v1/trade"]["post"].is_object());
+
+        let mut refs = Vec::new();
+        collect_refs(&doc, &mut refs);
+        assert!(!refs.is_empty());
+        for r in refs {
+            assert!(
+                doc["components"]["schemas"][&r].is_object(),
+                "Schema {} is referenced but not in the components",
+                r
+            );
+        }
+    }
+}
@@ -0,0 +1,420 @@
+// Response types of the v1 api.
+//
+// Every enum of the v1 api is serialized in snake_case, and enums with data are internally tagged
+// with `kind`. The league table, trade and comparison types of the rest of the crate keep the
+// shapes from before the api was versioned, which the alias routes still serve, so only the types
+// that contain one of their enums are mirrored here. The other types are shared by both.
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::compare::{self, SharedFixture};
+use crate::storage::table::{
+    self, H2HInfo, H2HMatch, InjuryStatus, PointSource, ProjectedPointsExplanation, RankInfo, Team,
+};
+use crate::trade::{self, SquadValue, UpcomingFixture};
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct LeagueTable {
+    /// An array of the entries of the draft league
+    pub entries: Vec<Entry>,
+
+    /// The code of the draft league
+    pub code: u32,
+
+    /// The name of the draft league
+    pub name: String,
+
+    /// The scoring method of the league
+    pub scoring: Scoring,
+
+    /// The gameweek the table was computed for
+    pub gw: u32,
+
+    /// Whether or not FPL has finished the gameweek, if not the points may still change
+    pub gw_finished: bool,
+
+    /// Only present if league is H2H. A map between gameweek and a vector of unordered matches.
+    pub matches: Option<BTreeMap<u32, Vec<H2HMatch>>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct Entry {
+    /// The code of the team
+    pub team_code: u32,
+
+    /// The name of the draft league team
+    pub team_name: String,
+
+    /// The total number of points of this team all season
+    pub total_points: i32,
+
+    /// The total number of projected points of this team all season
+    pub total_projected_points: i32,
+
+    /// The current number of total points of the team, as calculated by the official FPL site
+    pub gw_points: i32,
+
+    /// The current number of points of the team, by calculating current metrics such as bps and
+    /// certain substitutions
+    pub gw_projected_points: i32,
+
+    /// List of objects containing simple information about the projected points
+    pub projected_points_explanation: Vec<ProjectedPointsExplanation>,
+
+    /// Name of the owner of the FPL team
+    pub owner_name: String,
+
+    /// Array containing detailed information of each player
+    pub players: Vec<Player>,
+
+    /// Struct containing H2H information
+    pub h2h_info: Option<H2HInfo>,
+
+    /// Only present if league is CLASSIC. Struct containing the ranks of the team
+    pub rank_info: Option<RankInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct Player {
+    /// The ID of the player
+    pub id: u32,
+
+    /// The full name of the player, concatenation of first name and second name
+    pub full_name: String,
+
+    /// The display name used by FPL
+    pub display_name: String,
+
+    /// The team the player plays for
+    pub team: Team,
+
+    /// The position the player has on FPL
+    pub team_pos: Position,
+
+    /// The number of points of the player on FPL
+    pub points: i32,
+
+    /// The number of points in the BPS (bonus point system)
+    pub bps: i32,
+
+    /// The current projected number of points for the player considering information such as bps and
+    /// substitutions
+    pub projected_points: i32,
+
+    /// An array containing the sources of the points of the player (not including projected points)
+    pub point_sources: Vec<PointSource>,
+
+    /// Whether or not the player is on the field either selected by the team owner or if
+    /// substituted in
+    pub on_field: bool,
+
+    /// The pick number of the player on the FPL teams. Where 12-15 are the bench and the rest are
+    /// chosen by the team owner as playing
+    pub pick_number: i32,
+
+    /// Whether or not this player has played any minutes this game week, therefore qualifying for
+    /// points
+    pub has_played: bool,
+
+    /// Whether or not this player's fixtures are finished
+    pub fixtures_finished: bool,
+
+    /// Whether or not the player has any fixtures this gameweek
+    pub has_upcoming_fixtures: bool,
+
+    /// The type of injury status the player has as marked by the FPL Towers
+    pub status: InjuryStatus,
+
+    /// The news for the player if there are any. This includes the injury message and when the player is expected to make a return.
+    pub news: Option<String>,
+
+    /// Indicates whether or not the player is playing (or may play), benched (or may play)
+    pub play_status: PlayStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Scoring {
+    H2h,
+    Classic,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Position {
+    Gk,
+    Def,
+    Mid,
+    Fwd,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlayStatus {
+    /// The player is selected on the field and has either played or can't yet be benched
+    Playing,
+
+    /// The player is selected on the bench and is NOT guaranteed to be subbed in
+    Benched,
+
+    /// The player is selected on the bench and is guaranteed to be subbed in
+    SubbedIn { subbed_with: u32 },
+
+    /// The player is selected on the field and is guaranteed to be subbed off
+    SubbedOff { subbed_with: u32 },
+
+    /// Not yet able to project what the status is for the player
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TradeAnalysis {
+    pub entry_1: TradeSide,
+    pub entry_2: TradeSide,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TradeSide {
+    pub team_code: u32,
+    pub team_name: String,
+
+    /// The players leaving the team
+    pub gives: Vec<TradePlayer>,
+
+    /// The players joining the team
+    pub receives: Vec<TradePlayer>,
+
+    /// The squad before the trade
+    pub before: SquadValue,
+
+    /// The squad after the trade
+    pub after: SquadValue,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TradePlayer {
+    pub id: u32,
+    pub display_name: String,
+    pub team_short_name: String,
+    pub team_pos: Position,
+    pub season_points: i32,
+    pub form: f64,
+    pub expected_points: f64,
+    pub upcoming_fixtures: Vec<UpcomingFixture>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct Comparison {
+    pub entry_1: ComparisonSide,
+    pub entry_2: ComparisonSide,
+
+    /// The fixtures not yet finished where both teams have remaining players
+    pub shared_fixtures: Vec<SharedFixture>,
+
+    /// Projected points of the first team minus the projected points of the second team
+    pub margin: i32,
+
+    /// The estimated probability of the first team finishing the gameweek with more points
+    pub entry_1_win_probability: f64,
+
+    /// The estimated probability of the second team finishing the gameweek with more points
+    pub entry_2_win_probability: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ComparisonSide {
+    pub team_code: u32,
+    pub team_name: String,
+    pub gw_points: i32,
+    pub gw_projected_points: i32,
+
+    /// The counting players that have not yet finished all their fixtures
+    pub remaining_players: Vec<RemainingPlayer>,
+
+    /// Sum of the expected points of the remaining players
+    pub remaining_expected_points: f64,
+
+    /// The number of points the remaining players need to score to overtake the other team,
+    /// 0 if the team is already ahead
+    pub points_needed: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct RemainingPlayer {
+    pub id: u32,
+    pub display_name: String,
+    pub play_status: PlayStatus,
+    pub projected_points: i32,
+    /// The FPL expected points of the gameweek for the match time still to be played
+    pub expected_points: f64,
+}
+
+impl From<&table::LeagueTable> for LeagueTable {
+    fn from(table: &table::LeagueTable) -> LeagueTable {
+        LeagueTable {
+            entries: table.entries.iter().map(Entry::from).collect(),
+            code: table.code,
+            name: table.name.clone(),
+            scoring: table.scoring.into(),
+            gw: table.gw,
+            gw_finished: table.gw_finished,
+            matches: table.matches.clone(),
+        }
+    }
+}
+
+impl From<&table::Entry> for Entry {
+    fn from(entry: &table::Entry) -> Entry {
+        Entry {
+            team_code: entry.team_code,
+            team_name: entry.team_name.clone(),
+            total_points: entry.total_points,
+            total_projected_points: entry.total_projected_points,
+            gw_points: entry.gw_points,
+            gw_projected_points: entry.gw_projected_points,
+            projected_points_explanation: entry.projected_points_explanation.clone(),
+            owner_name: entry.owner_name.clone(),
+            players: entry.players.iter().map(Player::from).collect(),
+            h2h_info: entry.h2h_info.clone(),
+            rank_info: entry.rank_info.clone(),
+        }
+    }
+}
+
+impl From<&table::Player> for Player {
+    fn from(player: &table::Player) -> Player {
+        Player {
+            id: player.id,
+            full_name: player.full_name.clone(),
+            display_name: player.display_name.clone(),
+            team: player.team.clone(),
+            team_pos: player.team_pos.into(),
+            points: player.points,
+            bps: player.bps,
+            projected_points: player.projected_points,
+            point_sources: player.point_sources.clone(),
+            on_field: player.on_field,
+            pick_number: player.pick_number,
+            has_played: player.has_played,
+            fixtures_finished: player.fixtures_finished,
+            has_upcoming_fixtures: player.has_upcoming_fixtures,
+            status: player.status,
+            news: player.news.clone(),
+            play_status: (&player.play_status).into(),
+        }
+    }
+}
+
+impl From<table::Scoring> for Scoring {
+    fn from(scoring: table::Scoring) -> Scoring {
+        match scoring {
+            table::Scoring::H2H => Scoring::H2h,
+            table::Scoring::CLASSIC => Scoring::Classic,
+        }
+    }
+}
+
+impl From<table::Position> for Position {
+    fn from(position: table::Position) -> Position {
+        match position {
+            table::Position::GK => Position::Gk,
+            table::Position::DEF => Position::Def,
+            table::Position::MID => Position::Mid,
+            table::Position::FWD => Position::Fwd,
+        }
+    }
+}
+
+impl From<&table::PlayStatus> for PlayStatus {
+    fn from(play_status: &table::PlayStatus) -> PlayStatus {
+        match *play_status {
+            table::PlayStatus::Playing => PlayStatus::Playing,
+            table::PlayStatus::Benched => PlayStatus::Benched,
+            table::PlayStatus::SubbedIn { subbed_with } => PlayStatus::SubbedIn { subbed_with },
+            table::PlayStatus::SubbedOff { subbed_with } => PlayStatus::SubbedOff { subbed_with },
+            table::PlayStatus::Unknown => PlayStatus::Unknown,
+        }
+    }
+}
+
+impl From<trade::TradeAnalysis> for TradeAnalysis {
+    fn from(analysis: trade::TradeAnalysis) -> TradeAnalysis {
+        TradeAnalysis {
+            entry_1: analysis.entry_1.into(),
+            entry_2: analysis.entry_2.into(),
+        }
+    }
+}
+
+impl From<trade::TradeSide> for TradeSide {
+    fn from(side: trade::TradeSide) -> TradeSide {
+        TradeSide {
+            team_code: side.team_code,
+            team_name: side.team_name,
+            gives: side.gives.into_iter().map(TradePlayer::from).collect(),
+            receives: side.receives.into_iter().map(TradePlayer::from).collect(),
+            before: side.before,
+            after: side.after,
+        }
+    }
+}
+
+impl From<trade::TradePlayer> for TradePlayer {
+    fn from(player: trade::TradePlayer) -> TradePlayer {
+        TradePlayer {
+            id: player.id,
+            display_name: player.display_name,
+            team_short_name: player.team_short_name,
+            team_pos: player.team_pos.into(),
+            season_points: player.season_points,
+            form: player.form,
+            expected_points: player.expected_points,
+            upcoming_fixtures: player.upcoming_fixtures,
+        }
+    }
+}
+
+impl From<compare::Comparison> for Comparison {
+    fn from(comparison: compare::Comparison) -> Comparison {
+        Comparison {
+            entry_1: comparison.entry_1.into(),
+            entry_2: comparison.entry_2.into(),
+            shared_fixtures: comparison.shared_fixtures,
+            margin: comparison.margin,
+            entry_1_win_probability: comparison.entry_1_win_probability,
+            entry_2_win_probability: comparison.entry_2_win_probability,
+        }
+    }
+}
+
+impl From<compare::ComparisonSide> for ComparisonSide {
+    fn from(side: compare::ComparisonSide) -> ComparisonSide {
+        ComparisonSide {
+            team_code: side.team_code,
+            team_name: side.team_name,
+            gw_points: side.gw_points,
+            gw_projected_points: side.gw_projected_points,
+            remaining_players: side
+                .remaining_players
+                .into_iter()
+                .map(RemainingPlayer::from)
+                .collect(),
+            remaining_expected_points: side.remaining_expected_points,
+            points_needed: side.points_needed,
+        }
+    }
+}
+
+impl From<compare::RemainingPlayer> for RemainingPlayer {
+    fn from(player: compare::RemainingPlayer) -> RemainingPlayer {
+        RemainingPlayer {
+            id: player.id,
+            display_name: player.display_name,
+            play_status: (&player.play_status).into(),
+            projected_points: player.projected_points,
+            expected_points: player.expected_points,
+        }
+    }
+}
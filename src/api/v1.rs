@@ -0,0 +1,322 @@
+// Handlers of the v1 api. Every handler is documented with the utoipa path attribute so it shows
+// up in the OpenAPI document.
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use utoipa::OpenApi;
+
+use crate::api::features::{Enabled, Graphql};
+use crate::api::graphql::{self, Snapshot};
+use crate::api::openapi::ApiDoc;
+use crate::api::types::{Comparison, TradeAnalysis};
+use crate::awards::{self, GwAwards};
+use crate::changes::ChangeSet;
+use crate::compare;
+use crate::fixtures::{self, FixtureCentre};
+use crate::lineup::{self, LineupWarnings};
+use crate::news::{self, LeagueNews};
+use crate::standings::{self, AllPlayStandings};
+use crate::trade::{self, TradeProposal};
+use crate::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/openapi.json", get(get_openapi))
+        .route("/table", get(get_table))
+        .route("/table/changes", get(get_table_changes))
+        .route("/awards/gw/:gw", get(get_gw_awards))
+        .route("/awards/gw/:gw/markdown", get(get_gw_awards_markdown))
+        .route("/trade", post(post_trade))
+        .route("/standings/all-play", get(get_all_play_standings))
+        .route("/fixtures", get(get_fixtures))
+        .route("/compare/:entry_1/:entry_2", get(get_comparison))
+        .route("/news", get(get_news))
+        .route("/lineup-warnings", get(get_lineup_warnings))
+        .route("/graphql", get(get_graphiql).post(post_graphql))
+        .route("/graphql/schema.graphql", get(get_graphql_schema))
+}
+
+async fn get_openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// The live league table
+///
+/// Served gzip or brotli compressed when accepted, with a strong ETag for each encoding. Requests
+/// with a matching If-None-Match header get an empty 304 response.
+#[utoipa::path(
+    get,
+    path = "/api/v1/table",
+    responses(
+        (status = 200, description = "The current league table", body = LeagueTable),
+        (status = 304, description = "The table has not changed since the ETag was issued"),
+    )
+)]
+pub async fn get_table(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let table_json = state.table_json.borrow().clone();
+    table_json.v1.to_response(&headers)
+}
+
+/// What changed in the latest league table compared to the table before it
+#[utoipa::path(
+    get,
+    path = "/api/v1/table/changes",
+    responses((status = 200, body = ChangeSet))
+)]
+pub async fn get_table_changes(State(state): State<AppState>) -> Json<ChangeSet> {
+    let changes = state.changes.borrow().clone();
+    Json(changes.as_ref().clone())
+}
+
+/// The awards of a gameweek
+#[utoipa::path(
+    get,
+    path = "/api/v1/awards/gw/{gw}",
+    params(("gw" = u32, Path, description = "The gameweek")),
+    responses(
+        (status = 200, body = GwAwards),
+        (status = 404, description = "The gameweek has not started", body = String),
+        (status = 502, description = "The gameweek could not be fetched from FPL", body = String),
+    )
+)]
+pub async fn get_gw_awards(
+    State(state): State<AppState>,
+    Path(gw): Path<u32>,
+) -> Result<Json<GwAwards>, (StatusCode, String)> {
+    let awards = compute_gw_awards(&state, gw).await?;
+    Ok(Json(awards.as_ref().clone()))
+}
+
+/// The awards of a gameweek as markdown, ready to be posted to a group chat
+#[utoipa::path(
+    get,
+    path = "/api/v1/awards/gw/{gw}/markdown",
+    params(("gw" = u32, Path, description = "The gameweek")),
+    responses(
+        (status = 200, body = String, content_type = "text/markdown"),
+        (status = 404, description = "The gameweek has not started", body = String),
+        (status = 502, description = "The gameweek could not be fetched from FPL", body = String),
+    )
+)]
+pub async fn get_gw_awards_markdown(
+    State(state): State<AppState>,
+    Path(gw): Path<u32>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let awards = compute_gw_awards(&state, gw).await?;
+    Ok((
+        StatusCode::OK,
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/markdown; charset=utf-8",
+        )],
+        awards::render_markdown(&awards),
+    ))
+}
+
+async fn compute_gw_awards(
+    state: &AppState,
+    gw: u32,
+) -> Result<Arc<GwAwards>, (StatusCode, String)> {
+    let endpoints = state.endpoints.borrow().clone();
+
+    let current_gw = endpoints.game.current_event.unwrap_or(1);
+    if gw == 0 || gw > current_gw {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Gameweek {} has not started", gw),
+        ));
+    }
+
+    let context = state.fetcher.context();
+    awards::fetch_and_compute_gw_awards(
+        &state.client,
+        &state.awards,
+        &endpoints,
+        context.league_id,
+        &context.team_ids,
+        gw,
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to compute awards for GW {}: {}", gw, e);
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to fetch data for gameweek {}", gw),
+        )
+    })
+}
+
+/// Analyzes a proposed trade between two league teams
+#[utoipa::path(
+    post,
+    path = "/api/v1/trade",
+    request_body = TradeProposal,
+    responses(
+        (status = 200, body = TradeAnalysis),
+        (status = 400, description = "The trade is not valid", body = String),
+        (status = 404, description = "A team is not in the league", body = String),
+    )
+)]
+pub async fn post_trade(
+    State(state): State<AppState>,
+    Json(proposal): Json<TradeProposal>,
+) -> Result<Json<TradeAnalysis>, (StatusCode, String)> {
+    analyze_trade(&state, &proposal).map(|analysis| Json(analysis.into()))
+}
+
+// The trade analysis in the shape from before the api was versioned
+pub fn analyze_trade(
+    state: &AppState,
+    proposal: &TradeProposal,
+) -> Result<trade::TradeAnalysis, (StatusCode, String)> {
+    let ep = state.endpoints.borrow().clone();
+    trade::analyze_trade(&ep, proposal).map_err(|e| match e {
+        trade::TradeError::UnknownEntry(_) => (StatusCode::NOT_FOUND, e.to_string()),
+        _ => (StatusCode::BAD_REQUEST, e.to_string()),
+    })
+}
+
+/// The all-play standings and luck index of a H2H league
+#[utoipa::path(
+    get,
+    path = "/api/v1/standings/all-play",
+    responses(
+        (status = 200, body = AllPlayStandings),
+        (status = 404, description = "The league is not a H2H league", body = String),
+    )
+)]
+pub async fn get_all_play_standings(
+    State(state): State<AppState>,
+) -> Result<Json<AllPlayStandings>, (StatusCode, String)> {
+    let ep = state.endpoints.borrow().clone();
+    let table = state.table.borrow().clone();
+    match standings::compute_all_play_standings(&ep, &table) {
+        Some(standings) => Ok(Json(standings)),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            String::from("All-play standings are only available for H2H leagues"),
+        )),
+    }
+}
+
+/// The changes to the news and injury status of the players owned in the league
+#[utoipa::path(
+    get,
+    path = "/api/v1/news",
+    responses((status = 200, body = LeagueNews))
+)]
+pub async fn get_news(State(state): State<AppState>) -> Json<LeagueNews> {
+    let timeline = state.news.borrow().clone();
+    let ep = state.endpoints.borrow().clone();
+    let table = state.table.borrow().clone();
+    Json(news::compute_league_news(
+        &timeline,
+        &ep,
+        &table,
+        news::unix_time_now(),
+    ))
+}
+
+/// The problems with the lineups of the league teams for the next deadline
+#[utoipa::path(
+    get,
+    path = "/api/v1/lineup-warnings",
+    responses(
+        (status = 200, body = LineupWarnings),
+        (status = 404, description = "The last deadline of the season has passed", body = String),
+    )
+)]
+pub async fn get_lineup_warnings(
+    State(state): State<AppState>,
+) -> Result<Json<LineupWarnings>, (StatusCode, String)> {
+    let ep = state.endpoints.borrow().clone();
+    let table = state.table.borrow().clone();
+    lineup::compute_lineup_warnings(&ep, &table, news::unix_time_now())
+        .map(Json)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            String::from("There are no deadlines left this season"),
+        ))
+}
+
+/// The Premier League fixtures of the current gameweek
+#[utoipa::path(
+    get,
+    path = "/api/v1/fixtures",
+    responses((status = 200, body = FixtureCentre))
+)]
+pub async fn get_fixtures(State(state): State<AppState>) -> Json<FixtureCentre> {
+    let ep = state.endpoints.borrow().clone();
+    let table = state.table.borrow().clone();
+    Json(fixtures::compute_fixture_centre(&ep, &table))
+}
+
+/// Compares the current gameweek of two league teams
+#[utoipa::path(
+    get,
+    path = "/api/v1/compare/{entry_1}/{entry_2}",
+    params(
+        ("entry_1" = u32, Path, description = "The team code of the first team"),
+        ("entry_2" = u32, Path, description = "The team code of the second team"),
+    ),
+    responses(
+        (status = 200, body = Comparison),
+        (status = 404, description = "A team is not in the league", body = String),
+    )
+)]
+pub async fn get_comparison(
+    State(state): State<AppState>,
+    Path((entry_1, entry_2)): Path<(u32, u32)>,
+) -> Result<Json<Comparison>, (StatusCode, String)> {
+    compare_entries(&state, entry_1, entry_2).map(|comparison| Json(comparison.into()))
+}
+
+// The comparison in the shape from before the api was versioned
+pub fn compare_entries(
+    state: &AppState,
+    entry_1: u32,
+    entry_2: u32,
+) -> Result<compare::Comparison, (StatusCode, String)> {
+    let ep = state.endpoints.borrow().clone();
+    let table = state.table.borrow().clone();
+    compare::compare_entries(&ep, &table, entry_1, entry_2)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            format!(
+                "Entries {} and {} must both be in the league",
+                entry_1, entry_2
+            ),
+        ))
+}
+
+// The GraphQL api is described by its own schema and is not part of the OpenAPI document
+async fn post_graphql(
+    _: Enabled<Graphql>,
+    State(state): State<AppState>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    let snapshot = Snapshot {
+        endpoints: state.endpoints.borrow().clone(),
+        table: state.table.borrow().clone(),
+    };
+    Json(graphql::schema().execute(request.data(snapshot)).await)
+}
+
+// An in-browser IDE for exploring the GraphQL api
+async fn get_graphiql(_: Enabled<Graphql>) -> Html<String> {
+    Html(
+        async_graphql::http::GraphiQLSource::build()
+            .endpoint("/api/v1/graphql")
+            .finish(),
+    )
+}
+
+async fn get_graphql_schema(_: Enabled<Graphql>) -> String {
+    graphql::schema().sdl()
+}
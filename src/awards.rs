@@ -0,0 +1,536 @@
+// Computes the weekly league awards for a gameweek.
+//
+// The awards are computed from a league table that was computed with the endpoints of that
+// gameweek, meaning that the points of the entries are the gameweek points and not the current
+// points. Transfers are found by comparing the picks of the gameweek with the picks of the
+// previous gameweek. The awards of a finished gameweek and the picks of past gameweeks never
+// change, so they are cached and only fetched from FPL once.
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::client::{Client, ClientError};
+use crate::computer;
+use crate::fetcher;
+use crate::propcomp;
+use crate::storage::table::{Entry as TableEntry, PlayStatus, Player as TablePlayer, Scoring};
+use crate::storage::{FplEndpoints, LeagueTable};
+use crate::structs::TeamGw;
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct GwAwards {
+    /// The gameweek the awards are given for
+    pub gw: u32,
+
+    /// Whether or not FPL has finished the gameweek, if not the awards may change
+    pub gw_finished: bool,
+
+    /// The team with the most points in the gameweek
+    pub top_scorer: Option<TeamAward>,
+
+    /// The team with the most points left on the bench
+    pub biggest_bench_haul: Option<TeamAward>,
+
+    /// Only present if league is H2H. The losing team with the most points in the gameweek
+    pub unluckiest_h2h_loss: Option<H2HLossAward>,
+
+    /// The player new to a team since the previous gameweek with the most points
+    pub best_transfer: Option<PlayerAward>,
+
+    /// The best performing player of each team, in the same order as the league table
+    pub players_of_the_week: Vec<PlayerAward>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct TeamAward {
+    pub team_code: u32,
+    pub team_name: String,
+    pub owner_name: String,
+    pub points: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct H2HLossAward {
+    /// The losing team and its points
+    pub loser: TeamAward,
+
+    /// The winning team and its points
+    pub winner: TeamAward,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct PlayerAward {
+    pub team_code: u32,
+    pub team_name: String,
+    pub player_id: u32,
+    pub player_name: String,
+    pub points: i32,
+}
+
+// The awards of finished gameweeks and the picks of past gameweeks of one league. Everything is
+// dropped when another league is asked for.
+#[derive(Default)]
+pub struct AwardsCache {
+    entries: Mutex<CacheEntries>,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    league_id: u32,
+    awards: HashMap<u32, Arc<GwAwards>>,
+    // The picks of the league teams by gameweek
+    teams_gws: HashMap<u32, Arc<HashMap<u32, TeamGw>>>,
+}
+
+impl AwardsCache {
+    // The entries of the league, emptied if they were of another league
+    fn entries(&self, league_id: u32) -> std::sync::MutexGuard<'_, CacheEntries> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.league_id != league_id {
+            *entries = CacheEntries {
+                league_id,
+                ..Default::default()
+            };
+        }
+        entries
+    }
+
+    fn awards(&self, league_id: u32, gw: u32) -> Option<Arc<GwAwards>> {
+        self.entries(league_id).awards.get(&gw).cloned()
+    }
+
+    fn teams_gws(&self, league_id: u32, gw: u32) -> Option<Arc<HashMap<u32, TeamGw>>> {
+        self.entries(league_id).teams_gws.get(&gw).cloned()
+    }
+}
+
+// Fetches the data needed for the awards of a gameweek and computes them. The endpoints are the
+// current endpoints which are used as is if the gameweek is the current gameweek. Only the data
+// that is not in the cache is fetched.
+pub async fn fetch_and_compute_gw_awards(
+    client: &Client,
+    cache: &AwardsCache,
+    endpoints: &FplEndpoints,
+    league_id: u32,
+    teams: &Vec<u32>,
+    gw: u32,
+) -> Result<Arc<GwAwards>, ClientError> {
+    if let Some(awards) = cache.awards(league_id, gw) {
+        return Ok(awards);
+    }
+
+    let current_gw = endpoints.game.current_event.unwrap_or(1);
+    let gw_endpoints = if gw == current_gw {
+        endpoints.clone()
+    } else {
+        let gw_endpoints = fetcher::fetch_gw_endpoints(client, endpoints, teams, gw).await?;
+        cache
+            .entries(league_id)
+            .teams_gws
+            .insert(gw, Arc::new(gw_endpoints.teams_gws.clone()));
+        gw_endpoints
+    };
+
+    let previous_teams_gws = if gw <= 1 {
+        Arc::default()
+    } else if let Some(teams_gws) = cache.teams_gws(league_id, gw - 1) {
+        teams_gws
+    } else {
+        let teams_gws = Arc::new(fetcher::fetch_teams_gws(client, teams, gw - 1).await?);
+        cache
+            .entries(league_id)
+            .teams_gws
+            .insert(gw - 1, Arc::clone(&teams_gws));
+        teams_gws
+    };
+
+    let table = computer::compute_new_league_table(&gw_endpoints).ok_or_else(|| {
+        ClientError::Internal(format!("Could not compute league table for GW {}", gw))
+    })?;
+
+    let awards = Arc::new(compute_gw_awards(
+        gw,
+        &gw_endpoints,
+        &table,
+        &previous_teams_gws,
+    ));
+    if awards.gw_finished {
+        cache
+            .entries(league_id)
+            .awards
+            .insert(gw, Arc::clone(&awards));
+    }
+    Ok(awards)
+}
+
+pub fn compute_gw_awards(
+    gw: u32,
+    endpoints: &FplEndpoints,
+    table: &LeagueTable,
+    previous_teams_gws: &HashMap<u32, TeamGw>,
+) -> GwAwards {
+    // Final once FPL has finished the gameweek, not only its fixtures, as the bonus points and
+    // automatic subs are only settled then. No fixtures means the live data is not there yet.
+    let game = &endpoints.game;
+    let gw_over = match game.current_event {
+        Some(current_gw) if gw < current_gw => true,
+        Some(current_gw) => gw == current_gw && game.current_event_finished == Some(true),
+        None => false,
+    };
+    let fixtures = &endpoints.live.fixtures;
+    let gw_finished = gw_over && !fixtures.is_empty() && fixtures.iter().all(|f| f.finished);
+
+    let top_scorer =
+        best_entry_by(&table.entries, |e| e.gw_points).map(|e| team_award(e, e.gw_points));
+
+    let biggest_bench_haul =
+        best_entry_by(&table.entries, bench_points).map(|e| team_award(e, bench_points(e)));
+
+    let unluckiest_h2h_loss = match table.scoring {
+        Scoring::H2H => compute_unluckiest_h2h_loss(gw, endpoints, table),
+        Scoring::CLASSIC => None,
+    };
+
+    let best_transfer = table
+        .entries
+        .iter()
+        .filter_map(|e| {
+            let previous_picks: HashSet<u32> = previous_teams_gws
+                .get(&e.team_code)?
+                .picks
+                .iter()
+                .map(|p| p.element as u32)
+                .collect();
+            best_player_by(
+                e.players
+                    .iter()
+                    .filter(|p| !previous_picks.contains(&p.id) && is_counting(p)),
+            )
+            .map(|p| player_award(e, p))
+        })
+        .reduce(|best, award| {
+            if award.points > best.points {
+                award
+            } else {
+                best
+            }
+        });
+
+    let players_of_the_week = table
+        .entries
+        .iter()
+        .filter_map(|e| {
+            best_player_by(e.players.iter().filter(|p| is_counting(p))).map(|p| player_award(e, p))
+        })
+        .collect();
+
+    GwAwards {
+        gw,
+        gw_finished,
+        top_scorer,
+        biggest_bench_haul,
+        unluckiest_h2h_loss,
+        best_transfer,
+        players_of_the_week,
+    }
+}
+
+// Renders the awards as Markdown, suitable for pasting into a group chat
+pub fn render_markdown(awards: &GwAwards) -> String {
+    let mut md = String::new();
+    let _ = writeln!(md, "## Gameweek {} awards", awards.gw);
+    if !awards.gw_finished {
+        let _ = writeln!(
+            md,
+            "\n_The gameweek is not finished, the awards may change._"
+        );
+    }
+    md.push('\n');
+
+    if let Some(a) = &awards.top_scorer {
+        let _ = writeln!(
+            md,
+            "- **Top scorer:** {} ({}) with {} points",
+            a.team_name, a.owner_name, a.points
+        );
+    }
+    if let Some(a) = &awards.biggest_bench_haul {
+        let _ = writeln!(
+            md,
+            "- **Biggest bench haul:** {} ({}) left {} points on the bench",
+            a.team_name, a.owner_name, a.points
+        );
+    }
+    if let Some(a) = &awards.unluckiest_h2h_loss {
+        let _ = writeln!(
+            md,
+            "- **Unluckiest H2H loss:** {} lost {}-{} to {}",
+            a.loser.team_name, a.loser.points, a.winner.points, a.winner.team_name
+        );
+    }
+    if let Some(a) = &awards.best_transfer {
+        let _ = writeln!(
+            md,
+            "- **Best transfer:** {} scored {} points for {}",
+            a.player_name, a.points, a.team_name
+        );
+    }
+
+    if !awards.players_of_the_week.is_empty() {
+        let _ = writeln!(md, "\n### Players of the week\n");
+        let _ = writeln!(md, "| Team | Player | Points |");
+        let _ = writeln!(md, "| --- | --- | ---: |");
+        for a in awards.players_of_the_week.iter() {
+            let _ = writeln!(md, "| {} | {} | {} |", a.team_name, a.player_name, a.points);
+        }
+    }
+    md
+}
+
+fn compute_unluckiest_h2h_loss(
+    gw: u32,
+    endpoints: &FplEndpoints,
+    table: &LeagueTable,
+) -> Option<H2HLossAward> {
+    let entries: HashMap<u32, &TableEntry> =
+        table.entries.iter().map(|e| (e.team_code, e)).collect();
+
+    endpoints
+        .details
+        .matches
+        .as_ref()?
+        .iter()
+        .filter(|m| m.event == gw)
+        .filter_map(|m| {
+            let e1 = entries.get(&propcomp::get_entry_id_from_team_id(
+                endpoints,
+                m.league_entry_1,
+            ))?;
+            let e2 = entries.get(&propcomp::get_entry_id_from_team_id(
+                endpoints,
+                m.league_entry_2,
+            ))?;
+            match e1.gw_points.cmp(&e2.gw_points) {
+                std::cmp::Ordering::Less => Some((*e1, *e2)),
+                std::cmp::Ordering::Greater => Some((*e2, *e1)),
+                std::cmp::Ordering::Equal => None,
+            }
+        })
+        .reduce(|best, (loser, winner)| {
+            if loser.gw_points > best.0.gw_points {
+                (loser, winner)
+            } else {
+                best
+            }
+        })
+        .map(|(loser, winner)| H2HLossAward {
+            loser: team_award(loser, loser.gw_points),
+            winner: team_award(winner, winner.gw_points),
+        })
+}
+
+// Points of the players that were left on the bench and not subbed in
+fn bench_points(entry: &TableEntry) -> i32 {
+    entry
+        .players
+        .iter()
+        .filter(|p| p.play_status == PlayStatus::Benched)
+        .map(|p| p.points)
+        .sum()
+}
+
+fn is_counting(player: &TablePlayer) -> bool {
+    matches!(
+        player.play_status,
+        PlayStatus::Playing | PlayStatus::SubbedIn { .. }
+    )
+}
+
+// Returns the entry with the highest value, the first one in table order on ties
+fn best_entry_by<F>(entries: &[TableEntry], value: F) -> Option<&TableEntry>
+where
+    F: Fn(&TableEntry) -> i32,
+{
+    entries
+        .iter()
+        .reduce(|best, e| if value(e) > value(best) { e } else { best })
+}
+
+// Returns the player with the most points, the first one in pick order on ties
+fn best_player_by<'a, I>(players: I) -> Option<&'a TablePlayer>
+where
+    I: Iterator<Item = &'a TablePlayer>,
+{
+    players.reduce(|best, p| if p.points > best.points { p } else { best })
+}
+
+fn team_award(entry: &TableEntry, points: i32) -> TeamAward {
+    TeamAward {
+        team_code: entry.team_code,
+        team_name: entry.team_name.clone(),
+        owner_name: entry.owner_name.clone(),
+        points,
+    }
+}
+
+fn player_award(entry: &TableEntry, player: &TablePlayer) -> PlayerAward {
+    PlayerAward {
+        team_code: entry.team_code,
+        team_name: entry.team_name.clone(),
+        player_id: player.id,
+        player_name: player.display_name.clone(),
+        points: player.points,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Endpoint;
+    use crate::mockapi::MockFpl;
+    use crate::testdata;
+
+    fn awards_for(endpoints: &FplEndpoints, previous: &HashMap<u32, TeamGw>) -> GwAwards {
+        let table = computer::compute_new_league_table(endpoints).unwrap();
+        compute_gw_awards(testdata::CURRENT_GW, endpoints, &table, previous)
+    }
+
+    #[test]
+    fn top_scorer_and_bench_haul() {
+        let mut endpoints = testdata::endpoints(4, false);
+        testdata::set_player_points(&mut endpoints, testdata::player_id(3, 10), 20, 90);
+        testdata::set_player_points(&mut endpoints, testdata::player_id(2, 13), 15, 90);
+
+        let awards = awards_for(&endpoints, &HashMap::new());
+
+        let top_scorer = awards.top_scorer.unwrap();
+        assert_eq!(top_scorer.team_code, testdata::entry_id(3));
+        let bench = awards.biggest_bench_haul.unwrap();
+        assert_eq!(bench.team_code, testdata::entry_id(2));
+        assert!(bench.points >= 15);
+        assert!(awards.unluckiest_h2h_loss.is_none());
+        assert!(awards.best_transfer.is_none());
+        assert_eq!(awards.players_of_the_week.len(), 4);
+    }
+
+    #[test]
+    fn unluckiest_h2h_loss_is_highest_losing_score() {
+        let endpoints = testdata::endpoints(4, true);
+        let table = computer::compute_new_league_table(&endpoints).unwrap();
+        let awards = compute_gw_awards(testdata::CURRENT_GW, &endpoints, &table, &HashMap::new());
+
+        let entries: HashMap<u32, i32> = table
+            .entries
+            .iter()
+            .map(|e| (e.team_code, e.gw_points))
+            .collect();
+        let expected_loser_points = endpoints
+            .details
+            .matches
+            .as_ref()
+            .unwrap()
+            .iter()
+            .filter(|m| m.event == testdata::CURRENT_GW)
+            .map(|m| {
+                let p1 = entries[&testdata::entry_id(m.league_entry_1)];
+                let p2 = entries[&testdata::entry_id(m.league_entry_2)];
+                p1.min(p2)
+            })
+            .max()
+            .unwrap();
+
+        let loss = awards.unluckiest_h2h_loss.unwrap();
+        assert_eq!(loss.loser.points, expected_loser_points);
+        assert!(loss.winner.points > loss.loser.points);
+    }
+
+    #[test]
+    fn awards_are_final_once_fpl_finished_the_gameweek() {
+        let mut endpoints = testdata::endpoints(2, false);
+        // Every fixture is finished, but FPL has not finished the gameweek
+        assert!(!awards_for(&endpoints, &HashMap::new()).gw_finished);
+
+        endpoints.modify(|data| data.game.current_event_finished = Some(true));
+        assert!(awards_for(&endpoints, &HashMap::new()).gw_finished);
+
+        // No live data yet
+        endpoints.modify(|data| Arc::make_mut(&mut data.live).fixtures.clear());
+        assert!(!awards_for(&endpoints, &HashMap::new()).gw_finished);
+    }
+
+    #[test]
+    fn best_transfer_only_considers_new_players() {
+        let mut endpoints = testdata::endpoints(2, false);
+        let new_player = testdata::player_id(1, 6);
+        let old_star = testdata::player_id(2, 7);
+        testdata::set_player_points(&mut endpoints, new_player, 9, 90);
+        testdata::set_player_points(&mut endpoints, old_star, 18, 90);
+
+        let mut previous = endpoints.teams_gws.clone();
+        for team_gw in previous.values_mut() {
+            team_gw.picks.retain(|p| p.element as u32 != new_player);
+        }
+
+        let awards = awards_for(&endpoints, &previous);
+        let transfer = awards.best_transfer.as_ref().unwrap();
+        assert_eq!(transfer.player_id, new_player);
+        assert_eq!(transfer.points, 9);
+
+        let md = render_markdown(&awards);
+        assert!(md.contains("**Best transfer:** Player6 scored 9 points"));
+    }
+
+    #[tokio::test]
+    async fn awards_and_past_picks_are_fetched_once() {
+        const LEAGUE_ID: u32 = 1;
+        let endpoints = testdata::endpoints(2, false);
+        // The endpoints of the two gameweeks before the current one are the same as the current
+        let mut bodies: Vec<(Endpoint, String)> = Vec::new();
+        for gw in 1..=testdata::CURRENT_GW {
+            let mut gw_endpoints = endpoints.clone();
+            gw_endpoints.modify(|data| data.game.current_event = Some(gw));
+            bodies.extend(testdata::endpoint_bodies(&gw_endpoints, LEAGUE_ID));
+        }
+        let mock = MockFpl::start(
+            bodies
+                .into_iter()
+                .map(|(endpoint, body)| (endpoint.path(), body))
+                .collect(),
+        )
+        .await;
+        let client = mock.client();
+        let cache = AwardsCache::default();
+        let teams = vec![testdata::entry_id(1), testdata::entry_id(2)];
+        let team_gw = |gw| Endpoint::TeamGw { team: teams[0], gw };
+
+        let past_gw = testdata::CURRENT_GW - 1;
+        for _ in 0..2 {
+            let awards = fetch_and_compute_gw_awards(
+                &client, &cache, &endpoints, LEAGUE_ID, &teams, past_gw,
+            )
+            .await
+            .unwrap();
+            assert_eq!(awards.gw, past_gw);
+        }
+        assert_eq!(mock.requests(&Endpoint::Live { gw: past_gw }), 1);
+        assert_eq!(mock.requests(&team_gw(past_gw)), 1);
+        assert_eq!(mock.requests(&team_gw(past_gw - 1)), 1);
+
+        // The picks of the previous gameweek were fetched for the awards of that gameweek
+        let gw = testdata::CURRENT_GW;
+        fetch_and_compute_gw_awards(&client, &cache, &endpoints, LEAGUE_ID, &teams, gw)
+            .await
+            .unwrap();
+        assert_eq!(mock.requests(&team_gw(past_gw)), 1);
+        assert_eq!(mock.requests(&Endpoint::Live { gw }), 0);
+
+        // Another league starts over
+        fetch_and_compute_gw_awards(&client, &cache, &endpoints, LEAGUE_ID + 1, &teams, gw)
+            .await
+            .unwrap();
+        assert_eq!(mock.requests(&team_gw(past_gw)), 2);
+    }
+}
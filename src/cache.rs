@@ -0,0 +1,302 @@
+// Caching of serialized responses.
+//
+// Responses that are requested far more often than they change, such as the league table, are
+// serialized and compressed once per version and served with a strong ETag so clients polling the
+// api can revalidate with If-None-Match and get an empty 304 response when nothing changed. Every
+// content coding is a different representation with its own ETag, as RFC 9110 requires, and the
+// responses vary by Accept-Encoding so shared caches keep them apart. The compression layer of the
+// server leaves responses that already have a Content-Encoding alone.
+use std::hash::Hasher;
+use std::io::Write;
+
+use axum::body::Bytes;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use flate2::write::GzEncoder;
+use fnv::FnvHasher;
+use serde::Serialize;
+
+use crate::api::types;
+use crate::storage::LeagueTable;
+
+// Clients may keep the response but have to revalidate it before every use
+const CACHE_CONTROL: &str = "no-cache";
+
+// Brotli quality and window size, the body is compressed for every new version so the highest
+// quality is too slow
+const BROTLI_QUALITY: u32 = 5;
+const BROTLI_WINDOW: u32 = 22;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    Identity,
+    Gzip,
+    Brotli,
+}
+
+impl Encoding {
+    // The name in Accept-Encoding and Content-Encoding
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Identity => "identity",
+            Encoding::Gzip => "gzip",
+            Encoding::Brotli => "br",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedJson {
+    // Strong ETag computed from the json body, including the surrounding quotes. The body is
+    // hashed with FNV-1a, which unlike the hasher of std is the same in every build.
+    pub etag: String,
+    pub body: Bytes,
+    gzip: Bytes,
+    brotli: Bytes,
+}
+
+impl CachedJson {
+    pub fn new<T: Serialize>(value: &T) -> serde_json::Result<CachedJson> {
+        let body = serde_json::to_vec(value)?;
+        let mut hasher = FnvHasher::default();
+        hasher.write(&body);
+        Ok(CachedJson {
+            etag: format!("\"{:016x}\"", hasher.finish()),
+            gzip: Bytes::from(gzip(&body)),
+            brotli: Bytes::from(brotli(&body)),
+            body: Bytes::from(body),
+        })
+    }
+
+    // The ETag of the body in an encoding, the ETag of the json with the encoding appended
+    fn encoded_etag(&self, encoding: Encoding) -> String {
+        match encoding {
+            Encoding::Identity => self.etag.clone(),
+            _ => format!("{}-{}\"", self.etag.trim_end_matches('"'), encoding.name()),
+        }
+    }
+
+    // Responds with the best encoding the request accepts, and with 304 Not Modified if the
+    // request already has the current version in that encoding
+    pub fn to_response(&self, request_headers: &HeaderMap) -> Response {
+        let encoding = accepted_encoding(request_headers);
+        let etag = self.encoded_etag(encoding);
+        let cache_headers = [
+            (
+                header::ETAG,
+                HeaderValue::from_str(&etag).expect("ETag is always a valid header"),
+            ),
+            (
+                header::CACHE_CONTROL,
+                HeaderValue::from_static(CACHE_CONTROL),
+            ),
+            (
+                header::VARY,
+                HeaderValue::from_static(header::ACCEPT_ENCODING.as_str()),
+            ),
+        ];
+
+        let not_modified = request_headers
+            .get_all(header::IF_NONE_MATCH)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .any(|v| if_none_match(v, &etag));
+        if not_modified {
+            return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+        }
+
+        let content_type = (
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        let body = match encoding {
+            Encoding::Identity => {
+                return (
+                    StatusCode::OK,
+                    cache_headers,
+                    [content_type],
+                    self.body.clone(),
+                )
+                    .into_response()
+            }
+            Encoding::Gzip => self.gzip.clone(),
+            Encoding::Brotli => self.brotli.clone(),
+        };
+        let content_encoding = (
+            header::CONTENT_ENCODING,
+            HeaderValue::from_static(encoding.name()),
+        );
+        (
+            StatusCode::OK,
+            cache_headers,
+            [content_type, content_encoding],
+            body,
+        )
+            .into_response()
+    }
+}
+
+// The league table in the shape of the v1 api, and in the shape of the routes from before the api
+// was versioned
+#[derive(Debug, Clone)]
+pub struct CachedTable {
+    pub v1: CachedJson,
+    pub legacy: CachedJson,
+}
+
+impl CachedTable {
+    pub fn new(table: &LeagueTable) -> serde_json::Result<CachedTable> {
+        Ok(CachedTable {
+            v1: CachedJson::new(&types::LeagueTable::from(table))?,
+            legacy: CachedJson::new(table)?,
+        })
+    }
+}
+
+fn gzip(body: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(body)
+        .and_then(|_| encoder.finish())
+        .expect("Compressing in memory does not fail")
+}
+
+fn brotli(body: &[u8]) -> Vec<u8> {
+    let mut encoder =
+        brotli::CompressorWriter::new(Vec::new(), 4096, BROTLI_QUALITY, BROTLI_WINDOW);
+    encoder
+        .write_all(body)
+        .expect("Compressing in memory does not fail");
+    encoder.into_inner()
+}
+
+// The encoding with the highest quality value in Accept-Encoding, brotli before gzip when they are
+// equal. Identity if the request accepts neither.
+fn accepted_encoding(request_headers: &HeaderMap) -> Encoding {
+    let quality = |encoding: Encoding| -> f32 {
+        let mut wildcard = 0.0;
+        for value in request_headers
+            .get_all(header::ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+        {
+            for item in value.split(',') {
+                let mut parts = item.split(';');
+                let name = parts.next().unwrap_or("").trim();
+                let q = parts
+                    .filter_map(|p| p.trim().strip_prefix("q="))
+                    .find_map(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                if name.eq_ignore_ascii_case(encoding.name()) {
+                    return q;
+                }
+                if name == "*" {
+                    wildcard = q;
+                }
+            }
+        }
+        wildcard
+    };
+    let (brotli, gzip) = (quality(Encoding::Brotli), quality(Encoding::Gzip));
+    if brotli <= 0.0 && gzip <= 0.0 {
+        Encoding::Identity
+    } else if brotli >= gzip {
+        Encoding::Brotli
+    } else {
+        Encoding::Gzip
+    }
+}
+
+// Whether an If-None-Match header value matches the ETag. The header is either * or a comma
+// separated list of ETags, which are compared with the weak comparison as the RFC requires.
+fn if_none_match(header_value: &str, etag: &str) -> bool {
+    header_value.trim() == "*"
+        || header_value
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn not_modified_when_etag_matches() {
+        let cached = CachedJson::new(&serde_json::json!({ "points": 42 })).unwrap();
+        assert_eq!(
+            cached.etag,
+            CachedJson::new(&serde_json::json!({ "points": 42 }))
+                .unwrap()
+                .etag
+        );
+
+        let mut headers = HeaderMap::new();
+        let response = cached.to_response(&headers);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ETAG], cached.etag.as_str());
+
+        headers.insert(
+            header::IF_NONE_MATCH,
+            HeaderValue::from_str(&format!("\"other\", W/{}", cached.etag)).unwrap(),
+        );
+        let response = cached.to_response(&headers);
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let changed = CachedJson::new(&serde_json::json!({ "points": 43 })).unwrap();
+        assert_eq!(changed.to_response(&headers).status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn etags_are_the_same_in_every_build() {
+        let cached = CachedJson::new(&serde_json::json!({ "points": 42 })).unwrap();
+        // The FNV-1a hash of {"points":42}
+        assert_eq!(cached.etag, "\"db5e3ac28926263c\"");
+    }
+
+    #[test]
+    fn every_encoding_has_its_own_etag() {
+        let cached = CachedJson::new(&serde_json::json!({ "points": 42 })).unwrap();
+        let response = |accept_encoding: &str, if_none_match: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::ACCEPT_ENCODING,
+                HeaderValue::from_str(accept_encoding).unwrap(),
+            );
+            if let Some(etag) = if_none_match {
+                headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(etag).unwrap());
+            }
+            cached.to_response(&headers)
+        };
+
+        let gzip = response("gzip, deflate", None);
+        assert_eq!(gzip.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(gzip.headers()[header::VARY], "accept-encoding");
+        let gzip_etag = gzip.headers()[header::ETAG].to_str().unwrap().to_owned();
+        assert_ne!(gzip_etag, cached.etag);
+        let mut json = String::new();
+        flate2::read::GzDecoder::new(&cached.gzip[..])
+            .read_to_string(&mut json)
+            .unwrap();
+        assert_eq!(json.as_bytes(), &cached.body[..]);
+
+        let brotli = response("gzip;q=0.5, br", None);
+        assert_eq!(brotli.headers()[header::CONTENT_ENCODING], "br");
+        let identity = response("br;q=0, gzip;q=0", None);
+        assert!(!identity.headers().contains_key(header::CONTENT_ENCODING));
+        assert_eq!(identity.headers()[header::ETAG], cached.etag.as_str());
+
+        // The ETag of one encoding does not validate another
+        assert_eq!(
+            response("gzip", Some(&gzip_etag)).status(),
+            StatusCode::NOT_MODIFIED
+        );
+        assert_eq!(response("br", Some(&gzip_etag)).status(), StatusCode::OK);
+        assert_eq!(
+            response("identity", Some(&gzip_etag)).status(),
+            StatusCode::OK
+        );
+    }
+}
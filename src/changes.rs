@@ -0,0 +1,107 @@
+// Tracks what changed between two snapshots of the endpoints.
+//
+// The computer uses the change set to only recompute the league table entries and players that
+// are affected by the new endpoints, and publishes it together with the table so other consumers
+// can tell what happened since the last table without diffing the tables themselves.
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::storage::FplEndpoints;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+pub struct ChangeSet {
+    /// Every entry has to be recomputed. Happens on the first computation and when the gameweek,
+    /// the league details or bootstrap-static change.
+    pub full: bool,
+
+    /// The entry IDs of the teams whose picks or substitutions changed
+    pub teams_gws: BTreeSet<u32>,
+
+    /// The entry IDs of the teams whose team info (eg. season points) changed
+    pub teams_infos: BTreeSet<u32>,
+
+    /// The IDs of the players whose live stats or point sources changed
+    pub live_elements: BTreeSet<u32>,
+
+    /// The IDs of the live fixtures whose status, score or stats changed
+    pub fixtures: BTreeSet<u32>,
+
+    /// The entry IDs of the league table entries that were recomputed. Filled in by the computer.
+    pub entries: BTreeSet<u32>,
+
+    /// The IDs of the league table players that were recomputed. Filled in by the computer.
+    pub players: BTreeSet<u32>,
+}
+
+impl ChangeSet {
+    // A change set where everything is considered changed
+    pub fn full() -> ChangeSet {
+        ChangeSet {
+            full: true,
+            ..Default::default()
+        }
+    }
+
+    // Compares the current endpoints to the previous endpoints. A missing previous snapshot gives
+    // a full change set.
+    pub fn between(previous: Option<&FplEndpoints>, current: &FplEndpoints) -> ChangeSet {
+        let previous = match previous {
+            None => return ChangeSet::full(),
+            Some(previous) => previous,
+        };
+
+        if previous.game != current.game
+            || previous.details != current.details
+            || !(Arc::ptr_eq(&previous.static_info, &current.static_info)
+                || previous.static_info.elements == current.static_info.elements
+                    && previous.static_info.teams == current.static_info.teams)
+        {
+            return ChangeSet::full();
+        }
+
+        let mut changes = ChangeSet {
+            teams_gws: changed_keys(&previous.teams_gws, &current.teams_gws),
+            teams_infos: changed_keys(&previous.teams_infos, &current.teams_infos),
+            ..Default::default()
+        };
+
+        if !Arc::ptr_eq(&previous.live, &current.live) {
+            changes.live_elements = changed_keys(&previous.live.elements, &current.live.elements);
+            let previous_fixtures: HashMap<u32, _> =
+                previous.live.fixtures.iter().map(|f| (f.id, f)).collect();
+            changes.fixtures = current
+                .live
+                .fixtures
+                .iter()
+                .filter(|f| previous_fixtures.get(&f.id) != Some(f))
+                .map(|f| f.id)
+                .collect();
+        }
+
+        changes
+    }
+
+    // Whether nothing that the league table depends on changed
+    pub fn is_empty(&self) -> bool {
+        !self.full
+            && self.teams_gws.is_empty()
+            && self.teams_infos.is_empty()
+            && self.live_elements.is_empty()
+            && self.fixtures.is_empty()
+    }
+}
+
+// The keys that were added or whose values changed. Keys that were removed are not included.
+fn changed_keys<V: PartialEq>(
+    previous: &HashMap<u32, V>,
+    current: &HashMap<u32, V>,
+) -> BTreeSet<u32> {
+    current
+        .iter()
+        .filter(|(k, v)| previous.get(k) != Some(v))
+        .map(|(k, _)| *k)
+        .collect()
+}
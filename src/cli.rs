@@ -0,0 +1,357 @@
+// Subcommands that fetch the league once, print the result and exit, without starting the server.
+//
+// Every command prints a table formatted for the terminal by default, or json with --json.
+use std::path::PathBuf;
+
+use clap::Subcommand;
+use serde::Serialize;
+
+use crate::client::EndpointKind;
+use crate::initializer::{AppConfig, ConfigLayers, LoadedConfig};
+
+use crate::fixtures::{self, FixtureCentre, FixtureStatus};
+use crate::players::{self, PlayerInfo};
+use crate::standings::{self, AllPlayStandings};
+use crate::storage::table::{Entry, PlayStatus, RankMovement, Scoring};
+use crate::storage::{FplEndpoints, LeagueTable};
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Start the web server (default)
+    Serve,
+
+    /// Print the live league table
+    Table {
+        /// Print json instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print a league team and its players, by team code (entry ID)
+    Team {
+        id: u32,
+        /// Print json instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print a Premier League player, by player ID
+    Player {
+        id: u32,
+        /// Print json instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print the Premier League fixtures of the current gameweek
+    Fixtures {
+        /// Print json instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print everything that is computed for the league as json
+    Snapshot,
+
+    /// Show the live league table, the players of each team and the latest point events in the
+    /// terminal, updated on every fetch
+    Tui {
+        /// Write the logs to this file. Logs are discarded if not set, since they would
+        /// overwrite the screen
+        #[arg(long)]
+        log_file: Option<String>,
+    },
+
+    /// Download the endpoints of the league from the FPL api into a local database, readable with
+    /// local_fetch. Existing files are kept unless they are overwritten or refreshed.
+    Record {
+        /// The league to record, instead of the league in the config
+        #[arg(long)]
+        league_id: Option<u32>,
+
+        /// The root directory of the database
+        #[arg(short, long, default_value = "db/api")]
+        out: PathBuf,
+
+        /// Record every gameweek up to the current one, not only the current one
+        #[arg(long)]
+        all_gws: bool,
+
+        /// Record these gameweeks instead of the current one
+        #[arg(long, value_delimiter = ',')]
+        gw: Vec<u32>,
+
+        /// Overwrite all existing files
+        #[arg(long)]
+        overwrite: bool,
+
+        /// Overwrite the existing files of these endpoints, eg. --refresh live,team-gw
+        #[arg(long, value_enum, value_delimiter = ',')]
+        refresh: Vec<EndpointKind>,
+    },
+
+    /// Inspect the config without starting the server
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommand {
+    /// Validate the config and print the effective config, with where each setting was set
+    Check,
+}
+
+// Everything that is computed from a single fetch of the endpoints
+#[derive(Serialize)]
+pub struct Snapshot<'a> {
+    pub table: &'a LeagueTable,
+    pub fixtures: FixtureCentre,
+    pub all_play_standings: Option<AllPlayStandings>,
+}
+
+// Runs a command that prints the league. Returns an error message if the command can't be run, eg. if
+// the team or player does not exist.
+pub fn run(command: Command, endpoints: &FplEndpoints, table: &LeagueTable) -> Result<(), String> {
+    let output = match command {
+        Command::Serve | Command::Tui { .. } | Command::Record { .. } | Command::Config { .. } => {
+            return Err(format!("{:?} does not print the league", command))
+        }
+        Command::Table { json } => {
+            if json {
+                to_json(table)?
+            } else {
+                render_table(table)
+            }
+        }
+        Command::Team { id, json } => {
+            let entry = table
+                .entries
+                .iter()
+                .find(|e| e.team_code == id)
+                .ok_or(format!("Team {} is not in the league", id))?;
+            if json {
+                to_json(entry)?
+            } else {
+                render_team(entry)
+            }
+        }
+        Command::Player { id, json } => {
+            let player = players::compute_player_info(endpoints, table, id)
+                .ok_or(format!("Player {} does not exist", id))?;
+            if json {
+                to_json(&player)?
+            } else {
+                render_player(&player, table)
+            }
+        }
+        Command::Fixtures { json } => {
+            let centre = fixtures::compute_fixture_centre(endpoints, table);
+            if json {
+                to_json(&centre)?
+            } else {
+                render_fixtures(&centre)
+            }
+        }
+        Command::Snapshot => to_json(&Snapshot {
+            table,
+            fixtures: fixtures::compute_fixture_centre(endpoints, table),
+            all_play_standings: standings::compute_all_play_standings(endpoints, table),
+        })?,
+    };
+    println!("{}", output);
+    Ok(())
+}
+
+// Prints the effective config and returns every problem with it
+pub fn check_config(layers: &ConfigLayers) -> Result<(), String> {
+    let loaded = AppConfig::read(layers).map_err(|errors| render_config_errors(&errors))?;
+    print!("{}", render_config(&loaded));
+    loaded
+        .config
+        .validate()
+        .map_err(|errors| render_config_errors(&errors))?;
+    eprintln!("The config is valid");
+    Ok(())
+}
+
+pub fn render_config_errors(errors: &[String]) -> String {
+    let mut out = String::from("The config is invalid:");
+    for error in errors {
+        out.push_str(&format!("\n  - {}", error));
+    }
+    out
+}
+
+fn render_config(loaded: &LoadedConfig) -> String {
+    let mut out = loaded.config.to_toml();
+    if loaded.origins.is_empty() {
+        out.push_str("\n# Every setting is a default\n");
+        return out;
+    }
+    out.push_str("\n# Settings that are not defaults, and where they were set\n");
+    for (key, origin) in &loaded.origins {
+        out.push_str(&format!("#   {:<28} {}\n", key, origin));
+    }
+    out
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string_pretty(value).map_err(|e| format!("Failed to serialize output: {}", e))
+}
+
+pub fn render_table(table: &LeagueTable) -> String {
+    let h2h = matches!(table.scoring, Scoring::H2H);
+    let mut out = format!("{}\n", table.name);
+    out.push_str(&format!(
+        "{:>3} {:<24} {:<20} {:>4} {:>5} {:>6} {:>6}{}\n",
+        "#",
+        "Team",
+        "Owner",
+        "GW",
+        "Proj",
+        "Total",
+        "Proj",
+        if h2h { "  H2H" } else { "" }
+    ));
+    for (i, entry) in table.entries.iter().enumerate() {
+        let movement = match entry.rank_info.as_ref().map(|r| r.live_movement) {
+            Some(RankMovement::Up) => "+",
+            Some(RankMovement::Down) => "-",
+            _ => " ",
+        };
+        out.push_str(&format!(
+            "{:>2}{} {:<24} {:<20} {:>4} {:>5} {:>6} {:>6}{}\n",
+            i + 1,
+            movement,
+            truncate(&entry.team_name, 24),
+            truncate(&entry.owner_name, 20),
+            entry.gw_points,
+            entry.gw_projected_points,
+            entry.total_points,
+            entry.total_projected_points,
+            entry
+                .h2h_info
+                .as_ref()
+                .filter(|_| h2h)
+                .map_or(String::new(), |info| format!("{:>5}", info.points))
+        ));
+    }
+    out
+}
+
+pub fn render_team(entry: &Entry) -> String {
+    let mut out = format!(
+        "{} ({}): {} points, {} projected\n",
+        entry.team_name, entry.owner_name, entry.gw_points, entry.gw_projected_points
+    );
+    out.push_str(&format!(
+        "{:>2}  {:<3} {:<20} {:<4} {:>4} {:>5}  {}\n",
+        "", "Pos", "Player", "Club", "Pts", "Proj", "Status"
+    ));
+    let mut players: Vec<_> = entry.players.iter().collect();
+    players.sort_by_key(|p| p.pick_number);
+    for p in players {
+        out.push_str(&format!(
+            "{:>2}  {:<3} {:<20} {:<4} {:>4} {:>5}  {}\n",
+            p.pick_number,
+            format!("{:?}", p.team_pos),
+            truncate(&p.display_name, 20),
+            p.team.short_name,
+            p.points,
+            p.projected_points,
+            play_status_text(&p.play_status)
+        ));
+    }
+    out
+}
+
+pub fn play_status_text(play_status: &PlayStatus) -> String {
+    match play_status {
+        PlayStatus::Playing => String::from("playing"),
+        PlayStatus::Benched => String::from("benched"),
+        PlayStatus::SubbedIn { subbed_with } => format!("subbed in for {}", subbed_with),
+        PlayStatus::SubbedOff { subbed_with } => format!("subbed off for {}", subbed_with),
+        PlayStatus::Unknown => String::new(),
+    }
+}
+
+fn render_player(player: &PlayerInfo, table: &LeagueTable) -> String {
+    let owner = player
+        .owner
+        .and_then(|code| table.entries.iter().find(|e| e.team_code == code))
+        .map_or(String::from("-"), |e| e.team_name.clone());
+    let mut out = format!(
+        "{} ({:?}, {})\n",
+        player.full_name, player.position, player.team.name
+    );
+    out.push_str(&format!("Owner:           {}\n", owner));
+    out.push_str(&format!("Status:          {:?}\n", player.status));
+    if let Some(news) = &player.news {
+        out.push_str(&format!("News:            {}\n", news));
+    }
+    out.push_str(&format!("GW points:       {}\n", player.gw_points));
+    out.push_str(&format!("Season points:   {}\n", player.season_points));
+    out.push_str(&format!("Form:            {:.1}\n", player.form));
+    out.push_str(&format!("Expected points: {:.1}\n", player.expected_points));
+    out
+}
+
+fn render_fixtures(centre: &FixtureCentre) -> String {
+    let mut out = format!("Gameweek {}\n", centre.gw);
+    for f in centre.fixtures.iter() {
+        let score = |s: Option<i32>| s.map_or(String::from("-"), |s| s.to_string());
+        let status = match f.status {
+            FixtureStatus::NotStarted => f.kickoff_time.clone().unwrap_or_default(),
+            FixtureStatus::Live => format!("{}'", f.minutes),
+            FixtureStatus::FinishedProvisional => String::from("FT (bonus not confirmed)"),
+            FixtureStatus::Finished => String::from("FT"),
+        };
+        out.push_str(&format!(
+            "{:>4} {:>2} - {:<2} {:<4} {}\n",
+            f.home.short_name,
+            score(f.home.score),
+            score(f.away.score),
+            f.away.short_name,
+            status
+        ));
+        for p in f.league_players.iter().filter(|p| p.counting) {
+            out.push_str(&format!(
+                "       {:<20} {:>3}  {}\n",
+                truncate(&p.display_name, 20),
+                p.projected_points,
+                p.team_name
+            ));
+        }
+    }
+    out
+}
+
+fn truncate(s: &str, max_chars: usize) -> String {
+    s.chars().take(max_chars).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer;
+    use crate::testdata;
+
+    #[test]
+    fn render_table_and_team() {
+        let endpoints = testdata::endpoints(4, true);
+        let table = computer::compute_new_league_table(&endpoints).unwrap();
+
+        let rendered = render_table(&table);
+        assert_eq!(rendered.lines().count(), 2 + 4);
+        assert!(table
+            .entries
+            .iter()
+            .all(|e| rendered.contains(&e.team_name)));
+
+        let rendered = render_team(&table.entries[0]);
+        assert_eq!(rendered.lines().count(), 2 + testdata::SQUAD_SIZE as usize);
+        assert!(rendered.contains("playing"));
+    }
+}
@@ -0,0 +1,383 @@
+// Detection of changes in the json of the FPL api that the endpoint structs don't know about.
+//
+// FPL changes its json without notice. Fields the structs don't have are ignored, and optional
+// fields with an unexpected type are read as missing, so the table keeps working. Both are
+// collected per endpoint so the changes are noticed before a required field breaks the table.
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{ClientError, Endpoint, EndpointKind};
+use crate::metrics;
+
+// The number of mismatching fields that are read as missing before giving up on a response
+const MAX_TYPE_MISMATCHES: usize = 20;
+
+// The drift found in a single response
+#[derive(Debug, Default, PartialEq)]
+pub struct Drift {
+    pub unknown_fields: BTreeSet<String>,
+    // The error message by field
+    pub type_mismatches: BTreeMap<String, String>,
+    // Whether the response could not be deserialized at all
+    pub failed: bool,
+}
+
+impl Drift {
+    pub fn is_empty(&self) -> bool {
+        self.unknown_fields.is_empty() && self.type_mismatches.is_empty() && !self.failed
+    }
+}
+
+// The drift seen for an endpoint since the server started
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct EndpointDrift {
+    // Fields in the json that the structs do not have, eg. elements[].new_field. Array indices
+    // are written as [] and numeric map keys (eg. player IDs) as *
+    pub unknown_fields: BTreeSet<String>,
+
+    // Fields whose type in the json does not match the structs, with the latest error
+    pub type_mismatches: BTreeMap<String, String>,
+
+    // The number of responses with drift
+    pub responses: u64,
+
+    // The number of responses that could not be deserialized because of the drift
+    pub failures: u64,
+
+    // The latest response with drift, in seconds since the unix epoch
+    pub last_seen: u64,
+}
+
+#[derive(Default)]
+pub struct SchemaDrift {
+    endpoints: Mutex<BTreeMap<EndpointKind, EndpointDrift>>,
+}
+
+impl SchemaDrift {
+    // Adds the drift of a response. Logs the fields that were not seen before, so an upstream
+    // change is logged once and not on every fetch.
+    pub fn record(&self, kind: EndpointKind, drift: Drift) {
+        if drift.is_empty() {
+            return;
+        }
+        let endpoint = kind.name();
+        metrics::increment(
+            "fpl_schema_drift_responses_total",
+            &[("endpoint", endpoint)],
+        );
+        if drift.failed {
+            metrics::increment("fpl_schema_drift_failures_total", &[("endpoint", endpoint)]);
+        }
+
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let seen = endpoints.entry(kind).or_default();
+        let new_unknown: Vec<&String> = drift
+            .unknown_fields
+            .iter()
+            .filter(|f| !seen.unknown_fields.contains(*f))
+            .collect();
+        let new_mismatches: Vec<(&String, &String)> = drift
+            .type_mismatches
+            .iter()
+            .filter(|(f, _)| !seen.type_mismatches.contains_key(*f))
+            .collect();
+        if !new_unknown.is_empty() || !new_mismatches.is_empty() {
+            tracing::warn!(
+                endpoint,
+                unknown_fields = ?new_unknown,
+                type_mismatches = ?new_mismatches,
+                failed = drift.failed,
+                "Schema drift in the FPL api"
+            );
+        }
+
+        seen.unknown_fields.extend(drift.unknown_fields);
+        seen.type_mismatches.extend(drift.type_mismatches);
+        seen.responses += 1;
+        if drift.failed {
+            seen.failures += 1;
+        }
+        seen.last_seen = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+    }
+
+    pub fn report(&self) -> BTreeMap<EndpointKind, EndpointDrift> {
+        self.endpoints.lock().unwrap().clone()
+    }
+}
+
+// Deserializes a response and collects the drift from the structs on the way. Optional fields
+// with an unexpected type are read as missing, while a mismatch in a required field fails with
+// the path of the field.
+pub fn deserialize_with_drift<T: DeserializeOwned>(
+    endpoint: &Endpoint,
+    body: &str,
+) -> (Result<T, ClientError>, Drift) {
+    let mut drift = Drift::default();
+    let mut value: Value = match serde_json::from_str(body) {
+        Ok(v) => v,
+        Err(e) => return (Err(ClientError::from_json(endpoint, None, e)), drift),
+    };
+
+    // The first error of each field, a retry only adds that null is not allowed
+    let mut errors: BTreeMap<String, serde_json::Error> = BTreeMap::new();
+    loop {
+        let mut unknown_fields = BTreeSet::new();
+        let mut ignored = |path: serde_ignored::Path| {
+            unknown_fields.insert(format_ignored_path(&path));
+        };
+        let deserializer = serde_ignored::Deserializer::new(&value, &mut ignored);
+        let result = serde_path_to_error::deserialize::<_, T>(deserializer);
+        drift.unknown_fields = unknown_fields;
+        let error = match result {
+            Ok(v) => {
+                drift.type_mismatches = mismatch_messages(&errors);
+                return (Ok(v), drift);
+            }
+            Err(e) => e,
+        };
+
+        let path = format_error_path(error.path());
+        let retry = errors.len() < MAX_TYPE_MISMATCHES
+            && !errors.contains_key(&path)
+            && null_mismatching(&mut value, error.path());
+        errors.entry(path.clone()).or_insert(error.into_inner());
+        if !retry {
+            drift.type_mismatches = mismatch_messages(&errors);
+            drift.failed = true;
+            let source = errors
+                .remove(&path)
+                .expect("The error of the field was just added");
+            let error = ClientError::from_json(endpoint, Some(path), source);
+            return (Err(error), drift);
+        }
+    }
+}
+
+fn mismatch_messages(errors: &BTreeMap<String, serde_json::Error>) -> BTreeMap<String, String> {
+    errors
+        .iter()
+        .map(|(path, e)| (path.clone(), e.to_string()))
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    AnyIndex,
+    AnyKey,
+}
+
+fn key_segment(key: &str) -> Segment {
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_digit()) {
+        Segment::AnyKey
+    } else {
+        Segment::Key(key.to_string())
+    }
+}
+
+fn error_segments(path: &serde_path_to_error::Path) -> Vec<Segment> {
+    path.iter()
+        .filter_map(|s| match s {
+            serde_path_to_error::Segment::Seq { .. } => Some(Segment::AnyIndex),
+            serde_path_to_error::Segment::Map { key } => Some(key_segment(key)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn format_segments(segments: &[Segment]) -> String {
+    let mut out = String::new();
+    for segment in segments {
+        let key = match segment {
+            Segment::AnyIndex => {
+                out.push_str("[]");
+                continue;
+            }
+            Segment::Key(key) => key.as_str(),
+            Segment::AnyKey => "*",
+        };
+        if !out.is_empty() {
+            out.push('.');
+        }
+        out.push_str(key);
+    }
+    if out.is_empty() {
+        out.push('.');
+    }
+    out
+}
+
+fn format_error_path(path: &serde_path_to_error::Path) -> String {
+    format_segments(&error_segments(path))
+}
+
+fn format_ignored_path(path: &serde_ignored::Path) -> String {
+    fn collect(path: &serde_ignored::Path, segments: &mut Vec<Segment>) {
+        match path {
+            serde_ignored::Path::Root => {}
+            serde_ignored::Path::Seq { parent, .. } => {
+                collect(parent, segments);
+                segments.push(Segment::AnyIndex);
+            }
+            serde_ignored::Path::Map { parent, key } => {
+                collect(parent, segments);
+                segments.push(key_segment(key));
+            }
+            serde_ignored::Path::Some { parent }
+            | serde_ignored::Path::NewtypeStruct { parent }
+            | serde_ignored::Path::NewtypeVariant { parent } => collect(parent, segments),
+        }
+    }
+    let mut segments = Vec::new();
+    collect(path, &mut segments);
+    format_segments(&segments)
+}
+
+// Replaces the value at the path of an error with null, in every element of the arrays and maps
+// on the way that has a value of the same json type there. Returns false if nothing was replaced.
+fn null_mismatching(value: &mut Value, path: &serde_path_to_error::Path) -> bool {
+    let Some(mismatching) = exact_value(value, path).map(json_type) else {
+        return false;
+    };
+    if mismatching == "null" {
+        return false;
+    }
+    null_matching(value, &error_segments(path), mismatching)
+}
+
+fn exact_value<'a>(value: &'a Value, path: &serde_path_to_error::Path) -> Option<&'a Value> {
+    let mut current = value;
+    for segment in path.iter() {
+        current = match segment {
+            serde_path_to_error::Segment::Seq { index } => current.get(index)?,
+            serde_path_to_error::Segment::Map { key } => current.get(key)?,
+            _ => current,
+        };
+    }
+    Some(current)
+}
+
+fn null_matching(value: &mut Value, segments: &[Segment], json_type_name: &str) -> bool {
+    let Some((first, rest)) = segments.split_first() else {
+        if json_type(value) == json_type_name {
+            *value = Value::Null;
+            return true;
+        }
+        return false;
+    };
+    match (first, value) {
+        (Segment::AnyIndex, Value::Array(items)) => {
+            null_matching_all(items.iter_mut(), rest, json_type_name)
+        }
+        (Segment::AnyKey, Value::Object(map)) => {
+            null_matching_all(map.values_mut(), rest, json_type_name)
+        }
+        (Segment::Key(key), Value::Object(map)) => map
+            .get_mut(key)
+            .is_some_and(|v| null_matching(v, rest, json_type_name)),
+        _ => false,
+    }
+}
+
+// Goes through every value, unlike any() which stops at the first match
+fn null_matching_all<'a>(
+    values: impl Iterator<Item = &'a mut Value>,
+    segments: &[Segment],
+    json_type_name: &str,
+) -> bool {
+    let mut any = false;
+    for value in values {
+        any |= null_matching(value, segments, json_type_name);
+    }
+    any
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug)]
+    struct Element {
+        id: u32,
+        news: Option<String>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Root {
+        elements: Vec<Element>,
+    }
+
+    #[test]
+    fn unknown_fields_and_optional_mismatches_are_collected() {
+        let body = r#"{
+            "elements": [
+                {"id": 1, "news": "Knock", "new_field": 1},
+                {"id": 2, "news": 5},
+                {"id": 3, "news": 7}
+            ],
+            "extra": {"a": 1}
+        }"#;
+
+        let (result, drift) = deserialize_with_drift::<Root>(&Endpoint::Static, body);
+
+        let root = result.unwrap();
+        assert_eq!(
+            root.elements.iter().map(|e| e.id).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(root.elements[0].news.as_deref(), Some("Knock"));
+        assert_eq!(root.elements[1].news, None);
+        assert_eq!(
+            drift.unknown_fields,
+            BTreeSet::from([String::from("elements[].new_field"), String::from("extra")])
+        );
+        assert_eq!(
+            drift.type_mismatches.keys().collect::<Vec<_>>(),
+            vec!["elements[].news"]
+        );
+        assert!(!drift.failed);
+    }
+
+    #[test]
+    fn required_mismatch_fails_with_the_path() {
+        let body = r#"{"elements": [{"id": 1}, {"id": "2"}]}"#;
+
+        let (result, drift) = deserialize_with_drift::<Root>(&Endpoint::Static, body);
+
+        match result {
+            Err(ClientError::Json { path, .. }) => {
+                assert_eq!(path.as_deref(), Some("elements[].id"))
+            }
+            other => panic!("Expected a json error, got {:?}", other),
+        }
+        assert!(drift.failed);
+
+        let drift_store = SchemaDrift::default();
+        drift_store.record(EndpointKind::Static, drift);
+        let report = drift_store.report();
+        assert_eq!(report[&EndpointKind::Static].failures, 1);
+        assert!(report[&EndpointKind::Static]
+            .type_mismatches
+            .contains_key("elements[].id"));
+    }
+}
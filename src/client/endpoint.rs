@@ -0,0 +1,71 @@
+use std::fmt;
+
+use serde::Serialize;
+
+// The FPL draft api endpoints used by the client. The path of an endpoint is relative to the api
+// base, both for the web api and for a local database of recorded files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    Game,
+    Static,
+    Details { league: u32 },
+    TeamInfo { team: u32 },
+    TeamGw { team: u32, gw: u32 },
+    Live { gw: u32 },
+}
+
+// The kind of an endpoint, without the IDs that identify a specific instance of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum EndpointKind {
+    Game,
+    Static,
+    Details,
+    TeamInfo,
+    TeamGw,
+    Live,
+}
+
+impl Endpoint {
+    pub fn path(&self) -> String {
+        match self {
+            Endpoint::Game => String::from("/game"),
+            Endpoint::Static => String::from("/bootstrap-static"),
+            Endpoint::Details { league } => format!("/league/{}/details", league),
+            Endpoint::TeamInfo { team } => format!("/entry/{}/public", team),
+            Endpoint::TeamGw { team, gw } => format!("/entry/{}/event/{}", team, gw),
+            Endpoint::Live { gw } => format!("/event/{}/live", gw),
+        }
+    }
+
+    pub fn kind(&self) -> EndpointKind {
+        match self {
+            Endpoint::Game => EndpointKind::Game,
+            Endpoint::Static => EndpointKind::Static,
+            Endpoint::Details { .. } => EndpointKind::Details,
+            Endpoint::TeamInfo { .. } => EndpointKind::TeamInfo,
+            Endpoint::TeamGw { .. } => EndpointKind::TeamGw,
+            Endpoint::Live { .. } => EndpointKind::Live,
+        }
+    }
+}
+
+impl EndpointKind {
+    // The name of the kind as used on the command line and in reports, eg. team-gw
+    pub fn name(&self) -> &'static str {
+        match self {
+            EndpointKind::Game => "game",
+            EndpointKind::Static => "static",
+            EndpointKind::Details => "details",
+            EndpointKind::TeamInfo => "team-info",
+            EndpointKind::TeamGw => "team-gw",
+            EndpointKind::Live => "live",
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path())
+    }
+}
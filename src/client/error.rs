@@ -0,0 +1,282 @@
+// The errors of the client. Errors from fetching an endpoint carry the endpoint, so a log line
+// says which team or gameweek failed, and tell the fetcher whether fetching again can help.
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use reqwest::StatusCode;
+
+use super::{Endpoint, EndpointKind};
+
+// The number of characters kept from the body of an error response
+const BODY_EXCERPT_CHARS: usize = 200;
+
+// The underlying errors are behind an Arc so the client errors can be cloned
+#[derive(Debug, Clone)]
+pub enum ClientError {
+    // No response within the timeout of the client
+    Timeout {
+        endpoint: Endpoint,
+        source: Arc<reqwest::Error>,
+    },
+    // The api could not be connected to, eg. DNS or TLS failures
+    Connect {
+        endpoint: Endpoint,
+        source: Arc<reqwest::Error>,
+    },
+    // Any other failure to send the request or read the response
+    Request {
+        endpoint: Endpoint,
+        source: Arc<reqwest::Error>,
+    },
+    // The api answered with an unsuccessful status
+    Status {
+        endpoint: Endpoint,
+        status: StatusCode,
+        body_excerpt: String,
+    },
+    // The body is not valid json or does not match the endpoint struct. The path is the field
+    // that did not match and the line is where the json is invalid, when they are known.
+    Json {
+        endpoint: Endpoint,
+        path: Option<String>,
+        line: Option<usize>,
+        source: Arc<serde_json::Error>,
+    },
+    // The file of the endpoint is missing from a local database
+    FileNotFound {
+        endpoint: Endpoint,
+        path: PathBuf,
+    },
+    // The file of the endpoint could not be read or written
+    File {
+        endpoint: Endpoint,
+        path: PathBuf,
+        source: Arc<io::Error>,
+    },
+    // The client could not be created from its configuration
+    Setup(String),
+    Internal(String),
+}
+
+// What to do about a failed fetch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryAdvice {
+    // The failure is likely temporary, fetch again soon
+    Retry,
+    // The api is overloaded or rate limiting, wait longer before each new attempt
+    BackOff,
+    // Fetching again gives the same result
+    GiveUp,
+}
+
+impl ClientError {
+    pub fn from_reqwest(endpoint: &Endpoint, e: reqwest::Error) -> ClientError {
+        let endpoint = *endpoint;
+        let source = Arc::new(e);
+        if source.is_timeout() {
+            ClientError::Timeout { endpoint, source }
+        } else if source.is_connect() {
+            ClientError::Connect { endpoint, source }
+        } else {
+            ClientError::Request { endpoint, source }
+        }
+    }
+
+    pub fn from_status(endpoint: &Endpoint, status: StatusCode, body: &str) -> ClientError {
+        ClientError::Status {
+            endpoint: *endpoint,
+            status,
+            body_excerpt: body.chars().take(BODY_EXCERPT_CHARS).collect(),
+        }
+    }
+
+    pub fn from_io(endpoint: &Endpoint, path: PathBuf, e: io::Error) -> ClientError {
+        match e.kind() {
+            io::ErrorKind::NotFound => ClientError::FileNotFound {
+                endpoint: *endpoint,
+                path,
+            },
+            _ => ClientError::File {
+                endpoint: *endpoint,
+                path,
+                source: Arc::new(e),
+            },
+        }
+    }
+
+    pub fn from_json(
+        endpoint: &Endpoint,
+        path: Option<String>,
+        e: serde_json::Error,
+    ) -> ClientError {
+        // Errors from deserializing an already parsed value have no position
+        let line = (e.line() > 0).then_some(e.line());
+        ClientError::Json {
+            endpoint: *endpoint,
+            path,
+            line,
+            source: Arc::new(e),
+        }
+    }
+
+    pub fn endpoint(&self) -> Option<&Endpoint> {
+        match self {
+            ClientError::Timeout { endpoint, .. }
+            | ClientError::Connect { endpoint, .. }
+            | ClientError::Request { endpoint, .. }
+            | ClientError::Status { endpoint, .. }
+            | ClientError::Json { endpoint, .. }
+            | ClientError::FileNotFound { endpoint, .. }
+            | ClientError::File { endpoint, .. } => Some(endpoint),
+            ClientError::Setup(_) | ClientError::Internal(_) => None,
+        }
+    }
+
+    pub fn endpoint_kind(&self) -> Option<EndpointKind> {
+        self.endpoint().map(Endpoint::kind)
+    }
+
+    pub fn retry_advice(&self) -> RetryAdvice {
+        match self {
+            ClientError::Timeout { .. }
+            | ClientError::Connect { .. }
+            | ClientError::Request { .. } => RetryAdvice::Retry,
+            ClientError::Status { status, .. } => match *status {
+                StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+                    RetryAdvice::BackOff
+                }
+                StatusCode::REQUEST_TIMEOUT => RetryAdvice::Retry,
+                s if s.is_server_error() => RetryAdvice::Retry,
+                _ => RetryAdvice::GiveUp,
+            },
+            ClientError::Json { .. }
+            | ClientError::FileNotFound { .. }
+            | ClientError::File { .. }
+            | ClientError::Setup(_)
+            | ClientError::Internal(_) => RetryAdvice::GiveUp,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::Timeout { endpoint, source } => {
+                write!(f, "Timed out fetching {}: {}", endpoint, source)
+            }
+            ClientError::Connect { endpoint, source } => {
+                write!(f, "Could not connect to fetch {}: {}", endpoint, source)
+            }
+            ClientError::Request { endpoint, source } => {
+                write!(f, "Request for {} failed: {}", endpoint, source)
+            }
+            ClientError::Status {
+                endpoint,
+                status,
+                body_excerpt,
+            } => write!(f, "HTTP {} from {}: {}", status, endpoint, body_excerpt),
+            ClientError::Json {
+                endpoint,
+                path,
+                line,
+                source,
+            } => {
+                write!(f, "Invalid json from {}", endpoint)?;
+                if let Some(path) = path {
+                    write!(f, " in field {}", path)?;
+                }
+                if let Some(line) = line {
+                    write!(f, " at line {}", line)?;
+                }
+                write!(f, ": {}", source)
+            }
+            ClientError::FileNotFound { endpoint, path } => {
+                write!(f, "No file for {} at {}", endpoint, path.display())
+            }
+            ClientError::File {
+                endpoint,
+                path,
+                source,
+            } => write!(
+                f,
+                "Could not access the file for {} at {}: {}",
+                endpoint,
+                path.display(),
+                source
+            ),
+            ClientError::Setup(msg) => write!(f, "Could not create client: {}", msg),
+            ClientError::Internal(msg) => write!(f, "Internal client error: {}", msg),
+        }
+    }
+}
+
+impl error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ClientError::Timeout { source, .. }
+            | ClientError::Connect { source, .. }
+            | ClientError::Request { source, .. } => Some(source.as_ref()),
+            ClientError::Json { source, .. } => Some(source.as_ref()),
+            ClientError::File { source, .. } => Some(source.as_ref()),
+            ClientError::Status { .. }
+            | ClientError::FileNotFound { .. }
+            | ClientError::Setup(_)
+            | ClientError::Internal(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+
+    #[test]
+    fn errors_advise_on_retries() {
+        let live = Endpoint::Live { gw: 3 };
+        let status = |s| ClientError::from_status(&live, s, "");
+        assert_eq!(
+            status(StatusCode::TOO_MANY_REQUESTS).retry_advice(),
+            RetryAdvice::BackOff
+        );
+        assert_eq!(
+            status(StatusCode::BAD_GATEWAY).retry_advice(),
+            RetryAdvice::Retry
+        );
+        assert_eq!(
+            status(StatusCode::NOT_FOUND).retry_advice(),
+            RetryAdvice::GiveUp
+        );
+
+        let missing = io::Error::from(io::ErrorKind::NotFound);
+        let error = ClientError::from_io(&live, PathBuf::from("/db/event/3/live"), missing);
+        assert!(matches!(error, ClientError::FileNotFound { .. }));
+        assert_eq!(error.retry_advice(), RetryAdvice::GiveUp);
+        assert_eq!(error.endpoint_kind(), Some(EndpointKind::Live));
+    }
+
+    #[test]
+    fn json_errors_keep_the_line_and_source() {
+        let e = serde_json::from_str::<serde_json::Value>("{\n\"a\": }").unwrap_err();
+        let error = ClientError::from_json(&Endpoint::Game, None, e);
+
+        assert!(matches!(error, ClientError::Json { line: Some(2), .. }));
+        assert!(error.source().is_some());
+        assert!(error
+            .to_string()
+            .starts_with("Invalid json from /game at line 2"));
+
+        let long_body = "x".repeat(1000);
+        let error = ClientError::from_status(&Endpoint::Game, StatusCode::BAD_GATEWAY, &long_body);
+        match error {
+            ClientError::Status { body_excerpt, .. } => {
+                assert_eq!(body_excerpt.len(), BODY_EXCERPT_CHARS)
+            }
+            other => panic!("Expected a status error, got {:?}", other),
+        }
+    }
+}
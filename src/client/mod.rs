@@ -1,170 +1,83 @@
 use crate::structs::{Details, Game, Live, StaticInfo, TeamGw, TeamInfo};
 use futures::future::join_all;
-use reqwest::Client as ReqwestClient;
 use serde::de;
 use std::collections::HashMap;
-use std::error;
-use std::fmt;
-use std::fs::File;
-use std::io::prelude::*;
+use std::sync::Arc;
 
-pub const FPL_API_BASE: &str = "https://draft.premierleague.com/api";
-pub const DEFAULT_LOCAL_API_BASE: &str = "/fpl/api";
-
-#[allow(dead_code)]
-#[derive(Debug, Clone)]
-pub enum ClientError {
-    ReqwestError(String),
-    InternalError(String),
-    HttpError(String),
-    LocalError(String),
-    JsonError(String),
-}
+mod drift;
+mod endpoint;
+mod error;
+mod source;
 
-impl fmt::Display for ClientError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            ClientError::InternalError(msg) => write!(f, "Internal client error: {}", msg),
-            ClientError::ReqwestError(msg) => write!(f, "Reqwest lib error: {}", msg),
-            ClientError::HttpError(msg) => write!(f, "HTTP error: {}", msg),
-            ClientError::LocalError(msg) => write!(f, "Local error: {}", msg),
-            ClientError::JsonError(msg) => write!(f, "Json error: {}", msg),
-        }
-    }
-}
+pub use drift::{EndpointDrift, SchemaDrift};
+pub use endpoint::{Endpoint, EndpointKind};
+pub use error::{ClientError, RetryAdvice};
+#[cfg(test)]
+pub use source::MemorySource;
+pub use source::{DataSource, FileSource, ReplaySource, Timeouts, WebSource};
 
-impl error::Error for ClientError {
-    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
-        None
-    }
-}
+pub const FPL_API_BASE: &str = "https://draft.premierleague.com/api";
+pub const DEFAULT_LOCAL_API_BASE: &str = "/fpl/api";
 
-// TODO: Change to enum Local(), Web()
+// Fetches the FPL endpoints from a data source, eg. the FPL api or a local database
+#[derive(Clone)]
 pub struct Client {
-    #[allow(dead_code)]
-    http_client: ReqwestClient,
-    local: bool,
-    local_url: Option<String>,
-}
-
-fn deserialize_endpoint_struct<'a, T>(s: &'a str) -> Result<T, ClientError>
-where
-    T: de::Deserialize<'a>,
-{
-    serde_json::from_str(&s).or_else(|e| {
-        return Err(ClientError::ReqwestError(String::from(format!(
-            "Found Error: {}",
-            e
-        ))));
-    })
+    source: Arc<dyn DataSource>,
+    // Shared by the clones of the client
+    drift: Arc<SchemaDrift>,
 }
 
 // Basic client methods
 impl Client {
-    /* Creates and returns a new fpl client */
-    pub fn new() -> Result<Client, ClientError> {
-        let client_builder = ReqwestClient::builder().timeout(std::time::Duration::from_secs(10));
-
-        let reqwest_client = match client_builder.build() {
-            Ok(c) => c,
-            Err(e) => {
-                return Err(ClientError::ReqwestError(
-                    format!("Could not create client with reason: {}", e).to_string(),
-                ))
-            }
-        };
-
-        let client = Client {
-            http_client: reqwest_client,
-            local: false,
-            local_url: None,
-        };
-
-        Ok(client)
+    /* Creates and returns a new client fetching from the FPL api */
+    pub fn web(timeouts: Timeouts) -> Result<Client, ClientError> {
+        let source = WebSource::new(String::from(FPL_API_BASE), timeouts)?;
+        Ok(Client::with_source(Arc::new(source)))
     }
 
-    pub fn new_local(local_url: Option<String>) -> Result<Client, ClientError> {
-        let mut client = Client::new()?;
-        client.set_local(true);
-        client.local_url = local_url;
-        Ok(client)
+    pub fn with_source(source: Arc<dyn DataSource>) -> Client {
+        Client {
+            source,
+            drift: Arc::default(),
+        }
     }
 
-    pub fn is_local(&self) -> bool {
-        self.local
+    // Where the client fetches from, for logging
+    pub fn describe_source(&self) -> String {
+        self.source.describe()
     }
 
-    pub fn set_local(&mut self, local: bool) {
-        self.local = local;
+    async fn get(&self, endpoint: &Endpoint) -> Result<String, ClientError> {
+        self.source.fetch(endpoint).await
     }
 
-    fn get_base_url(&self) -> &str {
-        if self.is_local() {
-            if let Some(url) = &self.local_url {
-                url.as_str()
-            } else {
-                DEFAULT_LOCAL_API_BASE
-            }
-        } else {
-            FPL_API_BASE
-        }
+    // Deserializes the body of an endpoint, recording any drift from the endpoint structs
+    pub fn deserialize<T: de::DeserializeOwned>(
+        &self,
+        endpoint: &Endpoint,
+        body: &str,
+    ) -> Result<T, ClientError> {
+        let (result, drift) = drift::deserialize_with_drift(endpoint, body);
+        self.drift.record(endpoint.kind(), drift);
+        result
     }
 
-    async fn get(&self, path: &str) -> Result<String, ClientError> {
-        if self.is_local() {
-            self.fetch_file(path).await
-        } else {
-            self.fetch_web(path).await
-        }
+    // The drift from the endpoint structs seen since the client was created
+    pub fn schema_drift(&self) -> &SchemaDrift {
+        &self.drift
     }
 
-    async fn fetch_file(&self, path: &str) -> Result<String, ClientError> {
-        let mut file = match File::open(path) {
-            Ok(f) => f,
-            Err(e) => {
-                return Err(ClientError::LocalError(format!(
-                    "Error opening file with path {} {}",
-                    path, e
-                )))
-            }
-        };
-        let mut contents = String::new();
-        match file.read_to_string(&mut contents) {
-            Ok(_) => {}
-            Err(e) => {
-                return Err(ClientError::LocalError(format!(
-                    "Error reading file contents into string: {}",
-                    e
-                )))
-            }
-        }
-
-        Ok(contents)
+    async fn get_struct<T: de::DeserializeOwned>(
+        &self,
+        endpoint: &Endpoint,
+    ) -> Result<T, ClientError> {
+        let body = self.get(endpoint).await?;
+        self.deserialize(endpoint, &body)
     }
 
-    async fn fetch_web(&self, path: &str) -> Result<String, ClientError> {
-        tracing::trace!("Fetching {}", path);
-        let resp = match self.http_client.get(path).send().await {
-            Ok(r) => r,
-            Err(e) => {
-                return Err(ClientError::ReqwestError(String::from(format!(
-                    "Error with sending request: {}",
-                    e
-                ))))
-            }
-        };
-
-        let body = match resp.text().await {
-            Ok(b) => b,
-            Err(e) => {
-                return Err(ClientError::ReqwestError(String::from(format!(
-                    "Error with processing request: {}",
-                    e
-                ))))
-            }
-        };
-
-        Ok(body)
+    /* Fetches the body of an endpoint as is, without deserializing it */
+    pub async fn get_raw(&self, endpoint: &Endpoint) -> Result<String, ClientError> {
+        self.get(endpoint).await
     }
 }
 
@@ -173,39 +86,26 @@ impl Client {
     /* Fetches from /league/xxx/details endpoint */
     #[allow(dead_code)]
     pub async fn get_league_details(&self, league_code: &u32) -> Result<Details, ClientError> {
-        let url = format!(
-            "{api_base}/league/{league}/details",
-            api_base = self.get_base_url(),
-            league = league_code
-        );
-        let details = self.get(&url).await?;
-
-        let details: Details = deserialize_endpoint_struct(&details)?;
-        Ok(details)
+        self.get_struct(&Endpoint::Details {
+            league: *league_code,
+        })
+        .await
     }
 
     #[allow(dead_code)]
     /* Fetches from /game endpoint */
     pub async fn get_game(&self) -> Result<Game, ClientError> {
-        let url = format!("{api_base}/game", api_base = self.get_base_url());
-        let game = self.get(&url).await?;
-
-        let game: Game = deserialize_endpoint_struct(&game)?;
-        Ok(game)
+        self.get_struct(&Endpoint::Game).await
     }
 
     #[allow(dead_code)]
     /* Fetches from /entry/{team_code}/event/{gw} endpoint */
     pub async fn get_team_gw(&self, team: &u32, gw: &u32) -> Result<TeamGw, ClientError> {
-        let url = format!(
-            "{api_base}/entry/{team}/event/{gw}",
-            api_base = self.get_base_url(),
-            team = team,
-            gw = gw
-        );
-        let team_gw = self.get(&url).await?;
-        let team_gw = deserialize_endpoint_struct(&team_gw)?;
-        Ok(team_gw)
+        self.get_struct(&Endpoint::TeamGw {
+            team: *team,
+            gw: *gw,
+        })
+        .await
     }
 
     #[allow(dead_code)]
@@ -233,14 +133,7 @@ impl Client {
     #[allow(dead_code)]
     /* Fetches from /entry/{team_code}/public endpoint */
     pub async fn get_team_info(&self, team: &u32) -> Result<TeamInfo, ClientError> {
-        let url = format!(
-            "{api_base}/entry/{team}/public",
-            api_base = self.get_base_url(),
-            team = team
-        );
-        let team_info = self.get(&url).await?;
-        let team_info = deserialize_endpoint_struct(&team_info)?;
-        Ok(team_info)
+        self.get_struct(&Endpoint::TeamInfo { team: *team }).await
     }
 
     #[allow(dead_code)]
@@ -270,38 +163,13 @@ impl Client {
     #[allow(dead_code)]
     /* Fetches from event/{gw}/live endpoint */
     pub async fn get_gw_points_live(&self, gw: &u32) -> Result<Live, ClientError> {
-        let url = format!(
-            "{api_base}/event/{gw}/live",
-            api_base = self.get_base_url(),
-            gw = gw
-        );
-        let live = self.get(&url).await?;
-
-        let live: Live = deserialize_endpoint_struct(&live)?;
-        Ok(live)
+        self.get_struct(&Endpoint::Live { gw: *gw }).await
     }
 
     #[allow(dead_code)]
     /* Fetches from /bootstrap-static endpoint */
     pub async fn get_static(&self) -> Result<StaticInfo, ClientError> {
-        let url = format!(
-            "{api_base}/bootstrap-static",
-            api_base = self.get_base_url()
-        );
-        let static_info = self.get(&url).await?;
-        let static_info = deserialize_endpoint_struct(&static_info)?;
-        Ok(static_info)
-    }
-}
-
-#[allow(dead_code)]
-fn verify_error_code(code: reqwest::StatusCode) -> Result<(), ClientError> {
-    match code.is_success() {
-        true => Ok(()),
-        false => Err(ClientError::HttpError(String::from(format!(
-            "Received error code: {}",
-            code
-        )))),
+        self.get_struct(&Endpoint::Static).await
     }
 }
 
@@ -310,12 +178,15 @@ mod tests {
     use super::*;
     use futures::join;
 
+    fn local_client() -> Client {
+        Client::with_source(Arc::new(FileSource::new(DEFAULT_LOCAL_API_BASE)))
+    }
+
     #[tokio::test]
     async fn local_test_client() -> Result<(), ClientError> {
-        let client = Client::new_local(None).unwrap();
+        let client = local_client();
 
-        assert_eq!(client.local, true);
-        assert_eq!(client.get_base_url(), DEFAULT_LOCAL_API_BASE);
+        assert_eq!(client.describe_source(), DEFAULT_LOCAL_API_BASE);
 
         let league_code: u32 = 305;
         let gw: u32 = 1;
@@ -353,9 +224,8 @@ mod tests {
     #[tokio::test]
     #[ignore] //Expensive
     async fn web_test_client() -> Result<(), ClientError> {
-        let client = Client::new().unwrap();
-        assert_eq!(client.local, false);
-        assert_eq!(client.get_base_url(), FPL_API_BASE);
+        let client = Client::web(Timeouts::default()).unwrap();
+        assert_eq!(client.describe_source(), FPL_API_BASE);
 
         let game = client.get_game();
         let game2 = client.get_game();
@@ -371,7 +241,7 @@ mod tests {
 
     #[tokio::test]
     async fn local_endpoint_test_game() -> Result<(), ClientError> {
-        let client = Client::new_local(None).unwrap();
+        let client = local_client();
 
         client.get_game().await?;
 
@@ -380,7 +250,7 @@ mod tests {
 
     #[tokio::test]
     async fn local_endpoint_test_league_details() -> Result<(), ClientError> {
-        let client = Client::new_local(None).unwrap();
+        let client = local_client();
 
         let league_code: u32 = 305;
         client.get_league_details(&league_code).await?;
@@ -390,7 +260,7 @@ mod tests {
 
     #[tokio::test]
     async fn local_endpoint_test_live() -> Result<(), ClientError> {
-        let client = Client::new_local(None).unwrap();
+        let client = local_client();
 
         let gw: u32 = 1;
         client.get_gw_points_live(&gw).await?;
@@ -400,7 +270,7 @@ mod tests {
 
     #[tokio::test]
     async fn local_endpoint_test_team_gw() -> Result<(), ClientError> {
-        let client = Client::new_local(None).unwrap();
+        let client = local_client();
 
         let gw: u32 = 1;
         let team: u32 = 856;
@@ -411,7 +281,7 @@ mod tests {
 
     #[tokio::test]
     async fn local_endpoint_test_team_info() -> Result<(), ClientError> {
-        let client = Client::new_local(None).unwrap();
+        let client = local_client();
 
         let team: u32 = 856;
         client.get_team_info(&team).await?;
@@ -421,7 +291,7 @@ mod tests {
 
     #[tokio::test]
     async fn local_endpoint_test_static_info() -> Result<(), ClientError> {
-        let client = Client::new_local(None).unwrap();
+        let client = local_client();
 
         client.get_static().await?;
 
@@ -430,7 +300,7 @@ mod tests {
 
     #[tokio::test]
     async fn local_test_multiple_team_gw() -> Result<(), ClientError> {
-        let client = Client::new_local(None).unwrap();
+        let client = local_client();
         let game = client.get_game().await?;
         let gw = game.current_event.unwrap_or(1);
 
@@ -458,7 +328,7 @@ mod tests {
 
     #[tokio::test]
     async fn local_test_multiple_team_info() -> Result<(), ClientError> {
-        let client = Client::new_local(None).unwrap();
+        let client = local_client();
         let _game = client.get_game().await?;
 
         let details = client.get_league_details(&305).await?;
@@ -482,4 +352,29 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn drift_is_reported_by_endpoint() -> Result<(), ClientError> {
+        let endpoints = crate::testdata::endpoints(2, false);
+        let mut game = serde_json::to_value(&endpoints.game).unwrap();
+        game["new_field"] = serde_json::json!(1);
+        let source = MemorySource::from_bodies([(Endpoint::Game, game.to_string())]);
+        let client = Client::with_source(Arc::new(source));
+
+        client.get_game().await?;
+        client.get_game().await?;
+
+        let report = client.schema_drift().report();
+        let game_drift = &report[&EndpointKind::Game];
+        assert!(game_drift.unknown_fields.contains("new_field"));
+        assert_eq!(game_drift.responses, 2);
+        assert_eq!(game_drift.failures, 0);
+        assert!(
+            crate::metrics::get("fpl_schema_drift_responses_total", &[("endpoint", "game")]) >= 2
+        );
+        assert!(crate::metrics::render()
+            .contains("fpl_schema_drift_responses_total{endpoint=\"game\"}"));
+
+        Ok(())
+    }
 }
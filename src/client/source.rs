@@ -0,0 +1,256 @@
+// The sources the client reads the endpoints from. Every source returns the raw json body of an
+// endpoint, the client takes care of deserializing it.
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use reqwest::Client as ReqwestClient;
+
+use super::{ClientError, Endpoint};
+
+pub trait DataSource: Send + Sync {
+    // Fetches the json body of an endpoint
+    fn fetch<'a>(&'a self, endpoint: &'a Endpoint) -> BoxFuture<'a, Result<String, ClientError>>;
+
+    // Describes where the source reads from, for logging
+    fn describe(&self) -> String;
+}
+
+// The FPL api, or anything serving the same paths
+pub struct WebSource {
+    http_client: ReqwestClient,
+    base_url: String,
+}
+
+// The timeouts of the requests of the web source
+#[derive(Debug, Clone, Copy)]
+pub struct Timeouts {
+    // For the whole request, from connecting to reading the body
+    pub request: Duration,
+    pub connect: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            request: Duration::from_secs(10),
+            connect: Duration::from_secs(5),
+        }
+    }
+}
+
+impl WebSource {
+    pub fn new(base_url: String, timeouts: Timeouts) -> Result<WebSource, ClientError> {
+        let client_builder = ReqwestClient::builder()
+            .timeout(timeouts.request)
+            .connect_timeout(timeouts.connect);
+        let http_client = client_builder
+            .build()
+            .map_err(|e| ClientError::Setup(format!("Could not create http client: {}", e)))?;
+        Ok(WebSource {
+            http_client,
+            base_url,
+        })
+    }
+
+    async fn fetch_web(&self, endpoint: &Endpoint) -> Result<String, ClientError> {
+        let url = format!("{}{}", self.base_url, endpoint.path());
+        tracing::trace!("Fetching {}", url);
+        let reqwest_error = |e| ClientError::from_reqwest(endpoint, e);
+        let resp = self
+            .http_client
+            .get(&url)
+            .send()
+            .await
+            .map_err(reqwest_error)?;
+
+        let status = resp.status();
+        let body = resp.text().await.map_err(reqwest_error)?;
+        if !status.is_success() {
+            return Err(ClientError::from_status(endpoint, status, &body));
+        }
+        Ok(body)
+    }
+}
+
+impl DataSource for WebSource {
+    fn fetch<'a>(&'a self, endpoint: &'a Endpoint) -> BoxFuture<'a, Result<String, ClientError>> {
+        Box::pin(self.fetch_web(endpoint))
+    }
+
+    fn describe(&self) -> String {
+        self.base_url.clone()
+    }
+}
+
+// A local database with the files laid out like the paths of the api, as written by the record
+// command. Example: /game is read from {root}/game
+pub struct FileSource {
+    root: PathBuf,
+}
+
+impl FileSource {
+    pub fn new(root: impl Into<PathBuf>) -> FileSource {
+        FileSource { root: root.into() }
+    }
+
+    pub fn file_path(&self, endpoint: &Endpoint) -> PathBuf {
+        self.root.join(endpoint.path().trim_start_matches('/'))
+    }
+
+    async fn fetch_file(&self, endpoint: &Endpoint) -> Result<String, ClientError> {
+        let path = self.file_path(endpoint);
+        tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| ClientError::from_io(endpoint, path, e))
+    }
+}
+
+impl DataSource for FileSource {
+    fn fetch<'a>(&'a self, endpoint: &'a Endpoint) -> BoxFuture<'a, Result<String, ClientError>> {
+        Box::pin(self.fetch_file(endpoint))
+    }
+
+    fn describe(&self) -> String {
+        self.root.display().to_string()
+    }
+}
+
+// Replays a sequence of local databases, eg. recorded every few minutes during a gameweek. The
+// snapshots are the subdirectories of the replay directory in name order, and the replay moves to
+// the next snapshot every step. Endpoints missing from a snapshot are read from the latest
+// snapshot before it that has them, so a snapshot only needs the endpoints that changed.
+pub struct ReplaySource {
+    dir: PathBuf,
+    snapshots: Vec<FileSource>,
+    step: Duration,
+    start: Instant,
+}
+
+impl ReplaySource {
+    pub fn new(dir: &Path, step: Duration) -> Result<ReplaySource, ClientError> {
+        let read_error = |e: std::io::Error| {
+            ClientError::Setup(format!(
+                "Error reading replay directory {}: {}",
+                dir.display(),
+                e
+            ))
+        };
+        let mut snapshot_dirs = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(read_error)? {
+            let path = entry.map_err(read_error)?.path();
+            if path.is_dir() {
+                snapshot_dirs.push(path);
+            }
+        }
+        if snapshot_dirs.is_empty() {
+            return Err(ClientError::Setup(format!(
+                "No snapshots in replay directory {}",
+                dir.display()
+            )));
+        }
+        snapshot_dirs.sort();
+
+        Ok(ReplaySource {
+            dir: dir.to_path_buf(),
+            snapshots: snapshot_dirs.into_iter().map(FileSource::new).collect(),
+            step,
+            start: Instant::now(),
+        })
+    }
+
+    fn current_snapshot(&self) -> usize {
+        let steps = self.start.elapsed().as_secs_f64() / self.step.as_secs_f64().max(f64::EPSILON);
+        (steps as usize).min(self.snapshots.len() - 1)
+    }
+}
+
+impl DataSource for ReplaySource {
+    fn fetch<'a>(&'a self, endpoint: &'a Endpoint) -> BoxFuture<'a, Result<String, ClientError>> {
+        let current = self.current_snapshot();
+        let snapshot = self.snapshots[..=current]
+            .iter()
+            .rev()
+            .find(|s| s.file_path(endpoint).exists())
+            .unwrap_or(&self.snapshots[current]);
+        snapshot.fetch(endpoint)
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "replay of {} snapshots in {}",
+            self.snapshots.len(),
+            self.dir.display()
+        )
+    }
+}
+
+// Endpoints kept in memory and changed directly by tests
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemorySource {
+    bodies: std::sync::RwLock<std::collections::HashMap<Endpoint, String>>,
+}
+
+#[cfg(test)]
+impl MemorySource {
+    pub fn from_bodies(bodies: impl IntoIterator<Item = (Endpoint, String)>) -> MemorySource {
+        MemorySource {
+            bodies: std::sync::RwLock::new(bodies.into_iter().collect()),
+        }
+    }
+
+    pub fn set(&self, endpoint: Endpoint, body: String) {
+        self.bodies.write().unwrap().insert(endpoint, body);
+    }
+}
+
+#[cfg(test)]
+impl DataSource for MemorySource {
+    fn fetch<'a>(&'a self, endpoint: &'a Endpoint) -> BoxFuture<'a, Result<String, ClientError>> {
+        let body = self.bodies.read().unwrap().get(endpoint).cloned();
+        Box::pin(async move {
+            body.ok_or_else(|| ClientError::FileNotFound {
+                endpoint: *endpoint,
+                path: PathBuf::from(endpoint.path()),
+            })
+        })
+    }
+
+    fn describe(&self) -> String {
+        String::from("memory")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, endpoint: &Endpoint, body: &str) {
+        let path = FileSource::new(dir).file_path(endpoint);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, body).unwrap();
+    }
+
+    #[tokio::test]
+    async fn replay_steps_through_snapshots() {
+        let dir = std::env::temp_dir().join(format!("dof-replay-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let live = Endpoint::Live { gw: 1 };
+        write(&dir.join("001"), &Endpoint::Game, "game 1");
+        write(&dir.join("001"), &live, "live 1");
+        write(&dir.join("002"), &live, "live 2");
+
+        let step = Duration::from_millis(200);
+        let replay = ReplaySource::new(&dir, step).unwrap();
+        assert_eq!(replay.fetch(&live).await.unwrap(), "live 1");
+
+        tokio::time::sleep(step).await;
+        assert_eq!(replay.fetch(&live).await.unwrap(), "live 2");
+        // Not in the second snapshot, so read from the first
+        assert_eq!(replay.fetch(&Endpoint::Game).await.unwrap(), "game 1");
+        assert!(replay.fetch(&Endpoint::Static).await.is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,312 @@
+// Compares the current gameweek of two league teams, eg. the two teams of a H2H match-up.
+//
+// Lists the players each team has left to play, the fixtures where both teams have players and
+// estimates the probability of each team winning from the expected points of the remaining
+// players. The points of a remaining player are modelled as independent with a mean of the FPL
+// expected points for the share of the gameweek's match time still to be played, and a variance
+// proportional to the mean, and the margin is assumed to be normally distributed.
+// The points a player has already scored are in the projected points of the team.
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::propcomp;
+use crate::storage::table::{Entry as TableEntry, PlayStatus};
+use crate::storage::{FplEndpoints, LeagueTable};
+
+// Variance of the points of a player relative to the expected points. FPL points are heavy
+// tailed as a single goal is worth 4-6 points.
+const POINTS_VARIANCE_FACTOR: f64 = 2.0;
+
+// The length of a match in minutes, without stoppage time
+const MATCH_MINUTES: i32 = 90;
+
+// The smallest variance of a remaining player, so that players with no expected points still
+// add uncertainty
+const MIN_PLAYER_VARIANCE: f64 = 1.0;
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct Comparison {
+    pub entry_1: ComparisonSide,
+    pub entry_2: ComparisonSide,
+
+    /// The fixtures not yet finished where both teams have remaining players
+    pub shared_fixtures: Vec<SharedFixture>,
+
+    /// Projected points of the first team minus the projected points of the second team
+    pub margin: i32,
+
+    /// The estimated probability of the first team finishing the gameweek with more points
+    pub entry_1_win_probability: f64,
+
+    /// The estimated probability of the second team finishing the gameweek with more points
+    pub entry_2_win_probability: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ComparisonSide {
+    pub team_code: u32,
+    pub team_name: String,
+    pub gw_points: i32,
+    pub gw_projected_points: i32,
+
+    /// The counting players that have not yet finished all their fixtures
+    pub remaining_players: Vec<RemainingPlayer>,
+
+    /// Sum of the expected points of the remaining players
+    pub remaining_expected_points: f64,
+
+    /// The number of points the remaining players need to score to overtake the other team,
+    /// 0 if the team is already ahead
+    pub points_needed: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct RemainingPlayer {
+    pub id: u32,
+    pub display_name: String,
+    pub play_status: PlayStatus,
+    pub projected_points: i32,
+    /// The FPL expected points of the gameweek for the match time still to be played
+    pub expected_points: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct SharedFixture {
+    pub fixture_id: u32,
+    pub entry_1_players: Vec<u32>,
+    pub entry_2_players: Vec<u32>,
+}
+
+// Returns None if any of the entries is not in the league table
+pub fn compare_entries(
+    endpoints: &FplEndpoints,
+    table: &LeagueTable,
+    entry_1: u32,
+    entry_2: u32,
+) -> Option<Comparison> {
+    let e1 = table.entries.iter().find(|e| e.team_code == entry_1)?;
+    let e2 = table.entries.iter().find(|e| e.team_code == entry_2)?;
+
+    let remaining_1 = compute_remaining_players(endpoints, e1);
+    let remaining_2 = compute_remaining_players(endpoints, e2);
+
+    let margin = e1.gw_projected_points - e2.gw_projected_points;
+    let expected_1: f64 = remaining_1.iter().map(|p| p.expected_points).sum();
+    let expected_2: f64 = remaining_2.iter().map(|p| p.expected_points).sum();
+    let variance: f64 = remaining_1
+        .iter()
+        .chain(remaining_2.iter())
+        .map(|p| (p.expected_points * POINTS_VARIANCE_FACTOR).max(MIN_PLAYER_VARIANCE))
+        .sum();
+    let entry_1_win_probability =
+        estimate_win_probability(margin as f64 + expected_1 - expected_2, variance);
+
+    let shared_fixtures = compute_shared_fixtures(endpoints, &remaining_1, &remaining_2);
+
+    Some(Comparison {
+        entry_1: compute_side(e1, remaining_1, expected_1, -margin),
+        entry_2: compute_side(e2, remaining_2, expected_2, margin),
+        shared_fixtures,
+        margin,
+        entry_1_win_probability,
+        entry_2_win_probability: 1.0 - entry_1_win_probability,
+    })
+}
+
+fn compute_side(
+    entry: &TableEntry,
+    remaining_players: Vec<RemainingPlayer>,
+    remaining_expected_points: f64,
+    deficit: i32,
+) -> ComparisonSide {
+    ComparisonSide {
+        team_code: entry.team_code,
+        team_name: entry.team_name.clone(),
+        gw_points: entry.gw_points,
+        gw_projected_points: entry.gw_projected_points,
+        remaining_players,
+        remaining_expected_points,
+        points_needed: (deficit + 1).max(0),
+    }
+}
+
+fn compute_remaining_players(endpoints: &FplEndpoints, entry: &TableEntry) -> Vec<RemainingPlayer> {
+    entry
+        .players
+        .iter()
+        .filter(|p| {
+            !p.fixtures_finished
+                && matches!(
+                    p.play_status,
+                    PlayStatus::Playing | PlayStatus::SubbedIn { .. }
+                )
+        })
+        .map(|p| RemainingPlayer {
+            id: p.id,
+            display_name: p.display_name.clone(),
+            play_status: p.play_status.clone(),
+            projected_points: p.projected_points,
+            expected_points: propcomp::get_player_expected_points_this_gw(endpoints, p.id)
+                * remaining_share(endpoints, p.id),
+        })
+        .collect()
+}
+
+// The share of the match time of the player's fixtures in the gameweek that is still to be played
+fn remaining_share(endpoints: &FplEndpoints, player_id: u32) -> f64 {
+    let fixtures = propcomp::get_player_current_fixtures(endpoints, player_id);
+    if fixtures.is_empty() {
+        return 0.0;
+    }
+    let remaining: i32 = fixtures
+        .iter()
+        .filter(|f| !f.finished_provisional)
+        .map(|f| (MATCH_MINUTES - f.minutes).max(0))
+        .sum();
+    remaining as f64 / (MATCH_MINUTES * fixtures.len() as i32) as f64
+}
+
+fn compute_shared_fixtures(
+    endpoints: &FplEndpoints,
+    remaining_1: &[RemainingPlayer],
+    remaining_2: &[RemainingPlayer],
+) -> Vec<SharedFixture> {
+    let by_fixture = |players: &[RemainingPlayer]| -> HashMap<u32, Vec<u32>> {
+        let mut m: HashMap<u32, Vec<u32>> = HashMap::new();
+        for p in players {
+            for fixture in propcomp::get_player_current_fixtures(endpoints, p.id) {
+                if !fixture.finished_provisional {
+                    m.entry(fixture.id).or_default().push(p.id);
+                }
+            }
+        }
+        m
+    };
+    let fixtures_1 = by_fixture(remaining_1);
+    let mut fixtures_2 = by_fixture(remaining_2);
+
+    let shared: BTreeMap<u32, SharedFixture> = fixtures_1
+        .into_iter()
+        .filter_map(|(fixture_id, entry_1_players)| {
+            let entry_2_players = fixtures_2.remove(&fixture_id)?;
+            Some((
+                fixture_id,
+                SharedFixture {
+                    fixture_id,
+                    entry_1_players,
+                    entry_2_players,
+                },
+            ))
+        })
+        .collect();
+    shared.into_values().collect()
+}
+
+// The probability of a normally distributed margin being positive, where a tie counts as half.
+// Uses the logistic approximation of the normal distribution function.
+fn estimate_win_probability(expected_margin: f64, variance: f64) -> f64 {
+    if variance <= 0.0 {
+        return match expected_margin.partial_cmp(&0.0) {
+            Some(std::cmp::Ordering::Greater) => 1.0,
+            Some(std::cmp::Ordering::Less) => 0.0,
+            _ => 0.5,
+        };
+    }
+    let z = expected_margin / variance.sqrt();
+    1.0 / (1.0 + (-1.702 * z).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::computer;
+    use crate::testdata;
+
+    #[test]
+    fn compare_finished_gameweek() {
+        let mut endpoints = testdata::endpoints(2, true);
+        testdata::set_player_points(&mut endpoints, testdata::player_id(1, 10), 20, 90);
+        let table = computer::compute_new_league_table(&endpoints).unwrap();
+
+        let comparison = compare_entries(
+            &endpoints,
+            &table,
+            testdata::entry_id(1),
+            testdata::entry_id(2),
+        )
+        .unwrap();
+
+        assert!(comparison.margin > 0);
+        assert!(comparison.entry_1.remaining_players.is_empty());
+        assert!(comparison.shared_fixtures.is_empty());
+        assert_eq!(comparison.entry_1_win_probability, 1.0);
+        assert_eq!(comparison.entry_1.points_needed, 0);
+        assert_eq!(comparison.entry_2.points_needed, comparison.margin + 1);
+    }
+
+    #[test]
+    fn compare_with_remaining_players() {
+        let mut endpoints = testdata::endpoints(2, true);
+        // Fixture 1 is played by clubs 1 and 2, which both league teams have players from
+        testdata::set_fixture_not_started(&mut endpoints, 1);
+        let table = computer::compute_new_league_table(&endpoints).unwrap();
+
+        let comparison = compare_entries(
+            &endpoints,
+            &table,
+            testdata::entry_id(1),
+            testdata::entry_id(2),
+        )
+        .unwrap();
+
+        assert!(!comparison.entry_1.remaining_players.is_empty());
+        assert!(!comparison.entry_2.remaining_players.is_empty());
+        assert_eq!(comparison.shared_fixtures.len(), 1);
+        assert_eq!(comparison.shared_fixtures[0].fixture_id, 1);
+        let p = comparison.entry_1_win_probability;
+        assert!(p > 0.0 && p < 1.0);
+        assert!((p + comparison.entry_2_win_probability - 1.0).abs() < 1e-9);
+
+        assert!(compare_entries(&endpoints, &table, testdata::entry_id(1), 1).is_none());
+    }
+
+    #[test]
+    fn expected_points_are_for_the_time_left() {
+        let mut endpoints = testdata::endpoints(2, true);
+        testdata::set_fixture_not_started(&mut endpoints, 1);
+        let goalkeeper = testdata::player_id(1, 1);
+        let expected = |endpoints: &FplEndpoints| {
+            let table = computer::compute_new_league_table(endpoints).unwrap();
+            let comparison = compare_entries(
+                endpoints,
+                &table,
+                testdata::entry_id(1),
+                testdata::entry_id(2),
+            )
+            .unwrap();
+            comparison
+                .entry_1
+                .remaining_players
+                .iter()
+                .find(|p| p.id == goalkeeper)
+                .map(|p| p.expected_points)
+        };
+        // The expected points of the current gameweek, not the next
+        assert_eq!(expected(&endpoints), Some(3.0));
+
+        endpoints.modify(|data| {
+            let fixture = Arc::make_mut(&mut data.live)
+                .fixtures
+                .iter_mut()
+                .find(|f| f.id == 1)
+                .unwrap();
+            fixture.started = true;
+            fixture.minutes = 60;
+        });
+        assert_eq!(expected(&endpoints), Some(1.0));
+    }
+}
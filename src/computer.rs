@@ -1,68 +1,101 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::TryInto;
-use std::sync::{Arc, RwLock};
-use std::time;
+use std::sync::Arc;
 
+use tokio::sync::watch;
+
+use crate::cache::CachedTable;
+use crate::changes::ChangeSet;
 use crate::propcomp;
 use crate::storage::table::H2HMatch as TableH2HMatch;
 use crate::storage::table::PlayStatus as PlayerPlayStatus;
 use crate::storage::table::Player as TablePlayer;
 use crate::storage::table::Position;
-use crate::storage::table::{Entry as TableEntry, H2HInfo, ProjectedPointsExplanation, Scoring};
+use crate::storage::table::{
+    Entry as TableEntry, H2HInfo, ProjectedPointsExplanation, RankInfo, RankMovement, Scoring,
+};
 use crate::storage::{FplEndpoints, LeagueTable};
 
-#[allow(dead_code)]
+// Computes a new league table every time the fetcher publishes new endpoints. Only the entries
+// affected by the changes since the last computation are recomputed, and the change set is
+// published after the table. The table is also serialized once per version for the handlers
+// serving it as json.
 pub async fn league_table_computer(
-    lock: Arc<RwLock<LeagueTable>>,
-    endpoints_lock: Arc<RwLock<FplEndpoints>>,
+    table_tx: watch::Sender<Arc<LeagueTable>>,
+    table_json_tx: watch::Sender<Arc<CachedTable>>,
+    changes_tx: watch::Sender<Arc<ChangeSet>>,
+    mut endpoints_rx: watch::Receiver<Arc<FplEndpoints>>,
 ) {
+    // The endpoints the current table was computed from. Unknown for the table computed at
+    // startup, so the first computation is a full one.
+    let mut previous_endpoints: Option<Arc<FplEndpoints>> = None;
     loop {
-        {
-            let sleep_ms = 30_000;
-            tracing::trace!("Sleeping computer thread for {} ms", sleep_ms);
-            tokio::time::sleep(time::Duration::from_millis(sleep_ms)).await;
+        if endpoints_rx.changed().await.is_err() {
+            tracing::error!("Endpoints channel closed, stopping computer thread");
+            return;
         }
+        let endpoints = endpoints_rx.borrow_and_update().clone();
 
-        let endpoints = match endpoints_lock.read() {
-            Ok(e) => (*e).clone(),
-            Err(e) => {
-                tracing::error!(
-                    "Could not grab read lock for endpoints in computer thread, {}",
-                    e
-                );
-                continue;
-            }
-        };
+        let mut changes = ChangeSet::between(previous_endpoints.as_deref(), &endpoints);
+        if changes.is_empty() {
+            tracing::debug!("Endpoints unchanged, keeping league table");
+            previous_endpoints = Some(endpoints);
+            continue;
+        }
 
         tracing::debug!("Computing new league table");
-        let new_table = compute_new_league_table(endpoints);
-        match new_table {
-            Some(new_table) => {
-                match lock.write() {
-                    Ok(mut t) => {
-                        tracing::trace!("Grabbed the league table lock");
-                        *t = new_table;
-                    }
-                    Err(e) => {
-                        tracing::error!("Could not grab write lock for table: {}", e);
-                    }
-                };
+        let previous_table = table_tx.borrow().clone();
+        if let Some(new_table) =
+            compute_league_table_update(&endpoints, Some(&previous_table), &mut changes)
+        {
+            tracing::debug!(
+                "Recomputed {} entries and {} players",
+                changes.entries.len(),
+                changes.players.len()
+            );
+            match CachedTable::new(&new_table) {
+                Ok(json) => {
+                    table_json_tx.send_replace(Arc::new(json));
+                }
+                Err(e) => tracing::error!("Failed to serialize league table: {}", e),
             }
-            None => (),
+            table_tx.send_replace(Arc::new(new_table));
+            changes_tx.send_replace(Arc::new(changes));
+            previous_endpoints = Some(endpoints);
         }
     }
 }
 
-pub fn compute_new_league_table(endpoints: FplEndpoints) -> Option<LeagueTable> {
-    let mut entries = compute_league_entries(&endpoints);
-    entries.sort_by_key(|x| std::cmp::Reverse(x.total_points));
+pub fn compute_new_league_table(endpoints: &FplEndpoints) -> Option<LeagueTable> {
+    compute_league_table_update(endpoints, None, &mut ChangeSet::full())
+}
 
-    let matches = compute_all_league_matches(&endpoints);
+// Computes the league table from the endpoints, reusing the entries and players of the previous
+// table that are not affected by the changes. The recomputed entries and players are added to the
+// change set.
+pub fn compute_league_table_update(
+    endpoints: &FplEndpoints,
+    previous: Option<&LeagueTable>,
+    changes: &mut ChangeSet,
+) -> Option<LeagueTable> {
+    let previous = previous.filter(|_| !changes.full);
+    let mut entries = compute_league_entries(endpoints, previous, changes);
+    match propcomp::get_league_scoring(endpoints) {
+        Scoring::CLASSIC => {
+            compute_rank_info(endpoints, &mut entries);
+            entries.sort_by_key(|x| x.rank_info.as_ref().map(|r| r.live_rank));
+        }
+        Scoring::H2H => entries.sort_by_key(|x| std::cmp::Reverse(x.total_points)),
+    }
+
+    let matches = compute_all_league_matches(endpoints);
     let table = LeagueTable {
         entries,
-        code: propcomp::get_league_id(&endpoints),
-        name: propcomp::get_league_name(&endpoints),
-        scoring: propcomp::get_league_scoring(&endpoints),
+        code: propcomp::get_league_id(endpoints),
+        name: propcomp::get_league_name(endpoints),
+        scoring: propcomp::get_league_scoring(endpoints),
+        gw: endpoints.game.current_event.unwrap_or(1),
+        gw_finished: endpoints.game.current_event_finished.unwrap_or(false),
         matches,
     };
     Some(table)
@@ -70,11 +103,12 @@ pub fn compute_new_league_table(endpoints: FplEndpoints) -> Option<LeagueTable>
 
 fn compute_all_league_matches(
     endpoints: &FplEndpoints,
-) -> Option<HashMap<u32, Vec<TableH2HMatch>>> {
+) -> Option<BTreeMap<u32, Vec<TableH2HMatch>>> {
     match Scoring::from_fpl_str(&endpoints.details.league.scoring) {
         Scoring::CLASSIC => None,
         Scoring::H2H => {
-            let mut league_matches: HashMap<u32, Vec<TableH2HMatch>> = HashMap::new();
+            // Ordered by gameweek, so equal tables serialize to the same json
+            let mut league_matches: BTreeMap<u32, Vec<TableH2HMatch>> = BTreeMap::new();
             let n_gameweeks: u32 = endpoints
                 .static_info
                 .events
@@ -116,12 +150,23 @@ fn compute_table_league_match(league_match: &crate::structs::details::H2HMatch)
     }
 }
 
-fn compute_league_entries(endpoints: &FplEndpoints) -> Vec<TableEntry> {
+fn compute_league_entries(
+    endpoints: &FplEndpoints,
+    previous: Option<&LeagueTable>,
+    changes: &mut ChangeSet,
+) -> Vec<TableEntry> {
+    let previous_entries: HashMap<u32, &TableEntry> = previous
+        .map(|table| table.entries.iter().map(|e| (e.team_code, e)).collect())
+        .unwrap_or_default();
+
     let entries: Vec<TableEntry> = endpoints
         .details
         .league_entries
         .iter()
-        .map(|entry| compute_league_entry(endpoints, entry.entry_id))
+        .map(|entry| {
+            let previous_entry = previous_entries.get(&entry.entry_id).copied();
+            compute_league_entry(endpoints, entry.entry_id, previous_entry, changes)
+        })
         .collect();
 
     entries
@@ -132,9 +177,21 @@ fn get_total_points_before_gw(endpoints: &FplEndpoints, id: u32) -> i32 {
     team_info.overall_points - team_info.event_points
 }
 
-fn compute_league_entry(endpoints: &FplEndpoints, id: u32) -> TableEntry {
+// Reuses the previous entry if none of its players or its team info changed
+fn compute_league_entry(
+    endpoints: &FplEndpoints,
+    id: u32,
+    previous: Option<&TableEntry>,
+    changes: &mut ChangeSet,
+) -> TableEntry {
     let team_code = id;
-    let players = extract_players(endpoints, id);
+    let (players, players_changed) = extract_players(endpoints, id, previous, changes);
+    if let Some(previous) = previous {
+        if !players_changed && !changes.teams_infos.contains(&id) {
+            return previous.clone();
+        }
+    }
+    changes.entries.insert(id);
 
     let owner_name = propcomp::get_team_owner_name(endpoints, id);
     let team_name = propcomp::get_team_name(endpoints, id);
@@ -165,18 +222,61 @@ fn compute_league_entry(endpoints: &FplEndpoints, id: u32) -> TableEntry {
         projected_points_explanation,
         players,
         h2h_info,
+        rank_info: None,
     }
 }
 
-fn compute_h2h_info(endpoints: &FplEndpoints, id: u32) -> Option<H2HInfo> {
-    let team_id = propcomp::get_team_id_from_entry_id(endpoints, id);
-
-    let team_standings = match endpoints
+// Ranks the entries of a classic league by total points at the start of the gameweek, live and
+// projected. Teams level on points keep the order of the official standings (rank_sort) as FPL
+// does until the standings are processed, and are finally ordered by their entry ID so the
+// ranking is deterministic.
+fn compute_rank_info(endpoints: &FplEndpoints, entries: &mut [TableEntry]) {
+    let standings: HashMap<u32, (Option<u32>, u32)> = endpoints
         .details
         .standings
         .iter()
-        .find(|entry| entry.league_entry == team_id)
-    {
+        .map(|s| {
+            let entry_id = propcomp::get_entry_id_from_team_id(endpoints, s.league_entry);
+            (
+                entry_id,
+                (s.rank, s.rank_sort.or(s.rank).unwrap_or(u32::MAX)),
+            )
+        })
+        .collect();
+    let rank_sort = |e: &TableEntry| standings.get(&e.team_code).map_or(u32::MAX, |s| s.1);
+
+    let rank_by = |points: &dyn Fn(&TableEntry) -> i32| -> HashMap<u32, u32> {
+        let mut order: Vec<&TableEntry> = entries.iter().collect();
+        order.sort_by_key(|e| (std::cmp::Reverse(points(e)), rank_sort(e), e.team_code));
+        order
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.team_code, i as u32 + 1))
+            .collect()
+    };
+    let start_ranks = rank_by(&|e| e.total_points - e.gw_points);
+    let live_ranks = rank_by(&|e| e.total_points);
+    let projected_ranks = rank_by(&|e| e.total_projected_points);
+
+    for entry in entries.iter_mut() {
+        let start_rank = start_ranks[&entry.team_code];
+        let live_rank = live_ranks[&entry.team_code];
+        let projected_rank = projected_ranks[&entry.team_code];
+        entry.rank_info = Some(RankInfo {
+            official_rank: standings.get(&entry.team_code).and_then(|s| s.0),
+            start_rank,
+            live_rank,
+            projected_rank,
+            live_movement: RankMovement::from_ranks(start_rank, live_rank),
+            projected_movement: RankMovement::from_ranks(start_rank, projected_rank),
+        });
+    }
+}
+
+fn compute_h2h_info(endpoints: &FplEndpoints, id: u32) -> Option<H2HInfo> {
+    let team_id = propcomp::get_team_id_from_entry_id(endpoints, id);
+
+    let team_standings = match propcomp::get_team_standings(endpoints, team_id) {
         None => {
             return None;
         }
@@ -199,15 +299,48 @@ fn compute_h2h_info(endpoints: &FplEndpoints, id: u32) -> Option<H2HInfo> {
     })
 }
 
-fn extract_players(endpoints: &FplEndpoints, team_id: u32) -> Vec<TablePlayer> {
+// Whether a player has to be recomputed because its live stats or any of its fixtures changed
+fn is_player_changed(endpoints: &FplEndpoints, changes: &ChangeSet, player_id: u32) -> bool {
+    changes.live_elements.contains(&player_id)
+        || propcomp::get_player_current_fixture_ids(endpoints, player_id)
+            .iter()
+            .any(|id| changes.fixtures.contains(id))
+}
+
+// Returns the players of the team and whether any of them were recomputed. Players of the
+// previous entry are reused if they are not affected by the changes.
+fn extract_players(
+    endpoints: &FplEndpoints,
+    team_id: u32,
+    previous: Option<&TableEntry>,
+    changes: &mut ChangeSet,
+) -> (Vec<TablePlayer>, bool) {
+    let previous_players: HashMap<u32, &TablePlayer> = previous
+        .filter(|_| !changes.teams_gws.contains(&team_id))
+        .map(|entry| entry.players.iter().map(|p| (p.id, p)).collect())
+        .unwrap_or_default();
+
     let mut players = Vec::new();
-    let team_entry = endpoints.teams_gws.get(&team_id).expect(&format!(
-        "Could not find team GW info from team {}",
-        team_id
-    ));
+    let mut any_changed = false;
+    let team_entry = endpoints
+        .teams_gws
+        .get(&team_id)
+        .unwrap_or_else(|| panic!("Could not find team GW info from team {}", team_id));
     for pick in team_entry.picks.iter() {
         let player_id = pick.element as u32;
 
+        if let Some(previous_player) = previous_players.get(&player_id) {
+            if !is_player_changed(endpoints, changes, player_id) {
+                let mut player = (*previous_player).clone();
+                // The play status depends on the other players and is calculated again below
+                player.play_status = PlayerPlayStatus::Unknown;
+                players.push(player);
+                continue;
+            }
+        }
+        any_changed = true;
+        changes.players.insert(player_id);
+
         let id = player_id;
         let full_name = propcomp::get_player_full_name(endpoints, player_id);
         let display_name = propcomp::get_player_display_name(endpoints, player_id);
@@ -217,7 +350,7 @@ fn extract_players(endpoints: &FplEndpoints, team_id: u32) -> Vec<TablePlayer> {
         let bps = propcomp::get_player_bps(endpoints, player_id);
         let projected_points = propcomp::get_player_projected_points(endpoints, player_id);
         let point_sources = propcomp::get_player_point_sources(endpoints, player_id);
-        let on_field = propcomp::compute_player_is_on_field(pick, team_entry);
+        let on_field = propcomp::compute_player_is_on_field(endpoints, team_id, pick);
         let pick_number = pick.position;
         let has_played = propcomp::compute_player_has_played(endpoints, player_id);
         let fixtures_finished =
@@ -250,7 +383,7 @@ fn extract_players(endpoints: &FplEndpoints, team_id: u32) -> Vec<TablePlayer> {
     }
     calculate_play_status(&mut players);
 
-    players
+    (players, any_changed)
 }
 
 // Calculates the total number of points for the team
@@ -487,3 +620,129 @@ fn calculate_play_status(players: &mut Vec<TablePlayer>) {
         p.play_status = PlayerPlayStatus::Playing;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata;
+
+    fn rank_info(table: &LeagueTable, team: u32) -> &RankInfo {
+        table
+            .entries
+            .iter()
+            .find(|e| e.team_code == testdata::entry_id(team))
+            .and_then(|e| e.rank_info.as_ref())
+            .unwrap()
+    }
+
+    #[test]
+    fn classic_ranks_follow_total_points() {
+        let mut endpoints = testdata::endpoints(4, false);
+        // Team 1 starts last and jumps to first with a big gameweek
+        testdata::set_player_points(&mut endpoints, testdata::player_id(1, 10), 80, 90);
+
+        let table = compute_new_league_table(&endpoints).unwrap();
+
+        assert_eq!(table.entries[0].team_code, testdata::entry_id(1));
+        let team_1 = rank_info(&table, 1);
+        assert_eq!(team_1.start_rank, 4);
+        assert_eq!(team_1.live_rank, 1);
+        assert_eq!(team_1.live_movement, RankMovement::Up);
+        assert_eq!(team_1.official_rank, Some(4));
+        let team_4 = rank_info(&table, 4);
+        assert_eq!(team_4.live_rank, 2);
+        assert_eq!(team_4.live_movement, RankMovement::Down);
+        assert!(table
+            .entries
+            .windows(2)
+            .all(|w| w[0].total_points >= w[1].total_points));
+    }
+
+    #[test]
+    fn classic_ties_keep_official_order() {
+        let mut endpoints = testdata::endpoints(2, false);
+        endpoints.modify(|data| {
+            for info in data.teams_infos.values_mut() {
+                info.entry.overall_points = 600 + info.entry.event_points;
+            }
+        });
+        for team in 1..=2 {
+            for pick in 1..=11 {
+                testdata::set_player_points(&mut endpoints, testdata::player_id(team, pick), 2, 90);
+            }
+        }
+        // Team 2 is ahead of team 1 in the official standings even though it has a higher ID
+        endpoints.modify(|data| {
+            for standing in data.details.standings.iter_mut() {
+                standing.rank_sort = Some(3 - standing.league_entry);
+            }
+        });
+
+        let table = compute_new_league_table(&endpoints).unwrap();
+
+        assert_eq!(table.entries[0].total_points, table.entries[1].total_points);
+        assert_eq!(table.entries[0].team_code, testdata::entry_id(2));
+        assert_eq!(rank_info(&table, 2).live_rank, 1);
+        assert_eq!(rank_info(&table, 1).live_rank, 2);
+        assert_eq!(rank_info(&table, 1).live_movement, RankMovement::Same);
+    }
+
+    #[test]
+    fn no_rank_info_for_h2h() {
+        let table = compute_new_league_table(&testdata::endpoints(4, true)).unwrap();
+        assert!(table.entries.iter().all(|e| e.rank_info.is_none()));
+    }
+
+    #[test]
+    fn incremental_update_matches_full_computation() {
+        let previous_endpoints = testdata::endpoints(4, true);
+        let previous_table = compute_new_league_table(&previous_endpoints).unwrap();
+
+        let mut endpoints = previous_endpoints.clone();
+        let player = testdata::player_id(2, 5);
+        testdata::set_player_points(&mut endpoints, player, 12, 90);
+
+        let mut changes = ChangeSet::between(Some(&previous_endpoints), &endpoints);
+        let table =
+            compute_league_table_update(&endpoints, Some(&previous_table), &mut changes).unwrap();
+
+        assert!(!changes.full);
+        assert_eq!(changes.live_elements, [player].into());
+        assert!(changes.entries.contains(&testdata::entry_id(2)));
+        assert!(changes.players.contains(&player));
+        assert!(changes.players.len() < 4 * testdata::SQUAD_SIZE as usize);
+        assert_eq!(
+            serde_json::to_value(&table).unwrap(),
+            serde_json::to_value(compute_new_league_table(&endpoints).unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn unchanged_endpoints_give_empty_change_set() {
+        let endpoints = testdata::endpoints(4, false);
+        let changes = ChangeSet::between(Some(&endpoints), &endpoints.clone());
+        assert!(changes.is_empty());
+        assert!(ChangeSet::between(None, &endpoints).full);
+    }
+
+    // Run with: cargo test --release bench_compute_league_table -- --ignored --nocapture
+    #[test]
+    #[ignore] // Benchmark
+    fn bench_compute_league_table() {
+        let endpoints = testdata::endpoints_with_unowned(16, true, 500);
+        let iterations = 200;
+        compute_new_league_table(&endpoints);
+
+        let start = std::time::Instant::now();
+        for _ in 0..iterations {
+            std::hint::black_box(compute_new_league_table(std::hint::black_box(&endpoints)));
+        }
+        let elapsed = start.elapsed();
+
+        println!(
+            "Computed league table for 16 teams in {:?} on average over {} iterations",
+            elapsed / iterations,
+            iterations
+        );
+    }
+}
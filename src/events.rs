@@ -0,0 +1,140 @@
+// Point events of the players owned by the league teams, eg. a goal or an assist.
+//
+// The events are found by comparing the point sources of the players in two consecutive league
+// tables, so an event is only seen once the FPL api has registered it in the live endpoint.
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::table::{Player, PointSource};
+use crate::storage::LeagueTable;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PointEvent {
+    pub gw: u32,
+
+    // The league team owning the player
+    pub team_code: u32,
+    pub team_name: String,
+
+    pub player_id: u32,
+    pub player_name: String,
+
+    // Whether or not the points of the player count for the league team
+    pub counting: bool,
+
+    // The fixture the event happened in
+    pub fixture: u32,
+
+    // The identifying string for the point source (eg. goals_scored) and its display name
+    pub stat: String,
+    pub name: String,
+
+    // The change in the amount of the point source, negative if FPL took it back
+    pub amount: i32,
+
+    // The change in points
+    pub points: i32,
+}
+
+// The events between two tables of the same gameweek. Tables of different gameweeks give no
+// events, since the point sources are reset when a new gameweek starts.
+pub fn compute_point_events(
+    gw: u32,
+    previous: &LeagueTable,
+    current: &LeagueTable,
+) -> Vec<PointEvent> {
+    let previous_players: HashMap<(u32, u32), &Player> = previous
+        .entries
+        .iter()
+        .flat_map(|e| e.players.iter().map(move |p| ((e.team_code, p.id), p)))
+        .collect();
+
+    let mut events = Vec::new();
+    for entry in current.entries.iter() {
+        for player in entry.players.iter() {
+            let previous_sources = previous_players
+                .get(&(entry.team_code, player.id))
+                .map_or(&[][..], |p| &p.point_sources[..]);
+            for ((fixture, stat), change) in
+                diff_point_sources(previous_sources, &player.point_sources)
+            {
+                events.push(PointEvent {
+                    gw,
+                    team_code: entry.team_code,
+                    team_name: entry.team_name.clone(),
+                    player_id: player.id,
+                    player_name: player.display_name.clone(),
+                    counting: player.on_field,
+                    fixture,
+                    stat,
+                    name: change.name,
+                    amount: change.amount,
+                    points: change.points,
+                });
+            }
+        }
+    }
+    events
+}
+
+struct SourceChange {
+    name: String,
+    amount: i32,
+    points: i32,
+}
+
+// The changed point sources by fixture and stat, in order
+fn diff_point_sources(
+    previous: &[PointSource],
+    current: &[PointSource],
+) -> BTreeMap<(u32, String), SourceChange> {
+    let mut changes: BTreeMap<(u32, String), SourceChange> = BTreeMap::new();
+    let signed = current
+        .iter()
+        .map(|s| (s, 1))
+        .chain(previous.iter().map(|s| (s, -1)));
+    for (source, sign) in signed {
+        let change = changes
+            .entry((source.fixture, source.stat.clone()))
+            .or_insert_with(|| SourceChange {
+                name: source.name.clone(),
+                amount: 0,
+                points: 0,
+            });
+        change.amount += sign * source.amount;
+        change.points += sign * source.points_total;
+    }
+    changes.retain(|_, c| c.amount != 0 || c.points != 0);
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer;
+    use crate::testdata;
+
+    #[test]
+    fn new_points_give_events() {
+        let mut endpoints = testdata::endpoints(2, false);
+        let previous = computer::compute_new_league_table(&endpoints).unwrap();
+        let player = testdata::player_id(1, 10);
+        let before: i32 = previous
+            .entries
+            .iter()
+            .flat_map(|e| e.players.iter())
+            .find(|p| p.id == player)
+            .map_or(0, |p| p.point_sources.iter().map(|s| s.points_total).sum());
+        testdata::set_player_points(&mut endpoints, player, 12, 90);
+        let current = computer::compute_new_league_table(&endpoints).unwrap();
+
+        let events = compute_point_events(testdata::CURRENT_GW, &previous, &current);
+
+        assert!(!events.is_empty());
+        assert!(events.iter().all(|e| e.player_id == player));
+        assert!(events.iter().all(|e| e.team_code == testdata::entry_id(1)));
+        assert_eq!(events.iter().map(|e| e.points).sum::<i32>(), 12 - before);
+        assert!(compute_point_events(testdata::CURRENT_GW, &current, &current).is_empty());
+    }
+}
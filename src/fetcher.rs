@@ -1,54 +1,202 @@
 use std::collections::HashMap;
+use std::future::Future;
 use std::ops::Deref;
-use std::sync::{Arc, RwLock};
-use std::time;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{self, SystemTime};
 
 use futures::join;
+use tokio::sync::{watch, Notify};
 
-use crate::client::{Client, ClientError};
+use crate::client::{Client, ClientError, RetryAdvice};
 use crate::storage::endpoints::FplEndpointsUpdate;
 use crate::storage::FplEndpoints;
 use crate::structs::*;
 
-#[allow(dead_code)]
+// The longest extra wait between fetches while FPL asks to back off
+const MAX_FETCH_BACKOFF: time::Duration = time::Duration::from_secs(600);
+
+// The settings of the fetcher that can be changed while it runs, see the admin api
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FetchSettings {
+    pub fetch_interval: time::Duration,
+    pub static_info_interval: time::Duration,
+    pub paused: bool,
+}
+
+// Controls a running fetcher. A paused fetcher still fetches when asked to refetch.
+pub struct FetcherControl {
+    // The league that is fetched
+    context: watch::Sender<Arc<crate::AppContext>>,
+    settings: watch::Sender<FetchSettings>,
+    refetch: Notify,
+    refresh_static_info: AtomicBool,
+    last_fetch: Mutex<Option<SystemTime>>,
+}
+
+impl FetcherControl {
+    pub fn new(context: crate::AppContext) -> FetcherControl {
+        let settings = FetchSettings {
+            fetch_interval: context.fetch_sleep_duration,
+            static_info_interval: context.static_info_fetch_freq,
+            paused: false,
+        };
+        FetcherControl {
+            context: watch::channel(Arc::new(context)).0,
+            settings: watch::channel(settings).0,
+            refetch: Notify::new(),
+            refresh_static_info: AtomicBool::new(false),
+            last_fetch: Mutex::new(None),
+        }
+    }
+
+    pub fn context(&self) -> Arc<crate::AppContext> {
+        self.context.borrow().clone()
+    }
+
+    // Fetches another league from the next fetch on, which is done right away
+    pub fn set_context(&self, context: crate::AppContext) {
+        self.context.send_replace(Arc::new(context));
+        self.refetch();
+    }
+
+    pub fn settings(&self) -> FetchSettings {
+        *self.settings.borrow()
+    }
+
+    // The fetcher starts waiting for the next fetch over with the new settings
+    pub fn update_settings(&self, update: impl FnOnce(&mut FetchSettings)) {
+        self.settings.send_modify(update);
+    }
+
+    // Fetches as soon as the fetcher is done with the fetch it is doing, if any
+    pub fn refetch(&self) {
+        self.refetch.notify_one();
+    }
+
+    // Fetches static info in the next fetch, even if it was fetched recently
+    pub fn refresh_static_info(&self) {
+        self.refresh_static_info.store(true, Ordering::SeqCst);
+        self.refetch();
+    }
+
+    pub fn last_fetch(&self) -> Option<SystemTime> {
+        *self.last_fetch.lock().unwrap()
+    }
+}
+
 pub async fn endpoint_cache_fetcher(
     client: Client,
-    endpoints_lock: Arc<RwLock<FplEndpoints>>,
-    context: Arc<crate::AppContext>,
+    endpoints_tx: watch::Sender<Arc<FplEndpoints>>,
+    control: Arc<FetcherControl>,
 ) {
     let mut static_info_last_fetch: Option<time::Instant> = None;
+    let mut league_id = control.context().league_id;
+    let mut settings_rx = control.settings.subscribe();
+    // Added to the fetch interval while FPL is rate limiting or overloaded
+    let mut backoff: Option<time::Duration> = None;
     loop {
-        let app_context = context.deref().clone();
-        let fetch_sleep_duration = app_context.fetch_sleep_duration;
+        let settings = *settings_rx.borrow_and_update();
+        let sleep = settings.fetch_interval + backoff.unwrap_or_default();
+        let wait = async {
+            if settings.paused {
+                futures::future::pending::<()>().await;
+            }
+            tracing::trace!("Sleeping fetcher thread for {} ms", sleep.as_millis());
+            tokio::time::sleep(sleep).await;
+        };
+        tokio::select! {
+            _ = wait => {}
+            _ = control.refetch.notified() => tracing::info!("Refetch requested"),
+            _ = settings_rx.changed() => continue,
+        }
 
-        {
-            tracing::trace!(
-                "Sleeping fetcher thread for {} ms",
-                fetch_sleep_duration.as_millis()
-            );
-            tokio::time::sleep(fetch_sleep_duration).await;
+        if control.refresh_static_info.swap(false, Ordering::SeqCst) {
+            static_info_last_fetch = None;
+        }
+        let mut app_context = control.context().deref().clone();
+        app_context.fetch_sleep_duration = settings.fetch_interval;
+        app_context.static_info_fetch_freq = settings.static_info_interval;
+        let fetched_league_id = app_context.league_id;
+        let new_league = fetched_league_id != league_id;
+        if new_league {
+            static_info_last_fetch = None;
         }
 
         tracing::debug!("Fetching new endpoints");
-        let new = fetch_new_endpoints(&client, app_context, &mut static_info_last_fetch).await;
-        match endpoints_lock.write() {
-            Ok(mut t) => {
-                tracing::trace!("Grabbed the lock");
-                t.update(new);
-            }
-            Err(e) => {
-                tracing::error!("Could not grab write lock for endpoints: {}", e);
+        let mut back_off = false;
+        let new = fetch_new_endpoints(
+            &client,
+            app_context,
+            &mut static_info_last_fetch,
+            &mut back_off,
+        )
+        .await;
+        backoff = next_backoff(backoff, settings.fetch_interval, back_off);
+        if let Some(backoff) = backoff {
+            tracing::warn!(
+                "FPL asked to back off, waiting {} s longer before the next fetch",
+                backoff.as_secs()
+            );
+        }
+
+        let endpoints = if new_league {
+            // Nothing can be shared with the endpoints of the previous league
+            if !is_complete(&new) {
+                tracing::error!("Failed to fetch every endpoint of the new league, retrying later");
+                static_info_last_fetch = None;
+                continue;
             }
+            tracing::info!(
+                "Switched from league {} to {}",
+                league_id,
+                fetched_league_id
+            );
+            league_id = fetched_league_id;
+            FplEndpoints::initialize_from_update(new)
+        } else {
+            // Only the endpoints that were fetched are replaced, the rest are shared with the
+            // previous snapshot
+            let mut endpoints = (**endpoints_tx.borrow()).clone();
+            endpoints.update(new);
+            endpoints
         };
+        endpoints_tx.send_replace(Arc::new(endpoints));
+        *control.last_fetch.lock().unwrap() = Some(SystemTime::now());
     }
 }
 
-fn handle_error_into_option<T>(res: Result<T, ClientError>) -> Option<T> {
-    let the_type = std::any::type_name::<T>();
+fn is_complete(update: &FplEndpointsUpdate) -> bool {
+    update.details.is_some()
+        && update.game.is_some()
+        && update.live.is_some()
+        && update.static_info.is_some()
+        && update.teams_gws.values().all(Option::is_some)
+        && update.teams_infos.values().all(Option::is_some)
+}
+
+// The extra wait before the next fetch. It starts at the fetch interval and doubles for every
+// fetch FPL asks to back off on, and is dropped after a fetch without.
+fn next_backoff(
+    current: Option<time::Duration>,
+    fetch_interval: time::Duration,
+    back_off: bool,
+) -> Option<time::Duration> {
+    if !back_off {
+        return None;
+    }
+    let backoff = current.map_or(fetch_interval, |current| current * 2);
+    Some(backoff.min(MAX_FETCH_BACKOFF))
+}
+
+// Logs a failed fetch, and notes if the error asks to back off
+fn handle_error_into_option<T>(res: Result<T, ClientError>, back_off: &mut bool) -> Option<T> {
     return match res {
         Ok(x) => Some(x),
         Err(e) => {
-            tracing::error!("Error retrieving {}: {}", the_type, e);
+            *back_off |= e.retry_advice() == RetryAdvice::BackOff;
+            let endpoint = e.endpoint_kind().map_or("none", |kind| kind.name());
+            tracing::error!(endpoint, "Error fetching endpoint: {}", e);
             None
         }
     };
@@ -59,26 +207,31 @@ pub async fn fetch_and_initialize_endpoints(
     context: crate::AppContext,
 ) -> FplEndpoints {
     let mut last_fetch: Option<time::Instant> = None;
-    let endpoints = fetch_new_endpoints(&client, context, &mut last_fetch).await;
+    let endpoints = fetch_new_endpoints(&client, context, &mut last_fetch, &mut false).await;
     let endpoints = FplEndpoints::initialize_from_update(endpoints);
     endpoints
 }
 
+// Fetches the endpoints of the league. back_off is set if FPL asked to back off on any of them.
 pub async fn fetch_new_endpoints(
     client: &Client,
     context: crate::AppContext,
     static_info_last_fetch: &mut Option<time::Instant>,
+    back_off: &mut bool,
 ) -> FplEndpointsUpdate {
     let mut gw = 1;
     let league_code = context.league_id;
     let teams = context.team_ids;
 
-    //let (game, details) = fetch_game_and_details_with_retries(client, &league_code).await;
-    let retries = 15;
-    let retry_delay_ms = 10;
-    let game = fetch_game_with_retries(client, retries, retry_delay_ms);
-    let details = fetch_details_with_retries(client, retries, &league_code, retry_delay_ms);
-    let (game, details): (Option<Game>, Option<Details>) = join!(game, details);
+    // Every other endpoint depends on game and league details, so they are retried
+    let policy = context.retry_policy;
+    let game = fetch_with_retries(&policy, || client.get_game());
+    let details = fetch_with_retries(&policy, || client.get_league_details(&league_code));
+    let (game, details) = join!(game, details);
+    let (game, details) = (
+        handle_error_into_option(game, back_off),
+        handle_error_into_option(details, back_off),
+    );
 
     game.as_ref().map(|game| match game.current_event {
         Some(current_gw) => {
@@ -100,24 +253,24 @@ pub async fn fetch_new_endpoints(
         _ => {
             tracing::debug!("Fetching static info");
             *static_info_last_fetch = Some(time::Instant::now());
-            handle_error_into_option(client.get_static().await)
+            handle_error_into_option(client.get_static().await, back_off)
         }
     };
 
     // Handle results when returned
-    let live = handle_error_into_option(live.await);
+    let live = handle_error_into_option(live.await, back_off);
     let team_gws_res = team_gws_res.await;
     let team_infos_res = team_infos_res.await;
 
     // Convert hashmaps to use options in finalized result
     let mut team_gws: HashMap<u32, Option<TeamGw>> = HashMap::new();
     for (team, res) in team_gws_res.into_iter() {
-        team_gws.insert(team, handle_error_into_option(res));
+        team_gws.insert(team, handle_error_into_option(res, back_off));
     }
 
     let mut team_infos: HashMap<u32, Option<TeamInfo>> = HashMap::new();
     for (team, res) in team_infos_res.into_iter() {
-        team_infos.insert(team, handle_error_into_option(res));
+        team_infos.insert(team, handle_error_into_option(res, back_off));
     }
 
     FplEndpointsUpdate {
@@ -130,53 +283,205 @@ pub async fn fetch_new_endpoints(
     }
 }
 
-async fn fetch_game_with_retries(
-    client: &Client,
-    mut retries: i32,
-    retry_wait_ms: u64,
-) -> Option<Game> {
-    let mut err: Option<ClientError> = None;
-    while retries > 0 {
-        let details = client.get_game().await;
-        match details {
-            Ok(g) => {
-                return Some(g);
-            }
-            Err(e) => {
-                err = Some(e);
-                retries -= 1;
-            }
+// How an endpoint is fetched again after a failure. The error of the failure decides if it is
+// retried right away, after a growing delay or not at all, see ClientError::retry_advice.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub retry_delay: time::Duration,
+    // The first delay when backing off, doubled for every attempt up to the max
+    pub backoff_delay: time::Duration,
+    pub max_backoff_delay: time::Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 15,
+            retry_delay: time::Duration::from_millis(10),
+            backoff_delay: time::Duration::from_millis(500),
+            max_backoff_delay: time::Duration::from_secs(8),
         }
-        tokio::time::sleep(time::Duration::from_millis(retry_wait_ms)).await;
     }
-    if let Some(e) = err {
-        tracing::error!("Error fetching Game. \nGame: {}", e);
+}
+
+pub async fn fetch_with_retries<T, F, Fut>(
+    policy: &RetryPolicy,
+    mut fetch: F,
+) -> Result<T, ClientError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ClientError>>,
+{
+    let mut backoff_delay = policy.backoff_delay;
+    let mut attempt = 1;
+    loop {
+        let e = match fetch().await {
+            Ok(x) => return Ok(x),
+            Err(e) => e,
+        };
+        if attempt >= policy.attempts {
+            return Err(e);
+        }
+        let delay = match e.retry_advice() {
+            RetryAdvice::GiveUp => return Err(e),
+            RetryAdvice::Retry => policy.retry_delay,
+            RetryAdvice::BackOff => {
+                let delay = backoff_delay;
+                backoff_delay = (backoff_delay * 2).min(policy.max_backoff_delay);
+                delay
+            }
+        };
+        tracing::debug!(
+            "Attempt {} failed, retrying in {} ms: {}",
+            attempt,
+            delay.as_millis(),
+            e
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
     }
-    return None;
 }
 
-async fn fetch_details_with_retries(
+// Fetches the picks of all teams for a gameweek. Fails if the picks of any team can't be fetched.
+pub async fn fetch_teams_gws(
     client: &Client,
-    mut retries: i32,
-    league_code: &u32,
-    retry_wait_ms: u64,
-) -> Option<Details> {
-    let mut err: Option<ClientError> = None;
-    while retries > 0 {
-        let details = client.get_league_details(&league_code).await;
-        match details {
-            Ok(d) => {
-                return Some(d);
-            }
-            Err(e) => {
-                err = Some(e);
-                retries -= 1;
-            }
-        }
-        tokio::time::sleep(time::Duration::from_millis(retry_wait_ms)).await;
+    teams: &Vec<u32>,
+    gw: u32,
+) -> Result<HashMap<u32, TeamGw>, ClientError> {
+    let mut team_gws: HashMap<u32, TeamGw> = HashMap::new();
+    for (team, res) in client.get_multiple_teams_gw(teams, &gw).await.into_iter() {
+        team_gws.insert(team, res?);
     }
-    if let Some(e) = err {
-        tracing::error!("Error fetching Details. \nDetails: {}", e);
+    Ok(team_gws)
+}
+
+// Creates endpoints for a previous gameweek by fetching the gameweek specific endpoints and
+// reusing the rest from the current endpoints. The season totals in the team infos are not
+// adjusted, so only the gameweek points are valid in a table computed from these endpoints. The
+// gameweek is the finished current gameweek of the endpoints.
+pub async fn fetch_gw_endpoints(
+    client: &Client,
+    endpoints: &FplEndpoints,
+    teams: &Vec<u32>,
+    gw: u32,
+) -> Result<FplEndpoints, ClientError> {
+    let (live, teams_gws) = join!(
+        client.get_gw_points_live(&gw),
+        fetch_teams_gws(client, teams, gw)
+    );
+
+    let (live, teams_gws) = (live?, teams_gws?);
+    let mut gw_endpoints = endpoints.clone();
+    gw_endpoints.modify(|data| {
+        data.live = Arc::new(live);
+        data.teams_gws = teams_gws;
+        data.game.current_event = Some(gw);
+        data.game.current_event_finished = Some(true);
+    });
+    Ok(gw_endpoints)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::client::{Endpoint, MemorySource};
+    use crate::initializer;
+    use crate::mockapi::{Fault, MockFpl};
+    use crate::testdata;
+
+    #[tokio::test]
+    async fn fetches_changes_from_the_source() {
+        let league_id = 1;
+        let mut endpoints = testdata::endpoints(2, false);
+        let source = Arc::new(MemorySource::from_bodies(testdata::endpoint_bodies(
+            &endpoints, league_id,
+        )));
+        let client = Client::with_source(source.clone());
+        let context = initializer::initialize_app_context(&client, league_id).await;
+        let fetched = fetch_and_initialize_endpoints(&client, context.clone()).await;
+        assert_eq!(fetched.live, endpoints.live);
+
+        testdata::set_player_points(&mut endpoints, testdata::player_id(1, 1), 15, 90);
+        let live = serde_json::to_string(&*endpoints.live).unwrap();
+        source.set(
+            Endpoint::Live {
+                gw: testdata::CURRENT_GW,
+            },
+            live,
+        );
+
+        // Static info was just fetched, so it is not fetched again
+        let mut back_off = false;
+        let update = fetch_new_endpoints(
+            &client,
+            context,
+            &mut Some(time::Instant::now()),
+            &mut back_off,
+        )
+        .await;
+        assert!(update.static_info.is_none());
+        assert_eq!(update.live.as_ref(), Some(&*endpoints.live));
+        assert!(!back_off);
+    }
+
+    #[tokio::test]
+    async fn rate_limited_endpoints_stretch_the_next_sleep() {
+        let league_id = 1;
+        let mock = MockFpl::from_endpoints(&testdata::endpoints(2, false), league_id).await;
+        let client = mock.client();
+        let context = initializer::initialize_app_context(&client, league_id).await;
+
+        mock.fail(
+            &Endpoint::Live {
+                gw: testdata::CURRENT_GW,
+            },
+            Fault::Status(StatusCode::TOO_MANY_REQUESTS),
+        );
+        let mut back_off = false;
+        let update = fetch_new_endpoints(&client, context, &mut None, &mut back_off).await;
+        assert!(update.live.is_none());
+        assert!(back_off);
+
+        let interval = time::Duration::from_secs(60);
+        let backoff = next_backoff(None, interval, true);
+        assert_eq!(backoff, Some(interval));
+        let backoff = next_backoff(backoff, interval, true);
+        assert_eq!(backoff, Some(interval * 2));
+        assert_eq!(
+            next_backoff(Some(MAX_FETCH_BACKOFF), interval, true),
+            Some(MAX_FETCH_BACKOFF)
+        );
+        assert_eq!(next_backoff(backoff, interval, false), None);
+    }
+
+    #[tokio::test]
+    async fn retries_follow_the_error() {
+        let mock = MockFpl::from_endpoints(&testdata::endpoints(2, false), 1).await;
+        let client = mock.client();
+        let policy = RetryPolicy {
+            attempts: 4,
+            retry_delay: time::Duration::from_millis(1),
+            backoff_delay: time::Duration::from_millis(1),
+            max_backoff_delay: time::Duration::from_millis(2),
+        };
+        let fetch_game = || fetch_with_retries(&policy, || client.get_game());
+
+        mock.fail(&Endpoint::Game, Fault::Status(StatusCode::NOT_FOUND));
+        let error = fetch_game().await.unwrap_err();
+        assert!(matches!(error, ClientError::Status { .. }));
+        assert_eq!(mock.requests(&Endpoint::Game), 1);
+
+        mock.fail(
+            &Endpoint::Game,
+            Fault::Status(StatusCode::TOO_MANY_REQUESTS),
+        );
+        fetch_game().await.unwrap_err();
+        assert_eq!(mock.requests(&Endpoint::Game), 1 + 4);
+
+        mock.clear_faults();
+        assert!(fetch_game().await.is_ok());
     }
-    return None;
 }
@@ -0,0 +1,234 @@
+// Computes the fixture centre for the current gameweek.
+//
+// Lists every Premier League fixture of the gameweek with its live score and status, the BPS
+// leaderboard with projected bonus points and which league teams own players in the fixture.
+use std::collections::HashMap;
+
+use async_graphql::{Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::propcomp;
+use crate::storage::table::PlayStatus;
+use crate::storage::{FplEndpoints, LeagueTable};
+use crate::structs::live::Fixture as LiveFixture;
+
+// The number of players included in the BPS leaderboard of a fixture
+const BPS_LEADERBOARD_SIZE: usize = 10;
+
+#[derive(Serialize, Deserialize, Debug, ToSchema, SimpleObject)]
+pub struct FixtureCentre {
+    pub gw: u32,
+
+    /// The fixtures of the gameweek ordered by kickoff time
+    pub fixtures: Vec<FixtureInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema, SimpleObject)]
+pub struct FixtureInfo {
+    pub id: u32,
+    pub kickoff_time: Option<String>,
+    pub status: FixtureStatus,
+    pub minutes: i32,
+    pub home: FixtureTeam,
+    pub away: FixtureTeam,
+
+    /// The players with the most BPS in the fixture, in descending order
+    pub bps: Vec<BpsEntry>,
+
+    /// The players in the fixture owned by a league team
+    pub league_players: Vec<LeaguePlayer>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, ToSchema, Enum)]
+#[serde(rename_all = "snake_case")]
+pub enum FixtureStatus {
+    NotStarted,
+    Live,
+    /// Finished but the bonus points are not yet confirmed
+    FinishedProvisional,
+    Finished,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema, SimpleObject)]
+pub struct FixtureTeam {
+    pub id: u32,
+    pub name: String,
+    pub short_name: String,
+    pub score: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema, SimpleObject)]
+pub struct BpsEntry {
+    pub player_id: u32,
+    pub display_name: String,
+    pub bps: i32,
+
+    /// The bonus points given by the current BPS, or the confirmed bonus points when available
+    pub projected_bonus: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema, SimpleObject)]
+pub struct LeaguePlayer {
+    pub player_id: u32,
+    pub display_name: String,
+
+    /// The league team owning the player
+    pub team_code: u32,
+    pub team_name: String,
+
+    /// Whether or not the points of the player count for the league team
+    pub counting: bool,
+    pub points: i32,
+    pub projected_points: i32,
+}
+
+pub fn compute_fixture_centre(endpoints: &FplEndpoints, table: &LeagueTable) -> FixtureCentre {
+    let mut league_players = compute_league_players_by_fixture(endpoints, table);
+
+    let mut fixtures: Vec<&LiveFixture> = endpoints.live.fixtures.iter().collect();
+    fixtures.sort_by(|f1, f2| {
+        f1.kickoff_time
+            .cmp(&f2.kickoff_time)
+            .then(f1.id.cmp(&f2.id))
+    });
+
+    let fixtures = fixtures
+        .into_iter()
+        .map(|f| FixtureInfo {
+            id: f.id,
+            kickoff_time: f.kickoff_time.clone(),
+            status: compute_fixture_status(f),
+            minutes: f.minutes,
+            home: compute_fixture_team(endpoints, f.team_h, f.team_h_score),
+            away: compute_fixture_team(endpoints, f.team_a, f.team_a_score),
+            bps: compute_bps_leaderboard(endpoints, f),
+            league_players: league_players.remove(&f.id).unwrap_or_default(),
+        })
+        .collect();
+
+    FixtureCentre {
+        gw: endpoints.game.current_event.unwrap_or(0),
+        fixtures,
+    }
+}
+
+fn compute_fixture_status(fixture: &LiveFixture) -> FixtureStatus {
+    if fixture.finished {
+        FixtureStatus::Finished
+    } else if fixture.finished_provisional {
+        FixtureStatus::FinishedProvisional
+    } else if fixture.started {
+        FixtureStatus::Live
+    } else {
+        FixtureStatus::NotStarted
+    }
+}
+
+fn compute_fixture_team(
+    endpoints: &FplEndpoints,
+    team_id: Option<i32>,
+    score: Option<i32>,
+) -> FixtureTeam {
+    let id = team_id.unwrap_or(0) as u32;
+    let team = propcomp::get_team_from_static(endpoints, id);
+    FixtureTeam {
+        id,
+        name: team.map(|t| t.name.clone()).unwrap_or_default(),
+        short_name: team.map(|t| t.short_name.clone()).unwrap_or_default(),
+        score,
+    }
+}
+
+fn compute_bps_leaderboard(endpoints: &FplEndpoints, fixture: &LiveFixture) -> Vec<BpsEntry> {
+    let stat_values = |name: &str| -> Vec<(u32, i32)> {
+        fixture
+            .stats
+            .iter()
+            .filter(|s| s.s == name)
+            .flat_map(|s| s.h.iter().chain(s.a.iter()))
+            .map(|s| (s.element, s.value))
+            .collect()
+    };
+    let confirmed_bonus: HashMap<u32, i32> = stat_values("bonus").into_iter().collect();
+
+    let mut bps = stat_values("bps");
+    bps.sort_by_key(|(element, value)| (std::cmp::Reverse(*value), *element));
+    bps.into_iter()
+        .take(BPS_LEADERBOARD_SIZE)
+        .map(|(player_id, value)| {
+            let projected_bonus = if confirmed_bonus.is_empty() {
+                propcomp::calculate_bonus_points(fixture, player_id)
+            } else {
+                confirmed_bonus.get(&player_id).copied().unwrap_or(0)
+            };
+            BpsEntry {
+                player_id,
+                display_name: propcomp::get_player_display_name(endpoints, player_id),
+                bps: value,
+                projected_bonus,
+            }
+        })
+        .collect()
+}
+
+fn compute_league_players_by_fixture(
+    endpoints: &FplEndpoints,
+    table: &LeagueTable,
+) -> HashMap<u32, Vec<LeaguePlayer>> {
+    let mut by_fixture: HashMap<u32, Vec<LeaguePlayer>> = HashMap::new();
+    for entry in table.entries.iter() {
+        for player in entry.players.iter() {
+            for fixture_id in propcomp::get_player_current_fixture_ids(endpoints, player.id) {
+                by_fixture
+                    .entry(fixture_id)
+                    .or_default()
+                    .push(LeaguePlayer {
+                        player_id: player.id,
+                        display_name: player.display_name.clone(),
+                        team_code: entry.team_code,
+                        team_name: entry.team_name.clone(),
+                        counting: matches!(
+                            player.play_status,
+                            PlayStatus::Playing | PlayStatus::SubbedIn { .. }
+                        ),
+                        points: player.points,
+                        projected_points: player.projected_points,
+                    });
+            }
+        }
+    }
+    by_fixture
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer;
+    use crate::testdata;
+
+    #[test]
+    fn fixture_centre_lists_league_players() {
+        let mut endpoints = testdata::endpoints(2, false);
+        let star = testdata::player_id(1, 10);
+        testdata::set_player_points(&mut endpoints, star, 15, 90);
+        let table = computer::compute_new_league_table(&endpoints).unwrap();
+
+        let centre = compute_fixture_centre(&endpoints, &table);
+
+        assert_eq!(centre.gw, testdata::CURRENT_GW);
+        assert_eq!(centre.fixtures.len(), (testdata::N_CLUBS / 2) as usize);
+        let owned: usize = centre.fixtures.iter().map(|f| f.league_players.len()).sum();
+        assert_eq!(owned, 2 * testdata::SQUAD_SIZE as usize);
+
+        let fixture_id = testdata::club_fixture(testdata::player_club(star));
+        let fixture = centre.fixtures.iter().find(|f| f.id == fixture_id).unwrap();
+        assert_eq!(fixture.status, FixtureStatus::Finished);
+        assert_eq!(fixture.bps[0].player_id, star);
+        assert_eq!(fixture.bps[0].projected_bonus, 3);
+        assert!(fixture
+            .league_players
+            .iter()
+            .any(|p| p.player_id == star && p.counting && p.team_code == testdata::entry_id(1)));
+    }
+}
@@ -1,10 +1,21 @@
+use std::collections::BTreeMap;
 use std::env;
+use std::fmt;
 use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize, Serializer};
+use toml::value::{Table, Value};
 
-use crate::client::Client;
+use crate::client::{Client, ClientError, FileSource, ReplaySource, Timeouts};
+use crate::fetcher::RetryPolicy;
+use crate::webhooks::{WebhookEventKind, WebhookFormat};
+
+// The environment variables of the config start with this, eg. DOF_LEAGUE_ID
+const ENV_PREFIX: &str = "DOF_";
 
 #[derive(Clone, Debug)]
 pub struct AppContext {
@@ -12,85 +23,555 @@ pub struct AppContext {
     pub team_ids: Vec<u32>,
     pub fetch_sleep_duration: Duration,
     pub static_info_fetch_freq: Duration,
+    pub retry_policy: RetryPolicy,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+// The config is read in layers, each overriding the settings of the one before it: the defaults
+// below, the config file, the DOF_* environment variables and the command line. See
+// AppConfig::load.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct AppConfig {
     pub league_id: u32,
+    // Where the endpoints are read from. Defaults to the file source if local_fetch is set,
+    // otherwise the FPL api
+    pub source: Option<SourceKind>,
     pub local_fetch: Option<bool>,
+    // The root of the local database for the file source, or the directory of the snapshots
+    // for the replay source
     pub local_url: Option<String>,
-    pub server_port: Option<u16>,
+    // How long each snapshot of the replay source is used
+    pub replay_step_secs: u64,
+    pub bind_address: String,
+    pub server_port: u16,
     pub asset_path: Option<String>,
+    // The bearer token of the admin api, which is disabled without it
+    pub admin_token: Option<Secret>,
+    // How often the endpoints are fetched
+    pub fetch_interval_secs: u64,
+    // How often the static info is fetched
+    pub static_info_interval_secs: u64,
+    // How long before a deadline the lineups are checked and the warnings posted to the webhooks
+    pub lineup_check_before_deadline_mins: u64,
+    pub log_format: LogFormat,
+    pub timeouts: TimeoutsConfig,
+    pub retry: RetryConfig,
+    pub features: Features,
+    // Where league events are posted, none by default
+    pub webhooks: Vec<WebhookConfig>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            league_id: 0,
+            source: None,
+            local_fetch: None,
+            local_url: None,
+            replay_step_secs: 60,
+            bind_address: String::from("0.0.0.0"),
+            server_port: 8000,
+            asset_path: None,
+            admin_token: None,
+            fetch_interval_secs: 60,
+            static_info_interval_secs: 1800,
+            lineup_check_before_deadline_mins: 120,
+            log_format: LogFormat::default(),
+            timeouts: TimeoutsConfig::default(),
+            retry: RetryConfig::default(),
+            features: Features::default(),
+            webhooks: Vec::new(),
+        }
+    }
+}
+
+// The timeouts of requests to the FPL api
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutsConfig {
+    // For the whole request, from connecting to reading the body
+    pub request_secs: u64,
+    pub connect_secs: u64,
+}
+
+impl Default for TimeoutsConfig {
+    fn default() -> Self {
+        let timeouts = Timeouts::default();
+        TimeoutsConfig {
+            request_secs: timeouts.request.as_secs(),
+            connect_secs: timeouts.connect.as_secs(),
+        }
+    }
+}
+
+// How failed fetches of the game and the league details are retried, see fetcher::RetryPolicy
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    pub attempts: u32,
+    pub retry_delay_ms: u64,
+    pub backoff_delay_ms: u64,
+    pub max_backoff_delay_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        let policy = RetryPolicy::default();
+        RetryConfig {
+            attempts: policy.attempts,
+            retry_delay_ms: policy.retry_delay.as_millis() as u64,
+            backoff_delay_ms: policy.backoff_delay.as_millis() as u64,
+            max_backoff_delay_ms: policy.max_backoff_delay.as_millis() as u64,
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            attempts: self.attempts,
+            retry_delay: Duration::from_millis(self.retry_delay_ms),
+            backoff_delay: Duration::from_millis(self.backoff_delay_ms),
+            max_backoff_delay: Duration::from_millis(self.max_backoff_delay_ms),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Full,
+    Compact,
+    Pretty,
+    // One json object per line, for log collectors
+    Json,
+}
+
+// Parts of the server that can be turned off. Everything is on by default.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Features {
+    // The GraphQL api under /api/v1/graphql
+    pub graphql: bool,
+    // /metrics and /debug/*
+    pub debug: bool,
+    // The frontend served from the asset path
+    pub frontend: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Features {
+            graphql: true,
+            debug: true,
+            frontend: true,
+        }
+    }
+}
+
+// A webhook that is posted league events, see webhooks.rs
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    // Anyone with the url of a Discord or Slack webhook can post to it
+    pub url: Secret,
+    #[serde(default)]
+    pub format: WebhookFormat,
+    // The events that are posted, every event by default
+    #[serde(default = "WebhookEventKind::all")]
+    pub events: Vec<WebhookEventKind>,
+}
+
+// A value from the config that is not written to the logs, or printed by `config check`
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    #[cfg(test)]
+    pub fn new(value: impl Into<String>) -> Secret {
+        Secret(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret(<redacted>)")
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str("<redacted>")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    Web,
+    File,
+    Replay,
+}
+
+// Where the layers of the config are read from
+#[derive(Clone, Debug, Default)]
+pub struct ConfigLayers {
+    pub file: Option<PathBuf>,
+    // The DOF_* environment variables
+    pub env: Vec<(String, String)>,
+    // Settings from the command line, as dotted keys and values, eg. ("retry.attempts", "5")
+    pub cli: Vec<(String, String)>,
+}
+
+impl ConfigLayers {
+    // The layers of a config file, the current environment and the command line
+    pub fn new(file: Option<PathBuf>, cli: Vec<(String, String)>) -> ConfigLayers {
+        let env = env::vars()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .collect();
+        ConfigLayers { file, env, cli }
+    }
+}
+
+// A config and the layer that set each setting which is not a default, by dotted key
+#[derive(Debug)]
+pub struct LoadedConfig {
+    pub config: AppConfig,
+    pub origins: BTreeMap<String, String>,
 }
 
 impl AppConfig {
-    /// Initialize AppConfig from file if provided, otherwise fall back to environment variables
-    pub fn initialize(config_file_path: Option<String>) -> AppConfig {
-        let config = match config_file_path {
-            Some(path) => Self::initialize_from_file(path),
-            None => Self::initialize_from_env(),
+    // Reads and validates the config. Every problem found is returned, not only the first.
+    pub fn load(layers: &ConfigLayers) -> Result<LoadedConfig, Vec<String>> {
+        let loaded = AppConfig::read(layers)?;
+        loaded.config.validate()?;
+        Ok(loaded)
+    }
+
+    // Merges the layers of the config without validating the result
+    pub fn read(layers: &ConfigLayers) -> Result<LoadedConfig, Vec<String>> {
+        let defaults = match Value::try_from(AppConfig::default()) {
+            Ok(Value::Table(table)) => table,
+            other => panic!("The default config is not a table: {:?}", other),
         };
-        tracing::info!("Config used: {:?}", config);
-        config
+        let mut merged = defaults.clone();
+        let mut origins = BTreeMap::new();
+        let mut errors = Vec::new();
+
+        if let Some(path) = &layers.file {
+            match read_file(path) {
+                Ok(file) => {
+                    let origin = format!("file {}", path.display());
+                    merge(&mut merged, file, "", &origin, &mut origins);
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+
+        for (name, raw) in &layers.env {
+            // Set but empty variables are ignored, like DOF_LOCAL_URL= in the Dockerfile
+            if raw.is_empty() {
+                continue;
+            }
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let key = key.to_lowercase().replace("__", ".");
+            match parse_setting(&defaults, &key, raw) {
+                Ok(value) => {
+                    insert(&mut merged, &key, value);
+                    origins.insert(key, format!("env {}", name));
+                }
+                Err(e) => errors.push(format!("{}: {}", name, e)),
+            }
+        }
+
+        for (key, raw) in &layers.cli {
+            match parse_setting(&defaults, key, raw) {
+                Ok(value) => {
+                    insert(&mut merged, key, value);
+                    origins.insert(key.clone(), String::from("command line"));
+                }
+                Err(e) => errors.push(format!("Command line {}: {}", key, e)),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        match Value::Table(merged).try_into::<AppConfig>() {
+            Ok(config) => Ok(LoadedConfig { config, origins }),
+            Err(e) => Err(vec![format!("Invalid config: {}", e)]),
+        }
     }
 
-    fn initialize_from_file(config_file_path: String) -> AppConfig {
-        let config_file_content = fs::read_to_string(&config_file_path)
-            .expect(format!("Failed to read config file: {}", config_file_path).as_str());
-        let config: AppConfig = toml::from_str(config_file_content.as_str())
-            .expect("Could not parse application config file");
-        config
+    // The config as toml, with the secrets redacted
+    pub fn to_toml(&self) -> String {
+        Value::try_from(self)
+            .and_then(|value| toml::to_string(&value))
+            .unwrap_or_else(|e| format!("# The config could not be printed: {}", e))
     }
 
-    fn initialize_from_env() -> AppConfig {
-        let league_id_str =
-            env::var("DOF_LEAGUE_ID").expect("LEAGUE_ID must be set in environment");
-        let league_id: u32 = league_id_str
-            .parse()
-            .expect("DOF_LEAGUE_ID in environment must be a valid u32");
-
-        let local_fetch = match env::var("DOF_LOCAL_FETCH") {
-            Ok(val) => match val.as_str() {
-                "true" => Some(true),
-                "false" => Some(false),
-                _ => None,
-            },
-            Err(_) => None,
-        };
+    pub fn fetch_interval(&self) -> Duration {
+        Duration::from_secs(self.fetch_interval_secs)
+    }
 
-        let local_url = match env::var("DOF_LOCAL_URL") {
-            Ok(val) => Some(val),
-            Err(_) => None,
-        };
+    pub fn static_info_interval(&self) -> Duration {
+        Duration::from_secs(self.static_info_interval_secs)
+    }
 
-        let asset_path = match env::var("DOF_ASSET_PATH") {
-            Ok(val) => Some(val),
-            Err(_) => None,
-        };
+    pub fn asset_path(&self) -> String {
+        self.asset_path
+            .clone()
+            .unwrap_or_else(|| String::from("./www/vue"))
+    }
 
-        let server_port = match env::var("DOF_SERVER_PORT") {
-            Ok(val) => Some(val.parse().expect("DOF_SERVER_PORT must be a valid u16")),
-            Err(_) => None,
-        };
+    pub fn timeouts(&self) -> Timeouts {
+        Timeouts {
+            request: Duration::from_secs(self.timeouts.request_secs),
+            connect: Duration::from_secs(self.timeouts.connect_secs),
+        }
+    }
 
-        AppConfig {
+    // The address the server listens on. Only valid configs have one, see validate.
+    pub fn serve_address(&self) -> Option<SocketAddr> {
+        let ip: IpAddr = self.bind_address.parse().ok()?;
+        Some(SocketAddr::new(ip, self.server_port))
+    }
+
+    // Sets the intervals and the retry policy of the fetcher
+    pub fn apply_to(&self, context: &mut AppContext) {
+        context.fetch_sleep_duration = self.fetch_interval();
+        context.static_info_fetch_freq = self.static_info_interval();
+        context.retry_policy = self.retry.policy();
+    }
+
+    // Lists everything that is wrong with the config
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if self.league_id == 0 {
+            errors.push(String::from(
+                "league_id must be set, in the config file, with DOF_LEAGUE_ID or with --league-id",
+            ));
+        }
+        let at_least_one = [
+            ("fetch_interval_secs", self.fetch_interval_secs),
+            ("static_info_interval_secs", self.static_info_interval_secs),
+            ("replay_step_secs", self.replay_step_secs),
+            (
+                "lineup_check_before_deadline_mins",
+                self.lineup_check_before_deadline_mins,
+            ),
+            ("timeouts.request_secs", self.timeouts.request_secs),
+            ("timeouts.connect_secs", self.timeouts.connect_secs),
+            ("retry.attempts", u64::from(self.retry.attempts)),
+        ];
+        for (name, value) in at_least_one {
+            if value == 0 {
+                errors.push(format!("{} must be at least 1", name));
+            }
+        }
+        if self.retry.backoff_delay_ms > self.retry.max_backoff_delay_ms {
+            errors.push(format!(
+                "retry.backoff_delay_ms ({}) must not be above retry.max_backoff_delay_ms ({})",
+                self.retry.backoff_delay_ms, self.retry.max_backoff_delay_ms
+            ));
+        }
+        if self.bind_address.parse::<IpAddr>().is_err() {
+            errors.push(format!(
+                "bind_address {:?} is not an ip address, eg. 0.0.0.0 or 127.0.0.1",
+                self.bind_address
+            ));
+        }
+        if let Some(path) = &self.asset_path {
+            if !Path::new(path).is_dir() {
+                errors.push(format!("asset_path {} is not a directory", path));
+            }
+        }
+        for (i, webhook) in self.webhooks.iter().enumerate() {
+            let url = webhook.url.expose();
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                errors.push(format!("webhooks[{}].url must be an http or https url", i));
+            }
+        }
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+
+    // The fields that differ from another config, eg. "league_id: 1 -> 2". Secrets are redacted.
+    pub fn diff(&self, other: &AppConfig) -> Vec<String> {
+        let mut changes = Vec::new();
+        macro_rules! diff_fields {
+            ($($field:ident),*) => {
+                $(
+                    if self.$field != other.$field {
+                        changes.push(format!(
+                            "{}: {:?} -> {:?}",
+                            stringify!($field),
+                            self.$field,
+                            other.$field
+                        ));
+                    }
+                )*
+            };
+        }
+        diff_fields!(
             league_id,
+            source,
             local_fetch,
             local_url,
+            replay_step_secs,
+            bind_address,
             server_port,
             asset_path,
+            admin_token,
+            fetch_interval_secs,
+            static_info_interval_secs,
+            lineup_check_before_deadline_mins,
+            log_format,
+            timeouts,
+            retry,
+            features,
+            webhooks
+        );
+        changes
+    }
+
+    pub fn source_kind(&self) -> SourceKind {
+        match (self.source, self.local_fetch) {
+            (Some(kind), _) => kind,
+            (None, Some(true)) => SourceKind::File,
+            (None, _) => SourceKind::Web,
+        }
+    }
+
+    // Creates a client reading from the configured source
+    pub fn create_client(&self) -> Result<Client, ClientError> {
+        let local_url = self
+            .local_url
+            .clone()
+            .unwrap_or_else(|| String::from(crate::client::DEFAULT_LOCAL_API_BASE));
+        let client = match self.source_kind() {
+            SourceKind::Web => Client::web(self.timeouts())?,
+            SourceKind::File => Client::with_source(Arc::new(FileSource::new(local_url))),
+            SourceKind::Replay => {
+                let step = Duration::from_secs(self.replay_step_secs);
+                Client::with_source(Arc::new(ReplaySource::new(local_url.as_ref(), step)?))
+            }
+        };
+        tracing::info!("Fetching endpoints from {}", client.describe_source());
+        Ok(client)
+    }
+}
+
+fn read_file(path: &Path) -> Result<Table, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read config file {}: {}", path.display(), e))?;
+    toml::from_str(&content)
+        .map_err(|e| format!("Could not parse config file {}: {}", path.display(), e))
+}
+
+// Merges a layer into the config so far, noting the origin of every setting it has
+fn merge(
+    into: &mut Table,
+    layer: Table,
+    prefix: &str,
+    origin: &str,
+    origins: &mut BTreeMap<String, String>,
+) {
+    for (name, value) in layer {
+        let key = format!("{}{}", prefix, name);
+        match (into.get_mut(&name), value) {
+            (Some(Value::Table(into)), Value::Table(layer)) => {
+                merge(into, layer, &format!("{}.", key), origin, origins)
+            }
+            (_, value) => {
+                into.insert(name, value);
+                origins.insert(key, String::from(origin));
+            }
         }
     }
 }
 
+// Sets a dotted key, eg. "features.graphql"
+fn insert(table: &mut Table, key: &str, value: Value) {
+    match key.split_once('.') {
+        Some((name, rest)) => {
+            let entry = table
+                .entry(name)
+                .or_insert_with(|| Value::Table(Table::new()));
+            if !entry.is_table() {
+                *entry = Value::Table(Table::new());
+            }
+            if let Value::Table(inner) = entry {
+                insert(inner, rest, value);
+            }
+        }
+        None => {
+            table.insert(String::from(key), value);
+        }
+    }
+}
+
+// Settings without a default, that are not in the defaults to take the type of the setting from
+fn optional_setting(key: &str) -> Option<Value> {
+    match key {
+        "local_fetch" => Some(Value::Boolean(false)),
+        "source" | "local_url" | "asset_path" | "admin_token" => Some(Value::String(String::new())),
+        _ => None,
+    }
+}
+
+// Parses a setting from the environment or the command line into the type of its default
+fn parse_setting(defaults: &Table, key: &str, raw: &str) -> Result<Value, String> {
+    let mut default = None;
+    let mut table = Some(defaults);
+    for name in key.split('.') {
+        default = table.and_then(|t| t.get(name));
+        table = default.and_then(Value::as_table);
+    }
+    let Some(default) = default.cloned().or_else(|| optional_setting(key)) else {
+        return Err(format!("{} is not a setting", key));
+    };
+    match default {
+        Value::Integer(_) => raw
+            .parse()
+            .map(Value::Integer)
+            .map_err(|_| format!("{} must be a whole number, got {:?}", key, raw)),
+        Value::Boolean(_) => raw
+            .parse()
+            .map(Value::Boolean)
+            .map_err(|_| format!("{} must be true or false, got {:?}", key, raw)),
+        Value::String(_) => Ok(Value::String(String::from(raw))),
+        Value::Array(_) => Err(format!("{} can only be set in the config file", key)),
+        _ => Err(format!("{} is a group of settings, set one of them", key)),
+    }
+}
+
 pub async fn initialize_app_context(client: &Client, league_id: u32) -> AppContext {
-    let game = client.get_game().await.unwrap();
-    game.current_event
-        .expect("No game week found when initializing appContext, must be preseason!");
+    fetch_app_context(client, league_id)
+        .await
+        .expect("Failed to initialize the app context")
+}
+
+// Fetches the teams of a league, with the default fetch intervals and retry policy
+pub async fn fetch_app_context(client: &Client, league_id: u32) -> Result<AppContext, String> {
+    let game = client.get_game().await.map_err(|e| e.to_string())?;
+    if game.current_event.is_none() {
+        return Err(String::from("No game week found, must be preseason!"));
+    }
     let details = client
         .get_league_details(&league_id)
         .await
-        .expect("Something went wrong with parsing league details in initialization");
+        .map_err(|e| format!("Could not fetch league {}: {}", league_id, e))?;
 
     let team_ids = details.league_entries.iter().map(|x| x.entry_id).collect();
 
@@ -98,10 +579,104 @@ pub async fn initialize_app_context(client: &Client, league_id: u32) -> AppConte
 
     let static_info_fetch_freq = Duration::from_millis(1_800_000);
 
-    AppContext {
+    Ok(AppContext {
         league_id,
         team_ids,
         fetch_sleep_duration,
         static_info_fetch_freq,
+        retry_policy: RetryPolicy::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (String::from(*key), String::from(*value)))
+            .collect()
+    }
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let path = env::temp_dir().join(format!("dof-layers-{}.toml", std::process::id()));
+        let file = "league_id = 1\nserver_port = 9000\nfetch_interval_secs = 30\n\
+            [features]\ngraphql = false\n\
+            [[webhooks]]\nurl = \"https://example.com/hooks/t0ken\"\nformat = \"discord\"\n";
+        fs::write(&path, file).unwrap();
+        let layers = ConfigLayers {
+            file: Some(path.clone()),
+            env: settings(&[
+                ("DOF_SERVER_PORT", "9001"),
+                ("DOF_RETRY__ATTEMPTS", "3"),
+                ("DOF_ADMIN_TOKEN", "hunter2"),
+                ("DOF_LOCAL_URL", ""),
+            ]),
+            cli: settings(&[("server_port", "9002"), ("features.debug", "false")]),
+        };
+        let loaded = AppConfig::load(&layers).unwrap();
+        let _ = fs::remove_file(&path);
+
+        let config = &loaded.config;
+        assert_eq!(config.server_port, 9002);
+        assert_eq!(config.fetch_interval_secs, 30);
+        assert_eq!(config.static_info_interval_secs, 1800);
+        assert_eq!(config.retry.attempts, 3);
+        assert_eq!(config.local_url, None);
+        assert_eq!(
+            config.features,
+            Features {
+                graphql: false,
+                debug: false,
+                frontend: true,
+            }
+        );
+        assert_eq!(loaded.origins["server_port"], "command line");
+        assert_eq!(loaded.origins["retry.attempts"], "env DOF_RETRY__ATTEMPTS");
+        assert!(loaded.origins["features.graphql"].starts_with("file "));
+        assert!(!loaded.origins.contains_key("local_url"));
+
+        let printed = config.to_toml();
+        assert!(printed.contains("server_port = 9002"), "{}", printed);
+        assert!(!printed.contains("hunter2"), "{}", printed);
+        assert!(!printed.contains("t0ken"), "{}", printed);
+        assert_eq!(config.webhooks[0].format, WebhookFormat::Discord);
+        assert_eq!(config.webhooks[0].events, WebhookEventKind::all());
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let layers = ConfigLayers {
+            env: settings(&[("DOF_SERVER_PORT", "eighty"), ("DOF_NOT_A_SETTING", "1")]),
+            cli: settings(&[("features", "false"), ("webhooks", "[]")]),
+            ..Default::default()
+        };
+        let errors = AppConfig::load(&layers).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "DOF_SERVER_PORT: server_port must be a whole number, got \"eighty\"",
+                "DOF_NOT_A_SETTING: not_a_setting is not a setting",
+                "Command line features: features is a group of settings, set one of them",
+                "Command line webhooks: webhooks can only be set in the config file",
+            ]
+        );
+
+        let layers = ConfigLayers {
+            cli: settings(&[("fetch_interval_secs", "0"), ("bind_address", "localhost")]),
+            ..Default::default()
+        };
+        let errors = AppConfig::load(&layers).unwrap_err();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors[0].starts_with("league_id must be set"));
+
+        let layers = ConfigLayers {
+            cli: settings(&[("league_id", "1"), ("log_format", "xml")]),
+            ..Default::default()
+        };
+        let errors = AppConfig::load(&layers).unwrap_err();
+        assert!(errors[0].contains("log_format"), "{:?}", errors);
     }
 }
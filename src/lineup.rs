@@ -0,0 +1,376 @@
+// Checks of the lineups of the league teams before the next deadline.
+//
+// The starters of every team are checked against the gameweek of the next deadline: a starter
+// that is unavailable or whose club has no fixture in the gameweek scores nothing, and a lineup
+// that breaks the formation rules of the league can't be saved as is. The warnings are served at
+// /api/v1/lineup-warnings, and the checker posts them to the webhooks once before each deadline.
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::watch;
+use utoipa::ToSchema;
+
+use crate::initializer::AppConfig;
+use crate::news;
+use crate::propcomp;
+use crate::storage::table::{Entry, InjuryStatus, Position};
+use crate::storage::{FplEndpoints, LeagueTable};
+use crate::webhooks::{self, WebhookEvent};
+
+// How often the checker looks at the time left to the next deadline
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The problems with the lineups of the league teams for the next deadline
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct LineupWarnings {
+    /// The gameweek of the next deadline
+    pub gw: u32,
+    /// The next deadline, in seconds since the unix epoch
+    pub deadline: u64,
+    /// The teams with at least one warning
+    pub teams: Vec<TeamWarnings>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct TeamWarnings {
+    pub team_code: u32,
+    pub team_name: String,
+    pub owner_name: String,
+    pub warnings: Vec<LineupWarning>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LineupWarning {
+    /// A starter that is injured, suspended or otherwise unavailable
+    Unavailable {
+        player_id: u32,
+        player_name: String,
+        news: Option<String>,
+    },
+    /// A starter whose club has no fixture in the gameweek
+    BlankGw { player_id: u32, player_name: String },
+    /// The starters do not make a valid formation
+    InvalidFormation { reason: String },
+}
+
+impl LineupWarning {
+    pub fn message(&self) -> String {
+        match self {
+            LineupWarning::Unavailable {
+                player_name, news, ..
+            } => match news {
+                Some(news) => format!("{} is unavailable: {}", player_name, news),
+                None => format!("{} is unavailable", player_name),
+            },
+            LineupWarning::BlankGw { player_name, .. } => {
+                format!("{} has no fixture", player_name)
+            }
+            LineupWarning::InvalidFormation { reason } => format!("Invalid formation: {}", reason),
+        }
+    }
+}
+
+// The gameweek with the first deadline after now, and the deadline
+pub fn next_deadline(endpoints: &FplEndpoints, now: u64) -> Option<(u32, u64)> {
+    endpoints
+        .static_info
+        .events
+        .data
+        .iter()
+        .filter_map(|e| {
+            let gw = u32::try_from(e.id?).ok()?;
+            let deadline = propcomp::parse_utc_time(e.deadline_time.as_deref()?)?;
+            Some((gw, deadline))
+        })
+        .filter(|(_, deadline)| *deadline > now)
+        .min_by_key(|(_, deadline)| *deadline)
+}
+
+// The warnings for the next deadline, none after the last deadline of the season
+pub fn compute_lineup_warnings(
+    endpoints: &FplEndpoints,
+    table: &LeagueTable,
+    now: u64,
+) -> Option<LineupWarnings> {
+    let (gw, deadline) = next_deadline(endpoints, now)?;
+    // The clubs playing in the gameweek, unknown if its fixtures are not in the static info
+    let playing: Option<HashSet<u32>> =
+        endpoints
+            .static_info
+            .fixtures
+            .get(&(gw as i32))
+            .map(|fixtures| {
+                fixtures
+                    .iter()
+                    .flat_map(|f| [f.team_h, f.team_a])
+                    .flatten()
+                    .map(|club| club as u32)
+                    .collect()
+            });
+    let limits = FormationLimits::from_endpoints(endpoints);
+
+    let teams = table
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            let warnings = check_lineup(entry, playing.as_ref(), &limits);
+            (!warnings.is_empty()).then(|| TeamWarnings {
+                team_code: entry.team_code,
+                team_name: entry.team_name.clone(),
+                owner_name: entry.owner_name.clone(),
+                warnings,
+            })
+        })
+        .collect();
+    Some(LineupWarnings {
+        gw,
+        deadline,
+        teams,
+    })
+}
+
+fn check_lineup(
+    entry: &Entry,
+    playing: Option<&HashSet<u32>>,
+    limits: &FormationLimits,
+) -> Vec<LineupWarning> {
+    let starters: Vec<_> = entry
+        .players
+        .iter()
+        .filter(|p| !p.selected_on_bench())
+        .collect();
+
+    let mut warnings = Vec::new();
+    for player in starters.iter() {
+        if player.status == InjuryStatus::Unavailable {
+            warnings.push(LineupWarning::Unavailable {
+                player_id: player.id,
+                player_name: player.display_name.clone(),
+                news: player.news.clone(),
+            });
+        }
+        if playing.is_some_and(|clubs| !clubs.contains(&player.team.id)) {
+            warnings.push(LineupWarning::BlankGw {
+                player_id: player.id,
+                player_name: player.display_name.clone(),
+            });
+        }
+    }
+    let positions: Vec<Position> = starters.iter().map(|p| p.team_pos).collect();
+    if let Err(reason) = limits.check(&positions) {
+        warnings.push(LineupWarning::InvalidFormation { reason });
+    }
+    warnings
+}
+
+// The number of starters and the starters by position allowed by the league
+struct FormationLimits {
+    starters: usize,
+    positions: [(Position, &'static str, usize, usize); 4],
+}
+
+impl FormationLimits {
+    // From the squad settings of the league, with the FPL rules for the missing settings
+    fn from_endpoints(endpoints: &FplEndpoints) -> FormationLimits {
+        let squad = &endpoints.static_info.settings.squad;
+        let limit = |setting: Option<i32>, default| {
+            setting
+                .and_then(|n| usize::try_from(n).ok())
+                .unwrap_or(default)
+        };
+        FormationLimits {
+            starters: limit(squad.play, 11),
+            positions: [
+                (
+                    Position::GK,
+                    "goalkeepers",
+                    limit(squad.min_play_GKP, 1),
+                    limit(squad.max_play_GKP, 1),
+                ),
+                (
+                    Position::DEF,
+                    "defenders",
+                    limit(squad.min_play_DEF, 3),
+                    limit(squad.max_play_DEF, 5),
+                ),
+                (
+                    Position::MID,
+                    "midfielders",
+                    limit(squad.min_play_MID, 2),
+                    limit(squad.max_play_MID, 5),
+                ),
+                (
+                    Position::FWD,
+                    "forwards",
+                    limit(squad.min_play_FWD, 1),
+                    limit(squad.max_play_FWD, 3),
+                ),
+            ],
+        }
+    }
+
+    fn check(&self, positions: &[Position]) -> Result<(), String> {
+        if positions.len() != self.starters {
+            return Err(format!(
+                "{} starters instead of {}",
+                positions.len(),
+                self.starters
+            ));
+        }
+        for (position, name, min, max) in self.positions.iter() {
+            let n = positions.iter().filter(|p| *p == position).count();
+            if n < *min || n > *max {
+                return Err(match min == max {
+                    true => format!("{} {} instead of {}", n, name, min),
+                    false => format!("{} {} instead of {} to {}", n, name, min, max),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+// Posts the lineup warnings to the webhooks once before every deadline, when the deadline is
+// closer than lineup_check_before_deadline_mins of the config
+pub async fn deadline_checker(
+    config: watch::Receiver<Arc<AppConfig>>,
+    endpoints_rx: watch::Receiver<Arc<FplEndpoints>>,
+    table_rx: watch::Receiver<Arc<LeagueTable>>,
+) {
+    let http = match webhooks::http_client() {
+        Ok(http) => http,
+        Err(e) => {
+            tracing::error!(
+                "Could not create the webhook client, not checking lineups: {}",
+                e
+            );
+            return;
+        }
+    };
+    // The gameweek whose lineups were checked last
+    let mut checked_gw = None;
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let config = config.borrow().clone();
+        let endpoints = endpoints_rx.borrow().clone();
+        let now = news::unix_time_now();
+        let Some((gw, deadline)) = next_deadline(&endpoints, now) else {
+            continue;
+        };
+        if checked_gw == Some(gw) || deadline - now > config.lineup_check_before_deadline_mins * 60
+        {
+            continue;
+        }
+        checked_gw = Some(gw);
+
+        let table = table_rx.borrow().clone();
+        let Some(warnings) = compute_lineup_warnings(&endpoints, &table, now) else {
+            continue;
+        };
+        tracing::info!(
+            "Checked the lineups for the GW {} deadline, {} teams have warnings",
+            gw,
+            warnings.teams.len()
+        );
+        if warnings.teams.is_empty() || config.webhooks.is_empty() {
+            continue;
+        }
+        let event = WebhookEvent::LineupWarnings(warnings);
+        webhooks::send(&http, &config.webhooks, table.code, &[event]).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer;
+    use crate::testdata;
+
+    #[test]
+    fn starters_are_checked_for_the_next_deadline() {
+        let mut endpoints = testdata::endpoints(3, false);
+        let next_gw = testdata::CURRENT_GW + 1;
+        let deadline = propcomp::get_gw_deadline(&endpoints, next_gw).unwrap();
+
+        // Team 1 starts an injured defender, and a midfielder of a club without a fixture
+        let injured = testdata::player_id(1, 2);
+        let blank = testdata::player_id(1, 6);
+        let blank_club = testdata::player_club(blank) as i32;
+        endpoints.modify(|data| {
+            let static_info = Arc::make_mut(&mut data.static_info);
+            let element = static_info
+                .elements
+                .iter_mut()
+                .find(|e| e.id == Some(injured as i32))
+                .unwrap();
+            element.status = String::from("i");
+            element.news = Some(String::from("Knee injury - Unknown return date"));
+            static_info
+                .fixtures
+                .get_mut(&(next_gw as i32))
+                .unwrap()
+                .retain(|f| f.team_h != Some(blank_club) && f.team_a != Some(blank_club));
+            // Team 2 starts its bench goalkeeper instead of a forward
+            let picks = &mut data
+                .teams_gws
+                .get_mut(&testdata::entry_id(2))
+                .unwrap()
+                .picks;
+            for pick in picks.iter_mut() {
+                match pick.position {
+                    11 => pick.position = 12,
+                    12 => pick.position = 11,
+                    _ => {}
+                }
+            }
+        });
+        let table = computer::compute_new_league_table(&endpoints).unwrap();
+
+        let warnings = compute_lineup_warnings(&endpoints, &table, deadline - 3600).unwrap();
+        assert_eq!((warnings.gw, warnings.deadline), (next_gw, deadline));
+        let team = |t: u32| {
+            warnings
+                .teams
+                .iter()
+                .find(|w| w.team_code == testdata::entry_id(t))
+        };
+        let team_1 = team(1).unwrap();
+        assert!(matches!(
+            team_1.warnings[0],
+            LineupWarning::Unavailable { player_id, .. } if player_id == injured
+        ));
+        // The clubs playing each other both have no fixture
+        let blanks: Vec<u32> = team_1
+            .warnings
+            .iter()
+            .filter_map(|w| match w {
+                LineupWarning::BlankGw { player_id, .. } => Some(*player_id),
+                _ => None,
+            })
+            .collect();
+        assert!(blanks.contains(&blank), "{:?}", team_1.warnings);
+        // Team 2 also starts a forward of the opponent of the blank club
+        let opponent = testdata::player_id(2, 10);
+        assert_eq!(
+            team(2).unwrap().warnings,
+            vec![
+                LineupWarning::BlankGw {
+                    player_id: opponent,
+                    player_name: format!("Player{}", opponent),
+                },
+                LineupWarning::InvalidFormation {
+                    reason: String::from("2 goalkeepers instead of 1")
+                }
+            ]
+        );
+        assert!(team(3).is_none());
+
+        // After the deadline the next gameweek is checked
+        let warnings = compute_lineup_warnings(&endpoints, &table, deadline + 1).unwrap();
+        assert_eq!(warnings.gw, next_gw + 1);
+    }
+}
@@ -1,10 +1,13 @@
-use std::ops::Deref;
-use std::sync::{Arc, RwLock};
+use std::path::PathBuf;
+use std::sync::Arc;
 
-use axum::{
-    extract::Path, extract::State, http::StatusCode, response::IntoResponse, routing::get, Router,
-};
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Router;
 use clap::Parser;
+use tokio::sync::watch;
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::CorsLayer;
 
 pub use initializer::AppContext;
@@ -13,90 +16,246 @@ use crate::client::Client;
 use crate::storage::{FplEndpoints, LeagueTable};
 use tracing_subscriber;
 
+mod api;
+mod awards;
+mod cache;
+mod changes;
+mod cli;
 mod client;
+mod compare;
 mod computer;
+mod events;
 mod fetcher;
+mod fixtures;
 mod initializer;
+mod lineup;
+mod metrics;
+#[cfg(test)]
+mod mockapi;
+mod news;
+mod players;
 mod propcomp;
+mod record;
+mod reload;
+mod standings;
 mod storage;
 mod structs;
+#[cfg(test)]
+mod testdata;
+mod trade;
+mod tui;
+mod webhooks;
 
 #[derive(Parser)]
 #[command(name = "Draught of FPL")]
 #[command(about = "Fantasy Premier League Draft League Proxy Server", long_about = None)]
 struct Cli {
-    /// Path to configuration file (TOML format). If not provided, uses environment variables.
+    /// Path to configuration file (TOML format). Environment variables and the flags below
+    /// override its settings.
     #[arg(short = 'f', long)]
     config_source: Option<String>,
+
+    /// The league to follow, overrides league_id
+    #[arg(long)]
+    league_id: Option<u32>,
+
+    /// The port of the server, overrides server_port
+    #[arg(long)]
+    port: Option<u16>,
+
+    /// The address the server listens on, overrides bind_address
+    #[arg(long)]
+    bind: Option<String>,
+
+    /// full, compact, pretty or json, overrides log_format
+    #[arg(long)]
+    log_format: Option<String>,
+
+    /// Overrides any setting, eg. --set retry.attempts=5 --set features.graphql=false
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    settings: Vec<(String, String)>,
+
+    #[command(subcommand)]
+    command: Option<cli::Command>,
+}
+
+impl Cli {
+    // The settings of the command line layer of the config
+    fn config_settings(&self) -> Vec<(String, String)> {
+        let flags = [
+            ("league_id", self.league_id.map(|id| id.to_string())),
+            ("server_port", self.port.map(|port| port.to_string())),
+            ("bind_address", self.bind.clone()),
+            ("log_format", self.log_format.clone()),
+        ];
+        flags
+            .into_iter()
+            .filter_map(|(key, value)| Some((String::from(key), value?)))
+            .chain(self.settings.iter().cloned())
+            .collect()
+    }
 }
 
+fn parse_key_value(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) => Ok((String::from(key.trim()), String::from(value))),
+        None => Err(format!("expected KEY=VALUE, got {:?}", arg)),
+    }
+}
+
+// The endpoints and the league table are immutable snapshots that are replaced as a whole by the
+// fetcher and computer tasks. Handlers clone the Arc of the latest snapshot and never hold a lock
+// while working with it.
 #[derive(Clone)]
 pub struct AppState {
-    endpoints: Arc<RwLock<FplEndpoints>>,
-    table: Arc<RwLock<LeagueTable>>,
+    endpoints: watch::Receiver<Arc<FplEndpoints>>,
+    table: watch::Receiver<Arc<LeagueTable>>,
+    // The latest league table serialized as json, served as is by /api/v1/table and /table
+    table_json: watch::Receiver<Arc<cache::CachedTable>>,
+    // What changed in the latest league table compared to the table before it
+    changes: watch::Receiver<Arc<changes::ChangeSet>>,
+    // The changes to the news of the players since the server started
+    news: watch::Receiver<Arc<news::NewsTimeline>>,
+    // The awards of finished gameweeks and the picks they are computed from
+    awards: Arc<awards::AwardsCache>,
+    client: Client,
+    fetcher: Arc<fetcher::FetcherControl>,
+    // The current config, replaced when the config file is reloaded
+    config: watch::Receiver<Arc<initializer::AppConfig>>,
 }
 
 // Use four worker threads to make sure the two spawned tasks do not starve the main server task, even if they fail to yield.
 #[tokio::main(flavor = "multi_thread", worker_threads = 4)]
 async fn main() {
-    tracing_subscriber::fmt::init();
     let cli = Cli::parse();
-    let app_config = initializer::AppConfig::initialize(cli.config_source);
-
-    let client = match app_config.local_fetch {
-        Some(true) => Client::new_local(app_config.local_url.clone()).unwrap(),
-        Some(false) | None => Client::new().unwrap(),
+    let layers = initializer::ConfigLayers::new(
+        cli.config_source.clone().map(PathBuf::from),
+        cli.config_settings(),
+    );
+    let command = cli.command.unwrap_or(cli::Command::Serve);
+    if let cli::Command::Config { .. } = command {
+        if let Err(e) = cli::check_config(&layers) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let app_config = match initializer::AppConfig::load(&layers) {
+        Ok(loaded) => loaded.config,
+        Err(errors) => {
+            eprintln!("{}", cli::render_config_errors(&errors));
+            std::process::exit(1);
+        }
     };
 
+    let one_shot = !matches!(command, cli::Command::Serve | cli::Command::Tui { .. });
+    let log_format = app_config.log_format;
+    match &command {
+        cli::Command::Serve => init_logging(log_format, std::io::stdout, true),
+        // Logs written to the terminal would overwrite the screen of the tui
+        cli::Command::Tui { log_file } => match log_file {
+            Some(path) => {
+                let file = std::fs::File::create(path).expect("Failed to create log file");
+                init_logging(log_format, std::sync::Mutex::new(file), false);
+            }
+            None => init_logging(log_format, std::io::sink, false),
+        },
+        // Keep stdout clean for the output of one-shot commands
+        _ => init_logging(log_format, std::io::stderr, true),
+    }
+    tracing::info!("Config used: {:?}", app_config);
+
+    // Recording always reads from the FPL api, whatever the config says
+    if let cli::Command::Record {
+        league_id,
+        out,
+        all_gws,
+        gw,
+        overwrite,
+        refresh,
+    } = command
+    {
+        let options = record::RecordOptions {
+            league_id: league_id.unwrap_or(app_config.league_id),
+            out_dir: out,
+            all_gws,
+            gws: gw,
+            overwrite,
+            refresh,
+        };
+        let client = Client::web(app_config.timeouts()).unwrap();
+        match record::record(&client, &options).await {
+            Ok(summary) => {
+                eprintln!(
+                    "Wrote {} files, skipped {} existing and failed {}",
+                    summary.written, summary.skipped, summary.failed
+                );
+                if summary.failed > 0 {
+                    std::process::exit(1);
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let client = app_config
+        .create_client()
+        .expect("Failed to create the client for the configured source");
+
     let league_id = app_config.league_id;
 
-    let app_context = Arc::new(initializer::initialize_app_context(&client, league_id).await);
+    let mut app_context = initializer::initialize_app_context(&client, league_id).await;
+    app_config.apply_to(&mut app_context);
 
-    let endpoints =
-        fetcher::fetch_and_initialize_endpoints(&client, app_context.deref().clone()).await;
+    let endpoints = fetcher::fetch_and_initialize_endpoints(&client, app_context.clone()).await;
 
-    let initialize_table_endpoints = endpoints.clone();
-    let table = computer::compute_new_league_table(initialize_table_endpoints)
+    let table = computer::compute_new_league_table(&endpoints)
         .expect("Failed to compute league table in main");
 
-    let endpoints = Arc::new(RwLock::new(endpoints));
-    let endpoints_fetch_clone = Arc::clone(&endpoints);
-    let endpoints_compute_clone = Arc::clone(&endpoints);
+    if one_shot {
+        if let Err(e) = cli::run(command, &endpoints, &table) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let table = Arc::new(RwLock::new(table));
-    let table_compute_clone = Arc::clone(&table);
+    let (config_tx, config_rx) = watch::channel(Arc::new(app_config.clone()));
 
-    let state = AppState {
-        endpoints: Arc::clone(&endpoints),
-        table: Arc::clone(&table),
-    };
+    // The tui only shows the table, so it does not notify, track news or reload the config
+    if let cli::Command::Tui { .. } = command {
+        let (state, _) = start_table_tasks(client, app_context, endpoints, table, config_rx);
+        if let Err(e) = tui::run(state.endpoints, state.table).await {
+            eprintln!("Failed to run the tui: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    tokio::spawn(fetcher::endpoint_cache_fetcher(
-        client,
-        endpoints_fetch_clone,
-        app_context,
-    ));
-    tokio::spawn(computer::league_table_computer(
-        table_compute_clone,
-        endpoints_compute_clone,
-    ));
+    let state = start_background_tasks(client, app_context, endpoints, table, config_rx);
+    match reload::Reloader::new(layers, config_tx, &state) {
+        Some(reloader) => {
+            tokio::spawn(reloader.run());
+        }
+        None => tracing::info!("The config is not reloaded without a config file"),
+    }
 
-    let frontend = tower_http::services::ServeDir::new(
-        app_config
-            .asset_path
-            .clone()
-            .unwrap_or_else(|| "./www/vue".into()),
-    );
     // Build the router with CORS middleware
     let app = Router::new()
-        .route("/fpl/player/:id", get(get_player))
-        .route("/table", get(get_table))
-        .nest_service("/", frontend)
+        .merge(api::router())
+        .fallback(serve_frontend)
         .with_state(state)
+        .layer(CompressionLayer::new())
         .layer(CorsLayer::permissive());
 
-    let port = app_config.server_port.unwrap_or(8000);
-    let serve_addr = format!("0.0.0.0:{port}");
+    // The config is validated, so it has an address
+    let serve_addr = app_config
+        .serve_address()
+        .expect("Invalid bind address in the config");
     // Run the server
     tracing::info!("Server running on http://{serve_addr}");
     let listener = tokio::net::TcpListener::bind(serve_addr).await.unwrap();
@@ -104,40 +263,96 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
-async fn get_player(
-    State(state): State<AppState>,
-    Path(id): Path<u32>,
-) -> Result<String, (StatusCode, String)> {
-    match state.endpoints.read() {
-        Ok(ep) => {
-            let full_name = propcomp::get_player_full_name(&*ep, id);
-            Ok(format!("Player: {} with id {}\n", full_name, id))
-        }
-        Err(_) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            String::from("Error reading endpoints"),
-        )),
+// Installs the global logger, writing logs in the format of the config
+fn init_logging<W>(format: initializer::LogFormat, writer: W, ansi: bool)
+where
+    W: for<'w> tracing_subscriber::fmt::MakeWriter<'w> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        initializer::LogFormat::Full => builder.init(),
+        initializer::LogFormat::Compact => builder.compact().init(),
+        initializer::LogFormat::Pretty => builder.pretty().init(),
+        initializer::LogFormat::Json => builder.json().init(),
     }
 }
 
-async fn get_table(
-    State(state): State<AppState>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    match state.table.read() {
-        Ok(t) => match serde_json::to_string(t.deref()) {
-            Ok(json) => Ok((
-                StatusCode::OK,
-                [(axum::http::header::CONTENT_TYPE, "application/json")],
-                json,
-            )),
-            Err(_) => Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                String::from("Failed to serialize league table"),
-            )),
-        },
-        Err(_) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            String::from("Error reading league table"),
-        )),
+// Serves the frontend from the asset path of the current config
+async fn serve_frontend(State(state): State<AppState>, request: Request) -> Response {
+    let config = state.config.borrow().clone();
+    if !config.features.frontend {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let mut frontend = tower_http::services::ServeDir::new(config.asset_path());
+    match frontend.try_call(request).await {
+        Ok(response) => response.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
 }
+
+// Spawns the fetcher and the computer that keep the endpoints and the league table up to date,
+// starting from the given snapshots, and the tasks of the server that follow them: the news
+// tracker, the webhooks notifier and the lineup deadline checker
+fn start_background_tasks(
+    client: Client,
+    context: AppContext,
+    endpoints: FplEndpoints,
+    table: LeagueTable,
+    config: watch::Receiver<Arc<initializer::AppConfig>>,
+) -> AppState {
+    let (state, news_tx) = start_table_tasks(client, context, endpoints, table, config);
+    tokio::spawn(news::news_tracker(news_tx, state.endpoints.clone()));
+    tokio::spawn(webhooks::notifier(state.config.clone(), state.table.clone()));
+    tokio::spawn(lineup::deadline_checker(
+        state.config.clone(),
+        state.endpoints.clone(),
+        state.table.clone(),
+    ));
+    state
+}
+
+// Spawns only the fetcher and the computer, starting from the given snapshots. The news timeline
+// stays empty unless a news tracker is spawned with the returned sender.
+fn start_table_tasks(
+    client: Client,
+    context: AppContext,
+    endpoints: FplEndpoints,
+    table: LeagueTable,
+    config: watch::Receiver<Arc<initializer::AppConfig>>,
+) -> (AppState, watch::Sender<Arc<news::NewsTimeline>>) {
+    let (endpoints_tx, endpoints_rx) = watch::channel(Arc::new(endpoints));
+    let table_json =
+        cache::CachedTable::new(&table).expect("Failed to serialize league table in main");
+    let (table_tx, table_rx) = watch::channel(Arc::new(table));
+    let (table_json_tx, table_json_rx) = watch::channel(Arc::new(table_json));
+    let (changes_tx, changes_rx) = watch::channel(Arc::new(changes::ChangeSet::full()));
+    let (news_tx, news_rx) = watch::channel(Arc::new(news::NewsTimeline::default()));
+    let fetcher_control = Arc::new(fetcher::FetcherControl::new(context));
+
+    let state = AppState {
+        endpoints: endpoints_rx.clone(),
+        table: table_rx,
+        table_json: table_json_rx,
+        changes: changes_rx,
+        news: news_rx,
+        awards: Arc::default(),
+        client: client.clone(),
+        fetcher: Arc::clone(&fetcher_control),
+        config,
+    };
+
+    tokio::spawn(fetcher::endpoint_cache_fetcher(
+        client,
+        endpoints_tx,
+        fetcher_control,
+    ));
+    tokio::spawn(computer::league_table_computer(
+        table_tx,
+        table_json_tx,
+        changes_tx,
+        endpoints_rx,
+    ));
+    (state, news_tx)
+}
@@ -0,0 +1,61 @@
+// Process wide counters, served at /metrics in the Prometheus text format.
+//
+// Counters are identified by their name and labels, eg.
+// fpl_schema_drift_responses_total{endpoint="static"}, and are created on the first increment.
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+
+type Labels = Vec<(String, String)>;
+
+fn counters() -> &'static Mutex<BTreeMap<(String, Labels), u64>> {
+    static COUNTERS: OnceLock<Mutex<BTreeMap<(String, Labels), u64>>> = OnceLock::new();
+    COUNTERS.get_or_init(Default::default)
+}
+
+pub fn increment(name: &str, labels: &[(&str, &str)]) {
+    let labels = labels
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    *counters()
+        .lock()
+        .unwrap()
+        .entry((name.to_string(), labels))
+        .or_default() += 1;
+}
+
+#[cfg(test)]
+pub fn get(name: &str, labels: &[(&str, &str)]) -> u64 {
+    let labels: Labels = labels
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    counters()
+        .lock()
+        .unwrap()
+        .get(&(name.to_string(), labels))
+        .copied()
+        .unwrap_or(0)
+}
+
+pub fn render() -> String {
+    let counters = counters().lock().unwrap();
+    let mut out = String::new();
+    let mut previous_name: Option<&str> = None;
+    for ((name, labels), value) in counters.iter() {
+        if previous_name != Some(name.as_str()) {
+            out.push_str(&format!("# TYPE {} counter\n", name));
+            previous_name = Some(name.as_str());
+        }
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(k, v)| format!("{}=\"{}\"", k, v.replace('\\', "\\\\").replace('"', "\\\"")))
+            .collect();
+        if labels.is_empty() {
+            out.push_str(&format!("{} {}\n", name, value));
+        } else {
+            out.push_str(&format!("{}{{{}}} {}\n", name, labels.join(","), value));
+        }
+    }
+    out
+}
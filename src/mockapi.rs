@@ -0,0 +1,381 @@
+// An in-process mock of the FPL draft api for tests.
+//
+// Serves json bodies by api path, either built from endpoints (see testdata) or read from a
+// database recorded with the record command. Faults can be injected per endpoint, and the bodies
+// can be replaced while the server runs to simulate the season moving on. The pipeline runs the
+// fetcher, the computer and the api against the mock, like main does against FPL.
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::http::{StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+
+use crate::api::types::LeagueTable;
+use crate::client::{Client, Endpoint, Timeouts, WebSource};
+use crate::storage::FplEndpoints;
+use crate::{api, computer, fetcher, initializer, testdata};
+
+// The prefix of every path served, like the FPL api
+const API_PREFIX: &str = "/api";
+
+#[derive(Debug, Clone)]
+pub enum Fault {
+    // Respond with this status and a non-json body
+    Status(StatusCode),
+    // Respond with 200 and a body that is cut off in the middle
+    MalformedJson,
+    // Respond normally after a delay
+    Delay(Duration),
+}
+
+#[derive(Default)]
+struct MockState {
+    // Bodies by api path, eg. /game
+    bodies: HashMap<String, String>,
+    faults: HashMap<String, Fault>,
+    // Added to every response
+    latency: Duration,
+    requests: HashMap<String, usize>,
+}
+
+pub struct MockFpl {
+    pub url: String,
+    state: Arc<Mutex<MockState>>,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl MockFpl {
+    pub async fn start(bodies: HashMap<String, String>) -> MockFpl {
+        let state = Arc::new(Mutex::new(MockState {
+            bodies,
+            ..Default::default()
+        }));
+        let app = Router::new().fallback({
+            let state = Arc::clone(&state);
+            move |uri: Uri| respond(Arc::clone(&state), uri)
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        MockFpl {
+            url: format!("http://{}{}", addr, API_PREFIX),
+            state,
+            server,
+        }
+    }
+
+    pub async fn from_endpoints(endpoints: &FplEndpoints, league_id: u32) -> MockFpl {
+        MockFpl::start(endpoint_bodies(endpoints, league_id)).await
+    }
+
+    // Serves a database recorded with the record command
+    pub async fn from_recorded(dir: &Path) -> io::Result<MockFpl> {
+        let mut bodies = HashMap::new();
+        read_recorded(dir, dir, &mut bodies)?;
+        Ok(MockFpl::start(bodies).await)
+    }
+
+    // A web client fetching from the mock
+    pub fn client(&self) -> Client {
+        let source = WebSource::new(self.url.clone(), Timeouts::default()).unwrap();
+        Client::with_source(Arc::new(source))
+    }
+
+    // Replaces every body, eg. with the endpoints of a later point in the season
+    pub fn set_endpoints(&self, endpoints: &FplEndpoints, league_id: u32) {
+        self.state.lock().unwrap().bodies = endpoint_bodies(endpoints, league_id);
+    }
+
+    pub fn fail(&self, endpoint: &Endpoint, fault: Fault) {
+        let mut state = self.state.lock().unwrap();
+        state.faults.insert(endpoint.path(), fault);
+    }
+
+    pub fn clear_faults(&self) {
+        self.state.lock().unwrap().faults.clear();
+    }
+
+    pub fn set_latency(&self, latency: Duration) {
+        self.state.lock().unwrap().latency = latency;
+    }
+
+    pub fn requests(&self, endpoint: &Endpoint) -> usize {
+        let state = self.state.lock().unwrap();
+        state.requests.get(&endpoint.path()).copied().unwrap_or(0)
+    }
+}
+
+impl Drop for MockFpl {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+fn endpoint_bodies(endpoints: &FplEndpoints, league_id: u32) -> HashMap<String, String> {
+    testdata::endpoint_bodies(endpoints, league_id)
+        .into_iter()
+        .map(|(endpoint, body)| (endpoint.path(), body))
+        .collect()
+}
+
+fn read_recorded(root: &Path, dir: &Path, bodies: &mut HashMap<String, String>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            read_recorded(root, &path, bodies)?;
+        } else {
+            let relative = path.strip_prefix(root).unwrap().to_string_lossy();
+            bodies.insert(format!("/{}", relative), std::fs::read_to_string(&path)?);
+        }
+    }
+    Ok(())
+}
+
+async fn respond(state: Arc<Mutex<MockState>>, uri: Uri) -> Response {
+    let path = uri.path().strip_prefix(API_PREFIX).unwrap_or(uri.path());
+    let (body, fault, latency) = {
+        let mut state = state.lock().unwrap();
+        *state.requests.entry(path.to_string()).or_default() += 1;
+        (
+            state.bodies.get(path).cloned(),
+            state.faults.get(path).cloned(),
+            state.latency,
+        )
+    };
+    tokio::time::sleep(latency).await;
+
+    let Some(body) = body else {
+        return (
+            StatusCode::NOT_FOUND,
+            "The resource you requested could not be found.",
+        )
+            .into_response();
+    };
+    match fault {
+        Some(Fault::Status(status)) => (status, "Mock failure").into_response(),
+        Some(Fault::MalformedJson) => {
+            let mut end = body.len() / 2;
+            while !body.is_char_boundary(end) {
+                end -= 1;
+            }
+            json_response(body[..end].to_string())
+        }
+        Some(Fault::Delay(delay)) => {
+            tokio::time::sleep(delay).await;
+            json_response(body)
+        }
+        None => json_response(body),
+    }
+}
+
+fn json_response(body: String) -> Response {
+    (
+        [(axum::http::header::CONTENT_TYPE, "application/json")],
+        body,
+    )
+        .into_response()
+}
+
+// The league of the pipeline
+pub const LEAGUE_ID: u32 = 1;
+
+// A running server with the fetcher and computer, like main, fetching from the mock
+pub struct Pipeline {
+    pub base_url: String,
+    pub table_url: String,
+    pub http: reqwest::Client,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+impl Pipeline {
+    pub async fn start(mock: &MockFpl) -> Pipeline {
+        Pipeline::start_with_admin(mock, None).await
+    }
+
+    pub async fn start_with_admin(mock: &MockFpl, admin_token: Option<&str>) -> Pipeline {
+        let client = mock.client();
+        let mut context = initializer::initialize_app_context(&client, LEAGUE_ID).await;
+        context.fetch_sleep_duration = Duration::from_millis(20);
+        let endpoints = fetcher::fetch_and_initialize_endpoints(&client, context.clone()).await;
+        let table = computer::compute_new_league_table(&endpoints).unwrap();
+        let config = initializer::AppConfig {
+            league_id: LEAGUE_ID,
+            admin_token: admin_token.map(initializer::Secret::new),
+            ..Default::default()
+        };
+        let (_, config) = tokio::sync::watch::channel(Arc::new(config));
+        let state = crate::start_background_tasks(client, context, endpoints, table, config);
+
+        let app = api::router().with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        Pipeline {
+            base_url: format!("http://{}", addr),
+            table_url: format!("http://{}/api/v1/table", addr),
+            http: reqwest::Client::new(),
+            server,
+        }
+    }
+
+    pub async fn table(&self) -> LeagueTable {
+        let response = self.http.get(&self.table_url).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        response.json().await.unwrap()
+    }
+
+    // Polls /api/v1/table until the condition holds, panics if it does not within a few seconds
+    pub async fn wait_for(&self, condition: impl Fn(&LeagueTable) -> bool) -> LeagueTable {
+        let start = Instant::now();
+        loop {
+            let table = self.table().await;
+            if condition(&table) {
+                return table;
+            }
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "The table did not reach the expected state"
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+}
+
+pub fn gw_points(table: &LeagueTable, team: u32) -> i32 {
+    let entry = table
+        .entries
+        .iter()
+        .find(|e| e.team_code == testdata::entry_id(team));
+    entry.unwrap().gw_points
+}
+
+// The full pipeline against the mock: fetcher -> computer -> /table
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record;
+
+    #[tokio::test]
+    async fn table_follows_the_api() {
+        let mut endpoints = testdata::endpoints(4, true);
+        let mock = MockFpl::from_endpoints(&endpoints, LEAGUE_ID).await;
+        // A slow team does not hold back the rest of the table
+        let slow = Endpoint::TeamGw {
+            team: testdata::entry_id(4),
+            gw: testdata::CURRENT_GW,
+        };
+        mock.fail(&slow, Fault::Delay(Duration::from_millis(200)));
+        let pipeline = Pipeline::start(&mock).await;
+
+        let table = pipeline.table().await;
+        assert_eq!(table.entries.len(), 4);
+        let before = gw_points(&table, 1);
+
+        // A player of team 1 scores during the gameweek
+        let player = testdata::player_id(1, 10);
+        let player_before = endpoints.live.elements[&player].stats.total_points;
+        testdata::set_player_points(&mut endpoints, player, player_before + 10, 90);
+        mock.set_endpoints(&endpoints, LEAGUE_ID);
+
+        pipeline.wait_for(|t| gw_points(t, 1) == before + 10).await;
+    }
+
+    #[tokio::test]
+    async fn failing_endpoints_keep_the_previous_data() {
+        let mut endpoints = testdata::endpoints(4, false);
+        let mock = MockFpl::from_endpoints(&endpoints, LEAGUE_ID).await;
+        mock.set_latency(Duration::from_millis(30));
+        let pipeline = Pipeline::start(&mock).await;
+        let before = gw_points(&pipeline.table().await, 2);
+
+        let live = Endpoint::Live {
+            gw: testdata::CURRENT_GW,
+        };
+        let team_gw = Endpoint::TeamGw {
+            team: testdata::entry_id(3),
+            gw: testdata::CURRENT_GW,
+        };
+        mock.fail(&live, Fault::Status(StatusCode::INTERNAL_SERVER_ERROR));
+        mock.fail(&team_gw, Fault::MalformedJson);
+        let player = testdata::player_id(2, 1);
+        testdata::set_player_points(&mut endpoints, player, 20, 90);
+        mock.set_endpoints(&endpoints, LEAGUE_ID);
+
+        // Let the fetcher run into the faults a few times
+        let requests = mock.requests(&live);
+        let start = Instant::now();
+        while mock.requests(&live) < requests + 3 {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let table = pipeline.table().await;
+        assert_eq!(gw_points(&table, 2), before);
+        assert_eq!(table.entries.len(), 4);
+
+        mock.clear_faults();
+        pipeline.wait_for(|t| gw_points(t, 2) != before).await;
+    }
+
+    #[tokio::test]
+    async fn new_gameweek_is_picked_up() {
+        let mut endpoints = testdata::endpoints(4, false);
+        let mock = MockFpl::from_endpoints(&endpoints, LEAGUE_ID).await;
+        let pipeline = Pipeline::start(&mock).await;
+        pipeline.table().await;
+
+        // The next gameweek starts and nobody has played yet
+        let next_gw = testdata::CURRENT_GW + 1;
+        endpoints.modify(|data| data.game.current_event = Some(next_gw));
+        for fixture in 1..=testdata::N_CLUBS / 2 {
+            testdata::set_fixture_not_started(&mut endpoints, fixture);
+        }
+        mock.set_endpoints(&endpoints, LEAGUE_ID);
+
+        pipeline
+            .wait_for(|t| t.entries.iter().all(|e| e.gw_points == 0))
+            .await;
+        assert!(mock.requests(&Endpoint::Live { gw: next_gw }) > 0);
+    }
+
+    #[tokio::test]
+    async fn recorded_database_is_served() {
+        let endpoints = testdata::endpoints(3, true);
+        let mock = MockFpl::from_endpoints(&endpoints, LEAGUE_ID).await;
+
+        let dir = std::env::temp_dir().join(format!("dof-mock-recorded-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let options = record::RecordOptions {
+            league_id: LEAGUE_ID,
+            out_dir: dir.clone(),
+            all_gws: false,
+            gws: Vec::new(),
+            overwrite: false,
+            refresh: Vec::new(),
+        };
+        let summary = record::record(&mock.client(), &options).await.unwrap();
+        assert_eq!(summary.failed, 0);
+
+        let recorded = MockFpl::from_recorded(&dir).await.unwrap();
+        let live = recorded
+            .client()
+            .get_gw_points_live(&testdata::CURRENT_GW)
+            .await
+            .unwrap();
+        assert_eq!(live, *endpoints.live);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
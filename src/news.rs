@@ -0,0 +1,295 @@
+// A timeline of the changes to the news, injury status and chance of playing of the players.
+//
+// The FPL api only has the current news of a player, so the tracker compares the players of every
+// refreshed static info with the ones before it. The timeline has every player, /api/v1/news
+// shows the changes of the players owned in the league and flags the starters that became
+// doubtful since the last deadline, while there is still time to change the lineup.
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tokio::sync::watch;
+use utoipa::ToSchema;
+
+use crate::propcomp;
+use crate::storage::table::InjuryStatus;
+use crate::storage::{FplEndpoints, LeagueTable};
+use crate::structs::staticinfo::{Element, StaticInfo};
+
+// The number of changes kept, the oldest are dropped first
+const MAX_CHANGES: usize = 1000;
+
+/// The news of a player at one point in time
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct PlayerNews {
+    pub status: InjuryStatus,
+    pub news: Option<String>,
+    /// In percent, none when there is no doubt
+    pub chance_of_playing_next_round: Option<i32>,
+}
+
+impl PlayerNews {
+    fn from_element(element: &Element) -> PlayerNews {
+        PlayerNews {
+            status: InjuryStatus::from_fpl_str(&element.status),
+            news: element.news.clone().filter(|n| !n.is_empty()),
+            chance_of_playing_next_round: element.chance_of_playing_next_round,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewsChange {
+    // When the change was found, in seconds since the unix epoch
+    pub time: u64,
+    pub player_id: u32,
+    pub player_name: String,
+    pub previous: PlayerNews,
+    pub current: PlayerNews,
+}
+
+impl NewsChange {
+    // Whether the player is less likely to play than before
+    pub fn became_doubtful(&self) -> bool {
+        let severity = |status: InjuryStatus| match status {
+            InjuryStatus::Available => 0,
+            InjuryStatus::Doubt => 1,
+            InjuryStatus::Unavailable => 2,
+        };
+        let chance = |news: &PlayerNews| news.chance_of_playing_next_round.unwrap_or(100);
+        severity(self.current.status) > severity(self.previous.status)
+            || chance(&self.current) < chance(&self.previous)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NewsTimeline {
+    // Oldest first
+    pub changes: VecDeque<NewsChange>,
+}
+
+impl NewsTimeline {
+    pub fn extend(&mut self, changes: Vec<NewsChange>) {
+        self.changes.extend(changes);
+        while self.changes.len() > MAX_CHANGES {
+            self.changes.pop_front();
+        }
+    }
+}
+
+// The players whose news changed between two static infos. New players have no changes.
+pub fn compute_news_changes(
+    previous: &StaticInfo,
+    current: &StaticInfo,
+    time: u64,
+) -> Vec<NewsChange> {
+    let previous_news: HashMap<i32, PlayerNews> = previous
+        .elements
+        .iter()
+        .filter_map(|e| Some((e.id?, PlayerNews::from_element(e))))
+        .collect();
+
+    let mut changes = Vec::new();
+    for element in current.elements.iter() {
+        let Some(id) = element.id else {
+            continue;
+        };
+        let Some(previous) = previous_news.get(&id) else {
+            continue;
+        };
+        let current = PlayerNews::from_element(element);
+        if current != *previous {
+            changes.push(NewsChange {
+                time,
+                player_id: id as u32,
+                player_name: element.web_name.clone(),
+                previous: previous.clone(),
+                current,
+            });
+        }
+    }
+    changes
+}
+
+/// The news of the players owned in the league, newest first
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct LeagueNews {
+    /// The deadline of the next gameweek, in seconds since the unix epoch
+    pub next_deadline: Option<u64>,
+    pub changes: Vec<LeagueNewsChange>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct LeagueNewsChange {
+    /// When the change was found, in seconds since the unix epoch
+    pub time: u64,
+    pub player_id: u32,
+    pub player_name: String,
+    /// The league team owning the player
+    pub team_code: u32,
+    pub team_name: String,
+    /// Whether the player is in the starting eleven of the team
+    pub starter: bool,
+    pub previous: PlayerNews,
+    pub current: PlayerNews,
+    /// The player is a starter that became doubtful since the last deadline and the next deadline
+    /// has not passed
+    pub flagged: bool,
+}
+
+pub fn compute_league_news(
+    timeline: &NewsTimeline,
+    endpoints: &FplEndpoints,
+    table: &LeagueTable,
+    now: u64,
+) -> LeagueNews {
+    let owners: HashMap<u32, (u32, &str, bool)> = table
+        .entries
+        .iter()
+        .flat_map(|e| {
+            e.players.iter().map(move |p| {
+                let owner = (e.team_code, e.team_name.as_str(), !p.selected_on_bench());
+                (p.id, owner)
+            })
+        })
+        .collect();
+    let last_deadline = endpoints
+        .game
+        .current_event
+        .and_then(|gw| propcomp::get_gw_deadline(endpoints, gw));
+    let next_deadline = endpoints
+        .game
+        .next_event
+        .and_then(|gw| propcomp::get_gw_deadline(endpoints, gw));
+
+    let changes = timeline
+        .changes
+        .iter()
+        .rev()
+        .filter_map(|change| {
+            let (team_code, team_name, starter) = *owners.get(&change.player_id)?;
+            let before_next_deadline = next_deadline.is_some_and(|d| change.time < d && now < d);
+            let after_last_deadline = last_deadline.is_none_or(|d| change.time > d);
+            Some(LeagueNewsChange {
+                time: change.time,
+                player_id: change.player_id,
+                player_name: change.player_name.clone(),
+                team_code,
+                team_name: String::from(team_name),
+                starter,
+                previous: change.previous.clone(),
+                current: change.current.clone(),
+                flagged: starter
+                    && change.became_doubtful()
+                    && after_last_deadline
+                    && before_next_deadline,
+            })
+        })
+        .collect();
+    LeagueNews {
+        next_deadline,
+        changes,
+    }
+}
+
+pub fn unix_time_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Adds the news changes of every refreshed static info to the timeline
+pub async fn news_tracker(
+    news_tx: watch::Sender<Arc<NewsTimeline>>,
+    mut endpoints_rx: watch::Receiver<Arc<FplEndpoints>>,
+) {
+    let mut previous = endpoints_rx.borrow_and_update().static_info.clone();
+    loop {
+        if endpoints_rx.changed().await.is_err() {
+            tracing::error!("Endpoints channel closed, stopping news tracker");
+            return;
+        }
+        let static_info = endpoints_rx.borrow_and_update().static_info.clone();
+        // The static info is shared between the endpoints until it is refreshed
+        if Arc::ptr_eq(&previous, &static_info) {
+            continue;
+        }
+        let changes = compute_news_changes(&previous, &static_info, unix_time_now());
+        previous = static_info;
+        if changes.is_empty() {
+            continue;
+        }
+        tracing::info!("News changed for {} players", changes.len());
+        news_tx.send_modify(|timeline| Arc::make_mut(timeline).extend(changes));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer;
+    use crate::testdata;
+
+    fn set_news(endpoints: &mut FplEndpoints, player_id: u32, status: &str, chance: Option<i32>) {
+        endpoints.modify(|data| {
+            let element = Arc::make_mut(&mut data.static_info)
+                .elements
+                .iter_mut()
+                .find(|e| e.id == Some(player_id as i32))
+                .unwrap();
+            element.status = String::from(status);
+            element.news = Some(String::from("Hamstring injury"));
+            element.chance_of_playing_next_round = chance;
+        });
+    }
+
+    #[test]
+    fn changes_of_owned_players_are_shown_and_doubtful_starters_flagged() {
+        let mut endpoints = testdata::endpoints_with_unowned(2, false, 5);
+        let table = computer::compute_new_league_table(&endpoints).unwrap();
+        let before = endpoints.static_info.clone();
+
+        let starter = testdata::player_id(1, 2);
+        let bench = testdata::player_id(2, 13);
+        let unowned = 2 * testdata::SQUAD_SIZE + 1;
+        set_news(&mut endpoints, starter, "d", Some(50));
+        set_news(&mut endpoints, bench, "i", Some(0));
+        set_news(&mut endpoints, unowned, "i", Some(0));
+
+        let last_deadline = propcomp::get_gw_deadline(&endpoints, testdata::CURRENT_GW).unwrap();
+        let next_deadline =
+            propcomp::get_gw_deadline(&endpoints, testdata::CURRENT_GW + 1).unwrap();
+        assert_eq!(next_deadline - last_deadline, 86_400);
+
+        let mut timeline = NewsTimeline::default();
+        let early = compute_news_changes(&before, &endpoints.static_info, last_deadline - 60);
+        let late = compute_news_changes(&before, &endpoints.static_info, last_deadline + 60);
+        assert_eq!(late.len(), 3);
+        timeline.extend(early);
+        timeline.extend(late);
+
+        let news = compute_league_news(&timeline, &endpoints, &table, last_deadline + 120);
+        let shown: Vec<(u32, u64, bool)> = news
+            .changes
+            .iter()
+            .map(|c| (c.player_id, c.time, c.flagged))
+            .collect();
+        assert_eq!(
+            shown,
+            vec![
+                (bench, last_deadline + 60, false),
+                (starter, last_deadline + 60, true),
+                (bench, last_deadline - 60, false),
+                (starter, last_deadline - 60, false),
+            ]
+        );
+        assert_eq!(news.next_deadline, Some(next_deadline));
+        assert_eq!(news.changes[1].current.status, InjuryStatus::Doubt);
+
+        // Nothing is flagged once the deadline has passed
+        let news = compute_league_news(&timeline, &endpoints, &table, next_deadline + 1);
+        assert!(news.changes.iter().all(|c| !c.flagged));
+    }
+}
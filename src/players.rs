@@ -0,0 +1,70 @@
+// Information about a single Premier League player, whether or not the player is owned by a
+// league team.
+use async_graphql::SimpleObject;
+use serde::{Deserialize, Serialize};
+
+use crate::propcomp;
+use crate::storage::table::{InjuryStatus, Position, Team};
+use crate::storage::{FplEndpoints, LeagueTable};
+
+#[derive(Serialize, Deserialize, Debug, SimpleObject)]
+pub struct PlayerInfo {
+    pub id: u32,
+    pub display_name: String,
+    pub full_name: String,
+    pub team: Team,
+    pub position: Position,
+    pub status: InjuryStatus,
+    pub news: Option<String>,
+
+    /// Points in the current gameweek
+    pub gw_points: i32,
+
+    /// Total points this season
+    pub season_points: i32,
+
+    /// Average points per match over the last 30 days
+    pub form: f64,
+
+    /// Expected points in the next gameweek
+    pub expected_points: f64,
+
+    /// The team code of the league team owning the player, if any
+    pub owner: Option<u32>,
+}
+
+// Returns None if the player does not exist
+pub fn compute_player_info(
+    endpoints: &FplEndpoints,
+    table: &LeagueTable,
+    id: u32,
+) -> Option<PlayerInfo> {
+    if !endpoints.index().static_elements.contains_key(&id) {
+        return None;
+    }
+    let owner = table
+        .entries
+        .iter()
+        .find(|e| e.players.iter().any(|p| p.id == id))
+        .map(|e| e.team_code);
+    let gw_points = if endpoints.live.elements.contains_key(&id) {
+        propcomp::get_player_points(endpoints, id)
+    } else {
+        0
+    };
+
+    Some(PlayerInfo {
+        id,
+        display_name: propcomp::get_player_display_name(endpoints, id),
+        full_name: propcomp::get_player_full_name(endpoints, id),
+        team: propcomp::compute_player_team(endpoints, id),
+        position: Position::from_number(propcomp::get_player_position(endpoints, id)),
+        status: propcomp::get_player_injury_status(endpoints, id),
+        news: propcomp::get_player_news(endpoints, id),
+        gw_points,
+        season_points: propcomp::get_player_season_points(endpoints, id),
+        form: propcomp::get_player_form(endpoints, id),
+        expected_points: propcomp::get_player_expected_points(endpoints, id),
+        owner,
+    })
+}
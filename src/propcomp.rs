@@ -5,18 +5,22 @@
 // There are two different IDs for each league team and it is not exactly
 // understood what the difference is, but they both are used for different
 // parts of the FPL api.
+//
+// Lookups by ID go through the index of the endpoints, which is rebuilt every time the endpoints
+// are updated.
 use crate::storage::{
     table::{InjuryStatus, PointSource as TablePointSource, Scoring, Team as TableTeam},
     FplEndpoints,
 };
 use crate::structs::{
+    details::TeamStandings,
     live::{
         Element as LiveElement, Fixture as LiveFixture, FixtureStat, Point as LivePoint,
         PointsOrFixture,
         PointsOrFixture::{Fixture, Points},
     },
-    staticinfo::Element as StaticElement,
-    teamgw::{Pick as TeamGwPick, TeamGw},
+    staticinfo::{Element as StaticElement, Fixture as StaticFixture, Team as StaticTeam},
+    teamgw::Pick as TeamGwPick,
     teaminfo::Entry as TeamInfoEntry,
 };
 
@@ -62,19 +66,46 @@ pub fn get_player_news(endpoints: &FplEndpoints, player_id: u32) -> Option<Strin
     }
 }
 
+// Total points of the player this season as reported by bootstrap-static
+pub fn get_player_season_points(endpoints: &FplEndpoints, player_id: u32) -> i32 {
+    get_player_from_static(endpoints, player_id)
+        .total_points
+        .unwrap_or(0)
+}
+
+// FPL form of the player (average points per match over the last 30 days). Returns 0 if unknown.
+pub fn get_player_form(endpoints: &FplEndpoints, player_id: u32) -> f64 {
+    parse_decimal(&get_player_from_static(endpoints, player_id).form)
+}
+
+// FPL expected points of the player in the next gameweek. Returns 0 if unknown.
+pub fn get_player_expected_points(endpoints: &FplEndpoints, player_id: u32) -> f64 {
+    parse_decimal(&get_player_from_static(endpoints, player_id).ep_next)
+}
+
+// FPL expected points of the player in the current gameweek. Returns 0 if unknown.
+pub fn get_player_expected_points_this_gw(endpoints: &FplEndpoints, player_id: u32) -> f64 {
+    parse_decimal(&get_player_from_static(endpoints, player_id).ep_this)
+}
+
+// FPL serializes decimals in bootstrap-static as strings, eg. "4.5"
+fn parse_decimal(value: &Option<String>) -> f64 {
+    value
+        .as_ref()
+        .and_then(|v| v.parse::<f64>().ok())
+        .unwrap_or(0.0)
+}
+
 // "is on field" happens if the player was selected to play on field or is a part of the substitutes
-pub fn compute_player_is_on_field(pick: &TeamGwPick, team_gw: &TeamGw) -> bool {
+pub fn compute_player_is_on_field(
+    endpoints: &FplEndpoints,
+    team_id: u32,
+    pick: &TeamGwPick,
+) -> bool {
     let selected_on_field = pick.position <= 11;
-    let substituted_in = team_gw
-        .subs
-        .iter()
-        .find(|x| x.element_in == pick.element)
-        .is_some();
-    let substituted_out = team_gw
-        .subs
-        .iter()
-        .find(|x| x.element_out == pick.element)
-        .is_some();
+    let key = (team_id, pick.element as u32);
+    let substituted_in = endpoints.index().subbed_in.contains(&key);
+    let substituted_out = endpoints.index().subbed_out.contains(&key);
 
     (selected_on_field && !substituted_out) || substituted_in
 }
@@ -96,17 +127,14 @@ pub fn compute_player_has_upcoming_fixtures(endpoints: &FplEndpoints, player_id:
 
 pub fn compute_player_team(endpoints: &FplEndpoints, player_id: u32) -> TableTeam {
     let team_id = get_player_team_id(endpoints, player_id);
-    let team = endpoints
-        .static_info
-        .teams
-        .iter()
-        .find(|team| team_id == team.id)
-        .expect(&format!(
+    let team = get_team_from_static(endpoints, team_id).unwrap_or_else(|| {
+        panic!(
             "Can't find team with ID: {} for player with id {}",
             team_id, player_id
-        ));
+        )
+    });
     TableTeam {
-        id: team.id as u32,
+        id: team.id,
         name: String::from(&team.name),
         short_name: String::from(&team.short_name),
         code: team.code as u32,
@@ -185,10 +213,12 @@ fn calculate_point_sources(
                 };
             })
         })
-        .expect(&format!(
-            "Could not find fixture {} when computing point sources (parsing explain object)",
-            fixture_id
-        ));
+        .unwrap_or_else(|| {
+            panic!(
+                "Could not find fixture {} when computing point sources (parsing explain object)",
+                fixture_id
+            )
+        });
 
     let mut point_sources = Vec::new();
     for pof in fixture {
@@ -199,8 +229,8 @@ fn calculate_point_sources(
     point_sources
 }
 
-// Calculate bonus points
-fn calculate_bonus_points(fixture: &LiveFixture, player_id: u32) -> i32 {
+// Calculate bonus points from the BPS of the fixture
+pub fn calculate_bonus_points(fixture: &LiveFixture, player_id: u32) -> i32 {
     let fixture_minutes = fixture.minutes;
     for stat in fixture.stats.iter() {
         if stat.s.eq("bps") {
@@ -238,17 +268,29 @@ fn calculate_bonus_points(fixture: &LiveFixture, player_id: u32) -> i32 {
 }
 
 pub fn get_player_current_fixtures(endpoints: &FplEndpoints, player_id: u32) -> Vec<&LiveFixture> {
-    let ids = get_player_current_fixture_ids(endpoints, player_id);
-    let mut fixtures = Vec::new();
-    let live_fixtures = &endpoints.live.fixtures;
-    for fixture in live_fixtures.iter() {
-        for id in ids.iter() {
-            if *id == fixture.id {
-                fixtures.push(fixture);
-            }
-        }
-    }
-    fixtures
+    get_player_current_fixture_ids(endpoints, player_id)
+        .into_iter()
+        .filter_map(|id| get_live_fixture(endpoints, id))
+        .collect()
+}
+
+pub fn get_live_fixture(endpoints: &FplEndpoints, fixture_id: u32) -> Option<&LiveFixture> {
+    endpoints
+        .index()
+        .live_fixtures
+        .get(&fixture_id)
+        .and_then(|i| endpoints.live.fixtures.get(*i))
+        .filter(|f| f.id == fixture_id)
+}
+
+// The Premier League team with the ID, not a league team
+pub fn get_team_from_static(endpoints: &FplEndpoints, team_id: u32) -> Option<&StaticTeam> {
+    endpoints
+        .index()
+        .teams
+        .get(&team_id)
+        .and_then(|i| endpoints.static_info.teams.get(*i))
+        .filter(|t| t.id == team_id)
 }
 
 pub fn get_player_current_fixture_ids(endpoints: &FplEndpoints, player_id: u32) -> Vec<u32> {
@@ -262,6 +304,18 @@ pub fn get_player_current_fixture_ids(endpoints: &FplEndpoints, player_id: u32)
     fixtures
 }
 
+// The upcoming fixtures of a Premier League team, ordered by gameweek. Only the next few
+// gameweeks are available in bootstrap-static, and fixtures that already started are left out.
+pub fn get_team_upcoming_fixtures(endpoints: &FplEndpoints, team_id: u32) -> Vec<&StaticFixture> {
+    let mut gws: Vec<&i32> = endpoints.static_info.fixtures.keys().collect();
+    gws.sort();
+    gws.iter()
+        .flat_map(|gw| endpoints.static_info.fixtures[gw].iter())
+        .filter(|f| f.team_h == Some(team_id as i32) || f.team_a == Some(team_id as i32))
+        .filter(|f| f.started != Some(true) && f.finished != Some(true))
+        .collect()
+}
+
 pub fn get_league_name(endpoints: &FplEndpoints) -> String {
     String::from(&endpoints.details.league.name)
 }
@@ -316,11 +370,16 @@ pub fn get_current_h2h_opponent(endpoints: &FplEndpoints, team_id: u32) -> u32 {
     // The h2hmatch structure uses entry_id as the team identifier
     let team_id = get_team_id_from_entry_id(endpoints, team_id);
     if let Some(league_matches) = &endpoints.details.matches {
-        let h2h_match_opt = league_matches.iter().find(|league_match| {
-            league_match.event == current_gw
-                && (league_match.league_entry_1 == team_id
-                    || league_match.league_entry_2 == team_id)
-        });
+        let h2h_match_opt = endpoints
+            .index()
+            .current_matches
+            .get(&team_id)
+            .and_then(|i| league_matches.get(*i))
+            .filter(|league_match| {
+                league_match.event == current_gw
+                    && (league_match.league_entry_1 == team_id
+                        || league_match.league_entry_2 == team_id)
+            });
         if let Some(h2h_match) = h2h_match_opt {
             let opponent = if h2h_match.league_entry_1 == team_id {
                 h2h_match.league_entry_2
@@ -336,40 +395,48 @@ pub fn get_current_h2h_opponent(endpoints: &FplEndpoints, team_id: u32) -> u32 {
 // Returns 0 if ID does not exist.
 pub fn get_team_id_from_entry_id(endpoints: &FplEndpoints, entry_id: u32) -> u32 {
     endpoints
-        .details
-        .league_entries
-        .iter()
-        .find(|entry| entry.entry_id == entry_id)
-        .map(|entry| entry.id)
+        .index()
+        .league_entry_ids
+        .get(&entry_id)
+        .copied()
         .unwrap_or(0)
 }
 
 // Returns 0 if ID does not exist.
 pub fn get_entry_id_from_team_id(endpoints: &FplEndpoints, team_id: u32) -> u32 {
     endpoints
-        .details
-        .league_entries
-        .iter()
-        .find(|entry| entry.id == team_id)
-        .map(|entry| entry.entry_id)
+        .index()
+        .entry_ids
+        .get(&team_id)
+        .copied()
         .unwrap_or(0)
 }
 
+// The standings of a league team, by the ID used in the matches and standings (team_id)
+pub fn get_team_standings(endpoints: &FplEndpoints, team_id: u32) -> Option<&TeamStandings> {
+    endpoints
+        .index()
+        .standings
+        .get(&team_id)
+        .and_then(|i| endpoints.details.standings.get(*i))
+        .filter(|s| s.league_entry == team_id)
+}
+
 fn get_player_from_static(endpoints: &FplEndpoints, player_id: u32) -> &StaticElement {
-    let i = (player_id - 1) as usize;
     endpoints
-        .static_info
-        .elements
-        .get(i)
-        .expect(format!("Player Id {} does not exist in bootstrap-static", player_id).as_str())
+        .index()
+        .static_elements
+        .get(&player_id)
+        .and_then(|i| endpoints.static_info.elements.get(*i))
+        .unwrap_or_else(|| panic!("Player Id {} does not exist in bootstrap-static", player_id))
 }
 
 fn get_player_from_live(endpoints: &FplEndpoints, player_id: u32) -> &LiveElement {
     endpoints
         .live
         .elements
-        .get(player_id.to_string().as_str())
-        .expect(format!("Player Id {} does not exist in live endpoint", player_id).as_str())
+        .get(&player_id)
+        .unwrap_or_else(|| panic!("Player Id {} does not exist in live endpoint", player_id))
 }
 
 fn get_team_info_entry(endpoints: &FplEndpoints, team_id: u32) -> Option<&TeamInfoEntry> {
@@ -378,3 +445,108 @@ fn get_team_info_entry(endpoints: &FplEndpoints, team_id: u32) -> Option<&TeamIn
         .get(&team_id)
         .and_then(|val_res| Some(&val_res.entry))
 }
+
+// The deadline of a gameweek in seconds since the unix epoch
+pub fn get_gw_deadline(endpoints: &FplEndpoints, gw: u32) -> Option<u64> {
+    endpoints
+        .static_info
+        .events
+        .data
+        .iter()
+        .find(|e| e.id == Some(gw as i32))?
+        .deadline_time
+        .as_deref()
+        .and_then(parse_utc_time)
+}
+
+// Parses the UTC times of the FPL api, eg. 2024-08-16T17:30:00Z, into seconds since the unix epoch
+pub fn parse_utc_time(time: &str) -> Option<u64> {
+    let time = time
+        .strip_suffix('Z')
+        .or_else(|| time.strip_suffix("+00:00"))
+        .unwrap_or(time);
+    let (date, time) = time.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|x| x.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    // Fractions of seconds are dropped. Any offset other than UTC is left in the time and fails
+    // to parse.
+    let (time, fraction) = time.split_once('.').unwrap_or((time, "0"));
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let mut time = time.splitn(3, ':').map(|x| x.parse::<i64>().ok());
+    let (hour, minute, second) = (
+        time.next()??,
+        time.next()??,
+        time.next().unwrap_or(Some(0))?,
+    );
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || !(0..24).contains(&hour)
+        || !(0..60).contains(&minute)
+        || !(0..60).contains(&second)
+    {
+        return None;
+    }
+
+    // Days since 1970-01-01 in the proleptic Gregorian calendar, with years starting in March so
+    // the leap day is the last day of a year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    u64::try_from(days * 86_400 + hour * 3_600 + minute * 60 + second).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::testdata;
+
+    #[test]
+    fn upcoming_fixtures_leave_out_started_fixtures() {
+        let mut endpoints = testdata::endpoints(2, false);
+        let upcoming: Vec<Option<i32>> = get_team_upcoming_fixtures(&endpoints, 1)
+            .iter()
+            .map(|f| f.id)
+            .collect();
+        assert_eq!(upcoming, vec![Some(401), Some(501), Some(601)]);
+
+        endpoints.modify(|data| {
+            let static_info = Arc::make_mut(&mut data.static_info);
+            for fixture in static_info.fixtures.get_mut(&4).unwrap().iter_mut() {
+                fixture.started = Some(true);
+            }
+            static_info.fixtures.get_mut(&5).unwrap()[0].finished = Some(true);
+        });
+        let upcoming: Vec<Option<i32>> = get_team_upcoming_fixtures(&endpoints, 1)
+            .iter()
+            .map(|f| f.id)
+            .collect();
+        assert_eq!(upcoming, vec![Some(601)]);
+    }
+
+    #[test]
+    fn utc_times_are_parsed() {
+        assert_eq!(parse_utc_time("1970-01-02T00:00:01Z"), Some(86_401));
+        assert_eq!(parse_utc_time("2024-08-16T17:30:00Z"), Some(1_723_829_400));
+        assert_eq!(
+            parse_utc_time("2024-08-16T17:30:00+00:00"),
+            Some(1_723_829_400)
+        );
+        assert_eq!(parse_utc_time("2024-13-01T00:00:00Z"), None);
+        // Only UTC times are parsed
+        assert_eq!(parse_utc_time("2024-08-16T17:30:00+01:00"), None);
+        assert_eq!(parse_utc_time("2024-08-16T17:30:00.000+01:00"), None);
+        assert_eq!(
+            parse_utc_time("2024-08-16T17:30:00.250Z"),
+            Some(1_723_829_400)
+        );
+        assert_eq!(parse_utc_time("2024-08-16T24:30:00Z"), None);
+        assert_eq!(parse_utc_time("2024-08-16T17:60:00Z"), None);
+    }
+}
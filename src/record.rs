@@ -0,0 +1,238 @@
+// Records the endpoints of a league to a local database, a directory of json files laid out like
+// the paths of the api so that the file source can read them (see local_fetch in the README).
+//
+// Every endpoint is deserialized into its struct before it is written, so a recorded database
+// can always be read by the current structs.
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+
+use crate::client::{Client, ClientError, Endpoint, EndpointKind, FileSource};
+use crate::structs::{Details, Game, Live, StaticInfo, TeamGw, TeamInfo};
+
+#[derive(Debug, Clone)]
+pub struct RecordOptions {
+    pub league_id: u32,
+    // The root of the database, corresponding to local_url
+    pub out_dir: PathBuf,
+    // Record every gameweek up to the current one instead of only the current one
+    pub all_gws: bool,
+    // Record these gameweeks instead of the current one
+    pub gws: Vec<u32>,
+    // Overwrite every file that already exists
+    pub overwrite: bool,
+    // Overwrite the existing files of these endpoint kinds, eg. only the live points
+    pub refresh: Vec<EndpointKind>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct RecordSummary {
+    pub written: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+// Fetches every endpoint of the league with the client and writes the ones that are missing from
+// the database or should be overwritten. Game and league details are always fetched since they
+// decide which gameweeks and teams to record.
+pub async fn record(client: &Client, options: &RecordOptions) -> Result<RecordSummary, String> {
+    let mut recorder = Recorder {
+        client,
+        options,
+        refresh: options.refresh.iter().copied().collect(),
+        summary: RecordSummary::default(),
+    };
+
+    let game: Game = recorder
+        .record(Endpoint::Game, true)
+        .await
+        .ok_or("Failed to record the game endpoint")?;
+    let details: Details = recorder
+        .record(
+            Endpoint::Details {
+                league: options.league_id,
+            },
+            true,
+        )
+        .await
+        .ok_or("Failed to record the league details")?;
+    recorder.record::<StaticInfo>(Endpoint::Static, false).await;
+
+    let teams: Vec<u32> = details.league_entries.iter().map(|e| e.entry_id).collect();
+    tracing::info!(
+        "Recording {} teams of league {}",
+        teams.len(),
+        options.league_id
+    );
+    for team in teams.iter() {
+        recorder
+            .record::<TeamInfo>(Endpoint::TeamInfo { team: *team }, false)
+            .await;
+    }
+
+    let gws: Vec<u32> = if !options.gws.is_empty() {
+        options.gws.clone()
+    } else {
+        match game.current_event {
+            Some(current) if options.all_gws => (1..=current).collect(),
+            Some(current) => vec![current],
+            None => {
+                tracing::info!("The season has not started, no gameweeks to record");
+                Vec::new()
+            }
+        }
+    };
+    for gw in gws {
+        tracing::info!("Recording gameweek {}", gw);
+        recorder.record::<Live>(Endpoint::Live { gw }, false).await;
+        for team in teams.iter() {
+            recorder
+                .record::<TeamGw>(Endpoint::TeamGw { team: *team, gw }, false)
+                .await;
+        }
+    }
+
+    Ok(recorder.summary)
+}
+
+struct Recorder<'a> {
+    client: &'a Client,
+    options: &'a RecordOptions,
+    refresh: HashSet<EndpointKind>,
+    summary: RecordSummary,
+}
+
+impl Recorder<'_> {
+    fn should_write(&self, endpoint: &Endpoint, path: &Path) -> bool {
+        !path.exists() || self.options.overwrite || self.refresh.contains(&endpoint.kind())
+    }
+
+    // Records an endpoint and returns its contents if it was fetched. Endpoints that are not
+    // written are only fetched if `needed` is set.
+    async fn record<T: DeserializeOwned>(&mut self, endpoint: Endpoint, needed: bool) -> Option<T> {
+        let path = file_path(&self.options.out_dir, &endpoint);
+        let write = self.should_write(&endpoint, &path);
+        if !write {
+            self.summary.skipped += 1;
+            tracing::debug!("Skipped (exists): {}", path.display());
+            if !needed {
+                return None;
+            }
+        }
+
+        let result = self.fetch::<T>(&endpoint).await.and_then(|(body, value)| {
+            if write {
+                write_file(&endpoint, &path, &body)?;
+                tracing::info!("Wrote {}", path.display());
+            }
+            Ok(value)
+        });
+        match result {
+            Ok(value) => {
+                if write {
+                    self.summary.written += 1;
+                }
+                Some(value)
+            }
+            Err(e) => {
+                self.summary.failed += 1;
+                tracing::error!("Failed to record {}: {}", endpoint, e);
+                None
+            }
+        }
+    }
+
+    // The body of the endpoint pretty printed, along with the deserialized struct
+    async fn fetch<T: DeserializeOwned>(
+        &self,
+        endpoint: &Endpoint,
+    ) -> Result<(String, T), ClientError> {
+        let body = self.client.get_raw(endpoint).await?;
+        let value = self.client.deserialize(endpoint, &body)?;
+        let pretty = serde_json::from_str::<serde_json::Value>(&body)
+            .and_then(|json| serde_json::to_string_pretty(&json))
+            .map_err(|e| ClientError::from_json(endpoint, None, e))?;
+        Ok((pretty, value))
+    }
+}
+
+// The file of an endpoint in the database, the same path that the file source reads
+fn file_path(out_dir: &Path, endpoint: &Endpoint) -> PathBuf {
+    FileSource::new(out_dir).file_path(endpoint)
+}
+
+fn write_file(endpoint: &Endpoint, path: &Path, contents: &str) -> Result<(), ClientError> {
+    let io_error = |e| ClientError::from_io(endpoint, path.to_path_buf(), e);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(io_error)?;
+    }
+    fs::write(path, contents).map_err(io_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::client::{FileSource, MemorySource};
+    use crate::testdata;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dof-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[tokio::test]
+    async fn record_and_refresh_selectively() {
+        let league_id = 1;
+        let endpoints = testdata::endpoints(3, true);
+        let client = Client::with_source(Arc::new(MemorySource::from_bodies(
+            testdata::endpoint_bodies(&endpoints, league_id),
+        )));
+
+        let out_dir = temp_dir("record-out");
+        let mut options = RecordOptions {
+            league_id,
+            out_dir: out_dir.clone(),
+            all_gws: false,
+            gws: Vec::new(),
+            overwrite: false,
+            refresh: Vec::new(),
+        };
+        // game, details, static and live plus the info and gw of every team
+        let n_files = 4 + 2 * 3;
+        let summary = record(&client, &options).await.unwrap();
+        assert_eq!(
+            summary,
+            RecordSummary {
+                written: n_files,
+                skipped: 0,
+                failed: 0
+            }
+        );
+
+        // The recorded database is readable by a local client
+        let recorded = Client::with_source(Arc::new(FileSource::new(&out_dir)));
+        let live = recorded
+            .get_gw_points_live(&testdata::CURRENT_GW)
+            .await
+            .unwrap();
+        assert_eq!(live, *endpoints.live);
+
+        options.refresh = vec![EndpointKind::Live];
+        let summary = record(&client, &options).await.unwrap();
+        assert_eq!(
+            summary,
+            RecordSummary {
+                written: 1,
+                skipped: n_files - 1,
+                failed: 0
+            }
+        );
+
+        let _ = fs::remove_dir_all(&out_dir);
+    }
+}
@@ -0,0 +1,249 @@
+// Reloading of the config file while the server runs.
+//
+// The file is checked for changes every few seconds and reloaded on SIGHUP. A new config is
+// validated before anything is applied and is rejected as a whole if it is invalid, so the server
+// keeps running with the config it has. The environment and the command line keep overriding the
+// file. Where the endpoints are read from, the server address, the log format, the timeouts and the
+// retry policy are only used at startup, changes to them are logged and ignored until a restart.
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::watch;
+
+use crate::client::Client;
+use crate::fetcher::FetcherControl;
+use crate::initializer::{self, AppConfig, ConfigLayers};
+use crate::AppState;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+pub struct Reloader {
+    path: PathBuf,
+    layers: ConfigLayers,
+    config_tx: watch::Sender<Arc<AppConfig>>,
+    client: Client,
+    fetcher: Arc<FetcherControl>,
+}
+
+impl Reloader {
+    // Reloads the config file of the layers, none without a config file
+    pub fn new(
+        layers: ConfigLayers,
+        config_tx: watch::Sender<Arc<AppConfig>>,
+        state: &AppState,
+    ) -> Option<Reloader> {
+        Some(Reloader {
+            path: layers.file.clone()?,
+            layers,
+            config_tx,
+            client: state.client.clone(),
+            fetcher: Arc::clone(&state.fetcher),
+        })
+    }
+
+    pub async fn run(self) {
+        tracing::info!("Reloading the config when {} changes", self.path.display());
+        let mut modified = modified_time(&self.path);
+        let mut hangup = Hangup::new();
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {
+                    let now = modified_time(&self.path);
+                    if now == modified {
+                        continue;
+                    }
+                    modified = now;
+                    tracing::info!("Config file changed, reloading it");
+                }
+                _ = hangup.recv() => tracing::info!("Received SIGHUP, reloading the config"),
+            }
+            // The result is logged
+            let _ = self.reload().await;
+        }
+    }
+
+    // Reads, validates and applies the config. Returns the changes that were applied.
+    pub async fn reload(&self) -> Result<Vec<String>, String> {
+        let result = self.try_reload().await;
+        match &result {
+            Ok(changes) if changes.is_empty() => tracing::info!("Config reloaded without changes"),
+            Ok(changes) => tracing::info!(?changes, "Config reloaded"),
+            Err(e) => tracing::error!("Rejected the reloaded config: {}", e),
+        }
+        result
+    }
+
+    async fn try_reload(&self) -> Result<Vec<String>, String> {
+        let current = self.config_tx.borrow().clone();
+        let mut new = AppConfig::read(&self.layers)
+            .map_err(|errors| errors.join(", "))?
+            .config;
+        if let Err(errors) = new.validate() {
+            return Err(format!(
+                "{}. Changes: {:?}",
+                errors.join(", "),
+                current.diff(&new)
+            ));
+        }
+
+        let ignored = keep_startup_settings(&current, &mut new);
+        if !ignored.is_empty() {
+            tracing::warn!(
+                "Changes to {} are ignored until the server restarts",
+                ignored.join(", ")
+            );
+        }
+
+        if new.league_id != current.league_id {
+            let mut context = initializer::fetch_app_context(&self.client, new.league_id)
+                .await
+                .map_err(|e| format!("League {} could not be loaded: {}", new.league_id, e))?;
+            new.apply_to(&mut context);
+            self.fetcher.set_context(context);
+        }
+        // Only changed intervals are applied, so changes from the admin api are kept otherwise
+        if new.fetch_interval() != current.fetch_interval() {
+            self.fetcher
+                .update_settings(|s| s.fetch_interval = new.fetch_interval());
+        }
+        if new.static_info_interval() != current.static_info_interval() {
+            self.fetcher
+                .update_settings(|s| s.static_info_interval = new.static_info_interval());
+        }
+
+        let changes = current.diff(&new);
+        self.config_tx.send_replace(Arc::new(new));
+        Ok(changes)
+    }
+}
+
+// Keeps the settings that are only used at startup from the current config. Returns the names of
+// the settings that were changed in the new config.
+fn keep_startup_settings(current: &AppConfig, new: &mut AppConfig) -> Vec<&'static str> {
+    let mut ignored = Vec::new();
+    macro_rules! keep {
+        ($($field:ident),*) => {
+            $(
+                if new.$field != current.$field {
+                    ignored.push(stringify!($field));
+                    new.$field = current.$field.clone();
+                }
+            )*
+        };
+    }
+    keep!(
+        source,
+        local_fetch,
+        local_url,
+        replay_step_secs,
+        bind_address,
+        server_port,
+        log_format,
+        timeouts,
+        retry
+    );
+    ignored
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Completes on every SIGHUP, and never on platforms without it
+struct Hangup {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl Hangup {
+    fn new() -> Hangup {
+        #[cfg(unix)]
+        {
+            let kind = tokio::signal::unix::SignalKind::hangup();
+            let signal = tokio::signal::unix::signal(kind)
+                .map_err(|e| tracing::error!("Failed to listen for SIGHUP: {}", e))
+                .ok();
+            Hangup { signal }
+        }
+        #[cfg(not(unix))]
+        Hangup {}
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = &mut self.signal {
+            if signal.recv().await.is_some() {
+                return;
+            }
+        }
+        std::future::pending::<()>().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::MemorySource;
+    use crate::testdata;
+
+    const LEAGUE_ID: u32 = 1;
+
+    async fn reloader(path: PathBuf) -> Reloader {
+        let endpoints = testdata::endpoints(2, false);
+        let source = MemorySource::from_bodies(testdata::endpoint_bodies(&endpoints, LEAGUE_ID));
+        let client = Client::with_source(Arc::new(source));
+        let context = initializer::initialize_app_context(&client, LEAGUE_ID).await;
+        let config = AppConfig {
+            league_id: LEAGUE_ID,
+            ..Default::default()
+        };
+        Reloader {
+            path: path.clone(),
+            layers: ConfigLayers {
+                file: Some(path),
+                ..Default::default()
+            },
+            config_tx: watch::channel(Arc::new(config)).0,
+            client,
+            fetcher: Arc::new(FetcherControl::new(context)),
+        }
+    }
+
+    #[tokio::test]
+    async fn valid_changes_are_applied_and_invalid_rejected() {
+        let path = std::env::temp_dir().join(format!("dof-reload-{}.toml", std::process::id()));
+        let reloader = reloader(path.clone()).await;
+        let write = |config: &str| std::fs::write(&path, config).unwrap();
+
+        write("league_id = 1\nfetch_interval_secs = 5\n[features]\ngraphql = false\n");
+        let changes = reloader.reload().await.unwrap();
+        assert_eq!(changes.len(), 2, "{:?}", changes);
+        assert_eq!(
+            reloader.fetcher.settings().fetch_interval,
+            Duration::from_secs(5)
+        );
+        assert!(!reloader.config_tx.borrow().features.graphql);
+
+        write("league_id = 1\nfetch_interval_secs = 0\n");
+        let error = reloader.reload().await.unwrap_err();
+        assert!(
+            error.contains("fetch_interval_secs must be at least 1"),
+            "{}",
+            error
+        );
+        assert_eq!(reloader.config_tx.borrow().fetch_interval_secs, 5);
+
+        // The league can't be fetched
+        write("league_id = 2\nfetch_interval_secs = 5\n");
+        reloader.reload().await.unwrap_err();
+        assert_eq!(reloader.fetcher.context().league_id, LEAGUE_ID);
+
+        write("league_id = 1\nfetch_interval_secs = 5\nserver_port = 9000\n");
+        reloader.reload().await.unwrap();
+        assert_eq!(reloader.config_tx.borrow().server_port, 8000);
+        assert!(reloader.config_tx.borrow().features.graphql);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
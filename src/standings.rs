@@ -0,0 +1,230 @@
+// Computes additional standings views for H2H leagues.
+//
+// The all-play record of a team is the record it would have had if it played every other team of
+// the league every gameweek. Comparing the actual H2H results to the all-play record gives the
+// number of expected wins and how lucky the team has been with its opponents.
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::propcomp;
+use crate::storage::table::Scoring;
+use crate::storage::{FplEndpoints, LeagueTable};
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct AllPlayStandings {
+    /// The number of gameweeks included in the standings
+    pub gameweeks: u32,
+
+    /// The teams ordered by all-play win percentage
+    pub entries: Vec<AllPlayEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct AllPlayEntry {
+    pub team_code: u32,
+    pub team_name: String,
+    pub owner_name: String,
+
+    /// The sum of the points of the team and of its opponents in the included gameweeks
+    pub points_for: i32,
+    pub points_against: i32,
+
+    /// The actual H2H record
+    pub matches_won: u32,
+    pub matches_drawn: u32,
+    pub matches_lost: u32,
+
+    /// The record if the team had played every other team every gameweek
+    pub all_play_won: u32,
+    pub all_play_drawn: u32,
+    pub all_play_lost: u32,
+
+    /// Share of all-play matches won, where a draw counts as half a win
+    pub all_play_win_pct: f64,
+
+    /// The number of H2H wins the all-play record gives, where a draw counts as half a win
+    pub expected_wins: f64,
+
+    /// The number of actual H2H wins, where a draw counts as half a win
+    pub actual_wins: f64,
+
+    /// Actual wins minus expected wins. Positive if the team has been lucky with its opponents.
+    pub luck: f64,
+}
+
+#[derive(Default)]
+struct Record {
+    points_for: i32,
+    points_against: i32,
+    won: u32,
+    drawn: u32,
+    lost: u32,
+    all_play_won: u32,
+    all_play_drawn: u32,
+    all_play_lost: u32,
+    expected_wins: f64,
+}
+
+// Returns None if the league is not a H2H league. Finished matches use the official points, while
+// matches of the ongoing gameweek use the live points of the league table.
+pub fn compute_all_play_standings(
+    endpoints: &FplEndpoints,
+    table: &LeagueTable,
+) -> Option<AllPlayStandings> {
+    if let Scoring::CLASSIC = propcomp::get_league_scoring(endpoints) {
+        return None;
+    }
+    let matches = endpoints.details.matches.as_ref()?;
+    let live_points: HashMap<u32, i32> = table
+        .entries
+        .iter()
+        .map(|e| (e.team_code, e.gw_points))
+        .collect();
+
+    // Points per gameweek and team (entry_id) for all started matches
+    let mut gw_points: HashMap<u32, Vec<(u32, i32)>> = HashMap::new();
+    let mut records: HashMap<u32, Record> = HashMap::new();
+    for m in matches.iter().filter(|m| m.started) {
+        let entry_1 = propcomp::get_entry_id_from_team_id(endpoints, m.league_entry_1);
+        let entry_2 = propcomp::get_entry_id_from_team_id(endpoints, m.league_entry_2);
+        let (points_1, points_2) = if m.finished {
+            (
+                m.league_entry_1_points as i32,
+                m.league_entry_2_points as i32,
+            )
+        } else {
+            (
+                live_points.get(&entry_1).copied().unwrap_or(0),
+                live_points.get(&entry_2).copied().unwrap_or(0),
+            )
+        };
+
+        let gw = gw_points.entry(m.event).or_default();
+        gw.push((entry_1, points_1));
+        gw.push((entry_2, points_2));
+
+        add_match(records.entry(entry_1).or_default(), points_1, points_2);
+        add_match(records.entry(entry_2).or_default(), points_2, points_1);
+    }
+
+    for teams in gw_points.values() {
+        let opponents = (teams.len() - 1).max(1) as f64;
+        for (entry, points) in teams.iter() {
+            let record = records.entry(*entry).or_default();
+            let mut won = 0;
+            let mut drawn = 0;
+            for (_, other) in teams.iter().filter(|(other, _)| other != entry) {
+                match points.cmp(other) {
+                    std::cmp::Ordering::Greater => won += 1,
+                    std::cmp::Ordering::Equal => drawn += 1,
+                    std::cmp::Ordering::Less => record.all_play_lost += 1,
+                }
+            }
+            record.all_play_won += won;
+            record.all_play_drawn += drawn;
+            record.expected_wins += (won as f64 + 0.5 * drawn as f64) / opponents;
+        }
+    }
+
+    let mut entries: Vec<AllPlayEntry> = endpoints
+        .details
+        .league_entries
+        .iter()
+        .map(|league_entry| {
+            let id = league_entry.entry_id;
+            let record = records.remove(&id).unwrap_or_default();
+            compute_all_play_entry(endpoints, id, record)
+        })
+        .collect();
+    entries.sort_by(|e1, e2| {
+        e2.all_play_win_pct
+            .total_cmp(&e1.all_play_win_pct)
+            .then(e2.points_for.cmp(&e1.points_for))
+    });
+
+    Some(AllPlayStandings {
+        gameweeks: gw_points.len() as u32,
+        entries,
+    })
+}
+
+fn add_match(record: &mut Record, points: i32, opponent_points: i32) {
+    record.points_for += points;
+    record.points_against += opponent_points;
+    match points.cmp(&opponent_points) {
+        std::cmp::Ordering::Greater => record.won += 1,
+        std::cmp::Ordering::Equal => record.drawn += 1,
+        std::cmp::Ordering::Less => record.lost += 1,
+    }
+}
+
+fn compute_all_play_entry(endpoints: &FplEndpoints, id: u32, record: Record) -> AllPlayEntry {
+    let all_play_matches = record.all_play_won + record.all_play_drawn + record.all_play_lost;
+    let all_play_win_pct = if all_play_matches == 0 {
+        0.0
+    } else {
+        (record.all_play_won as f64 + 0.5 * record.all_play_drawn as f64) / all_play_matches as f64
+    };
+    let actual_wins = record.won as f64 + 0.5 * record.drawn as f64;
+
+    AllPlayEntry {
+        team_code: id,
+        team_name: propcomp::get_team_name(endpoints, id),
+        owner_name: propcomp::get_team_owner_name(endpoints, id),
+        points_for: record.points_for,
+        points_against: record.points_against,
+        matches_won: record.won,
+        matches_drawn: record.drawn,
+        matches_lost: record.lost,
+        all_play_won: record.all_play_won,
+        all_play_drawn: record.all_play_drawn,
+        all_play_lost: record.all_play_lost,
+        all_play_win_pct,
+        expected_wins: record.expected_wins,
+        actual_wins,
+        luck: actual_wins - record.expected_wins,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer;
+    use crate::testdata;
+
+    #[test]
+    fn all_play_standings_for_h2h_league() {
+        let endpoints = testdata::endpoints(6, true);
+        let table = computer::compute_new_league_table(&endpoints).unwrap();
+
+        let standings = compute_all_play_standings(&endpoints, &table).unwrap();
+
+        assert_eq!(standings.gameweeks, testdata::CURRENT_GW);
+        assert_eq!(standings.entries.len(), 6);
+        for e in standings.entries.iter() {
+            let all_play = e.all_play_won + e.all_play_drawn + e.all_play_lost;
+            assert_eq!(all_play, 5 * testdata::CURRENT_GW);
+            assert_eq!(
+                e.matches_won + e.matches_drawn + e.matches_lost,
+                testdata::CURRENT_GW
+            );
+            assert!((e.luck - (e.actual_wins - e.expected_wins)).abs() < 1e-9);
+        }
+        // Every gameweek hands out as many expected wins as there are matches
+        let expected_wins: f64 = standings.entries.iter().map(|e| e.expected_wins).sum();
+        assert!((expected_wins - 3.0 * testdata::CURRENT_GW as f64).abs() < 1e-9);
+        assert!(standings
+            .entries
+            .windows(2)
+            .all(|w| w[0].all_play_win_pct >= w[1].all_play_win_pct));
+    }
+
+    #[test]
+    fn no_all_play_standings_for_classic_league() {
+        let endpoints = testdata::endpoints(4, false);
+        let table = computer::compute_new_league_table(&endpoints).unwrap();
+        assert!(compute_all_play_standings(&endpoints, &table).is_none());
+    }
+}
@@ -1,17 +1,31 @@
 
+use crate::storage::index::EndpointsIndex;
 use crate::structs;
 use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::Arc;
 
+// The live and static endpoints are by far the largest and are shared between snapshots of the
+// endpoints, so cloning the endpoints does not copy them.
 #[derive(Debug, Clone)]
-pub struct FplEndpoints {
+pub struct EndpointsData {
     pub details: structs::Details,
     pub game: structs::Game,
-    pub live: structs::Live,
-    pub static_info: structs::StaticInfo,
+    pub live: Arc<structs::Live>,
+    pub static_info: Arc<structs::StaticInfo>,
     pub teams_gws: HashMap<u32, structs::TeamGw>,
     pub teams_infos: HashMap<u32, structs::TeamInfo>,
 }
 
+// The endpoints together with lookup tables into them. The data can be read through Deref, but
+// only changed through `update` and `modify`, which rebuild the lookup tables so they never go
+// stale.
+#[derive(Debug, Clone)]
+pub struct FplEndpoints {
+    data: EndpointsData,
+    index: Arc<EndpointsIndex>,
+}
+
 #[derive(Debug, Clone)]
 pub struct FplEndpointsUpdate {
     pub details: Option<structs::Details>,
@@ -24,6 +38,11 @@ pub struct FplEndpointsUpdate {
 
 impl FplEndpoints {
 
+    pub fn new(data: EndpointsData) -> FplEndpoints {
+        let index = Arc::new(EndpointsIndex::build(&data));
+        FplEndpoints { data, index }
+    }
+
     pub fn initialize_from_update(update: FplEndpointsUpdate) -> FplEndpoints {
         let FplEndpointsUpdate {
             details,
@@ -36,8 +55,8 @@ impl FplEndpoints {
 
         let details = details.unwrap();
         let game = game.unwrap();
-        let live = live.unwrap();
-        let static_info = static_info.unwrap();
+        let live = Arc::new(live.unwrap());
+        let static_info = Arc::new(static_info.unwrap());
 
         let mut new_teams_gws: HashMap<u32, structs::TeamGw> = HashMap::new();
         teams_gws.into_iter().for_each( |(key,val)| {
@@ -50,14 +69,14 @@ impl FplEndpoints {
             new_teams_infos.insert(key, val.unwrap());
         });
 
-        FplEndpoints {
+        FplEndpoints::new(EndpointsData {
             details,
             game,
             live,
             static_info,
             teams_gws: new_teams_gws,
             teams_infos: new_teams_infos,
-        }
+        })
     }
 
     pub fn update(&mut self, other: FplEndpointsUpdate){
@@ -69,34 +88,59 @@ impl FplEndpoints {
             teams_gws,
             teams_infos
         } = other;
-
+        let data = &mut self.data;
 
         if let Some(game) = game {
-            self.game = game
+            data.game = game
         }
 
         if let Some(live) = live {
-            self.live = live;
+            data.live = Arc::new(live);
         }
 
         if let Some(details) = details {
-            self.details = details;
+            data.details = details;
         }
 
         if let Some(static_info) = static_info {
-            self.static_info = static_info;
+            data.static_info = Arc::new(static_info);
         }
 
         for (team_id, team_gw) in teams_gws.into_iter() {
             if let Some(team_gw) = team_gw {
-                self.teams_gws.insert(team_id, team_gw);
+                data.teams_gws.insert(team_id, team_gw);
             }
         }
 
         for (team_id, team_info) in teams_infos.into_iter() {
             if let Some(team_info) = team_info {
-                self.teams_infos.insert(team_id, team_info);
+                data.teams_infos.insert(team_id, team_info);
             }
         }
+
+        self.reindex();
+    }
+
+    // Changes the data with the given function and rebuilds the lookup tables
+    pub fn modify<R>(&mut self, f: impl FnOnce(&mut EndpointsData) -> R) -> R {
+        let result = f(&mut self.data);
+        self.reindex();
+        result
+    }
+
+    pub fn index(&self) -> &EndpointsIndex {
+        &self.index
+    }
+
+    fn reindex(&mut self) {
+        self.index = Arc::new(EndpointsIndex::build(&self.data));
+    }
+}
+
+impl Deref for FplEndpoints {
+    type Target = EndpointsData;
+
+    fn deref(&self) -> &EndpointsData {
+        &self.data
     }
 }
@@ -0,0 +1,134 @@
+use crate::storage::endpoints::EndpointsData;
+use std::collections::{HashMap, HashSet};
+
+// Lookup tables from the IDs used by the FPL api to the positions of the objects in the
+// endpoints. Built once every time the endpoints are updated so that computing the league table
+// does not scan the endpoints for every player.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointsIndex {
+    // Player ID to the position of the player in the bootstrap-static elements
+    pub static_elements: HashMap<u32, usize>,
+
+    // Premier League team ID to the position of the team in the bootstrap-static teams
+    pub teams: HashMap<u32, usize>,
+
+    // Fixture ID to the position of the fixture in the live fixtures
+    pub live_fixtures: HashMap<u32, usize>,
+
+    // Entry ID (team_id) to league entry ID (entry_id), and the other way around
+    pub league_entry_ids: HashMap<u32, u32>,
+    pub entry_ids: HashMap<u32, u32>,
+
+    // League entry ID to the position of the team in the league standings
+    pub standings: HashMap<u32, usize>,
+
+    // League entry ID to the position of the H2H match of the team in the current gameweek
+    pub current_matches: HashMap<u32, usize>,
+
+    // (Entry ID, player ID) of every automatic substitution of the current gameweek
+    pub subbed_in: HashSet<(u32, u32)>,
+    pub subbed_out: HashSet<(u32, u32)>,
+}
+
+impl EndpointsIndex {
+    pub fn build(endpoints: &EndpointsData) -> EndpointsIndex {
+        let static_elements = endpoints
+            .static_info
+            .elements
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| e.id.map(|id| (id as u32, i)))
+            .collect();
+
+        let teams = endpoints
+            .static_info
+            .teams
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.id, i))
+            .collect();
+
+        let live_fixtures = endpoints
+            .live
+            .fixtures
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f.id, i))
+            .collect();
+
+        let league_entries = &endpoints.details.league_entries;
+        let league_entry_ids = league_entries.iter().map(|e| (e.entry_id, e.id)).collect();
+        let entry_ids = league_entries.iter().map(|e| (e.id, e.entry_id)).collect();
+
+        let standings = endpoints
+            .details
+            .standings
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.league_entry, i))
+            .collect();
+
+        let mut current_matches = HashMap::new();
+        if let (Some(matches), Some(current_gw)) =
+            (&endpoints.details.matches, endpoints.game.current_event)
+        {
+            for (i, m) in matches
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| m.event == current_gw)
+            {
+                current_matches.insert(m.league_entry_1, i);
+                current_matches.insert(m.league_entry_2, i);
+            }
+        }
+
+        let mut subbed_in = HashSet::new();
+        let mut subbed_out = HashSet::new();
+        for (entry_id, team_gw) in endpoints.teams_gws.iter() {
+            for sub in team_gw.subs.iter() {
+                subbed_in.insert((*entry_id, sub.element_in as u32));
+                subbed_out.insert((*entry_id, sub.element_out as u32));
+            }
+        }
+
+        EndpointsIndex {
+            static_elements,
+            teams,
+            live_fixtures,
+            league_entry_ids,
+            entry_ids,
+            standings,
+            current_matches,
+            subbed_in,
+            subbed_out,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::computer;
+    use crate::testdata;
+    use std::sync::Arc;
+
+    #[test]
+    fn lookups_do_not_depend_on_endpoint_order() {
+        let mut endpoints = testdata::endpoints(4, true);
+        let table = computer::compute_new_league_table(&endpoints).unwrap();
+
+        endpoints.modify(|data| {
+            let static_info = Arc::make_mut(&mut data.static_info);
+            static_info.elements.reverse();
+            static_info.teams.reverse();
+            Arc::make_mut(&mut data.live).fixtures.reverse();
+            data.details.league_entries.reverse();
+            data.details.standings.reverse();
+        });
+        let reordered = computer::compute_new_league_table(&endpoints).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&table).unwrap(),
+            serde_json::to_value(&reordered).unwrap()
+        );
+    }
+}
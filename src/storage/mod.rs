@@ -1,5 +1,6 @@
 pub mod table;
 pub mod endpoints;
+pub mod index;
 
 
 pub use endpoints::FplEndpoints;
@@ -1,125 +1,140 @@
+use async_graphql::{Enum, SimpleObject};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, SimpleObject)]
+#[graphql(complex)]
 pub struct LeagueTable {
-    // An array of the entries of the draft league
+    /// An array of the entries of the draft league
     pub entries: Vec<Entry>,
 
-    // The code of the draft league
+    /// The code of the draft league
     pub code: u32,
 
-    // The name of the draft league
+    /// The name of the draft league
     pub name: String,
 
-    // The scoring method for the leageu. Either CLASSIC or H2H
+    /// The scoring method for the leageu. Either CLASSIC or H2H
     pub scoring: Scoring,
 
-    // Only present if league is H2H. A map between gameweek and a vector of unordered matches.
-    pub matches: Option<HashMap<u32, Vec<H2HMatch>>>,
+    /// The gameweek the table was computed for
+    pub gw: u32,
+
+    /// Whether or not FPL has finished the gameweek, if not the points may still change
+    pub gw_finished: bool,
+
+    /// Only present if league is H2H. A map between gameweek and a vector of unordered matches.
+    #[graphql(skip)]
+    pub matches: Option<BTreeMap<u32, Vec<H2HMatch>>>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, SimpleObject)]
 pub struct Entry {
-    // The code of the team
+    /// The code of the team
     pub team_code: u32,
 
-    // The name of the draft league team
+    /// The name of the draft league team
     pub team_name: String,
 
-    // The total number of points of this team all season
+    /// The total number of points of this team all season
     pub total_points: i32,
 
-    // The total number of projected points of this team all season
+    /// The total number of projected points of this team all season
     pub total_projected_points: i32,
 
-    // The current number of total points of the team, as calculated by the official FPL site
+    /// The current number of total points of the team, as calculated by the official FPL site
     pub gw_points: i32,
 
-    // The current number of points of the team, by calculating current metrics such as bps and
-    // certain substitutions
+    /// The current number of points of the team, by calculating current metrics such as bps and
+    /// certain substitutions
     pub gw_projected_points: i32,
 
-    // List of objects containing simple information about the projected points
+    /// List of objects containing simple information about the projected points
     pub projected_points_explanation: Vec<ProjectedPointsExplanation>,
 
-    // Name of the owner of the FPL team
+    /// Name of the owner of the FPL team
     pub owner_name: String,
 
-    // Array containing detailed information of each player
+    /// Array containing detailed information of each player
     pub players: Vec<Player>,
 
-    // Struct containing H2H information
+    /// Struct containing H2H information
     pub h2h_info: Option<H2HInfo>,
+
+    /// Only present if league is CLASSIC. Struct containing the ranks of the team
+    pub rank_info: Option<RankInfo>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, SimpleObject)]
 pub struct ProjectedPointsExplanation {
-    // Name of player
+    /// Name of player
     pub name: String,
 
-    // Bonus points of the player if exists (diff in points and projected points)
+    /// Bonus points of the player if exists (diff in points and projected points)
     pub bonus_points: Option<i32>,
 
-    // Points of the players if subbed on (not projected)
+    /// Points of the players if subbed on (not projected)
     pub subbed_points: Option<i32>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, SimpleObject)]
+#[graphql(complex)]
 pub struct Player {
-    // The ID of the player
+    /// The ID of the player
     pub id: u32,
 
-    // The full name of the player, concatenation of first name and second name
+    /// The full name of the player, concatenation of first name and second name
     pub full_name: String,
 
-    // The display name used by FPL
+    /// The display name used by FPL
     pub display_name: String,
 
-    // The team the player plays for
+    /// The team the player plays for
     pub team: Team,
 
-    // The position the player has on FPL
+    /// The position the player has on FPL
     pub team_pos: Position,
 
-    // The number of points of the player on FPL
+    /// The number of points of the player on FPL
     pub points: i32,
 
-    // The number of points in the BPS (bonus point system)
+    /// The number of points in the BPS (bonus point system)
     pub bps: i32,
 
-    // The current projected number of points for the player considering information such as bps and
-    // substitutions
+    /// The current projected number of points for the player considering information such as bps and
+    /// substitutions
     pub projected_points: i32,
 
-    // An array containing the sources of the points of the player (not including projected points)
+    /// An array containing the sources of the points of the player (not including projected points)
     pub point_sources: Vec<PointSource>,
 
-    // Whether or not the player is on the field either selected by the team owner or if
-    // substituted in
+    /// Whether or not the player is on the field either selected by the team owner or if
+    /// substituted in
     pub on_field: bool,
 
-    // The pick number of the player on the FPL teams. Where 12-15 are the bench and the rest are
-    // chosen by the team owner as playing
+    /// The pick number of the player on the FPL teams. Where 12-15 are the bench and the rest are
+    /// chosen by the team owner as playing
     pub pick_number: i32,
 
-    // Whether or not this player has played any minutes this game week, therefore qualifying for
-    // points
+    /// Whether or not this player has played any minutes this game week, therefore qualifying for
+    /// points
     pub has_played: bool,
 
-    // Whether or not this player's fixtures are finished
+    /// Whether or not this player's fixtures are finished
     pub fixtures_finished: bool,
 
-    // Whether or not the player has any fixtures this gameweek
+    /// Whether or not the player has any fixtures this gameweek
     pub has_upcoming_fixtures: bool,
 
-    // The type of injury status the player has as marked by the FPL Towers
+    /// The type of injury status the player has as marked by the FPL Towers
     pub status: InjuryStatus,
 
-    // The news for the player if there are any. This includes the injury message and when the player is expected to make a return.
+    /// The news for the player if there are any. This includes the injury message and when the player is expected to make a return.
     pub news: Option<String>,
 
-    // Indicates whether or not the player is playing (or may play), benched (or may play)
+    /// Indicates whether or not the player is playing (or may play), benched (or may play)
+    #[graphql(skip)]
     pub play_status: PlayStatus,
 }
 
@@ -129,30 +144,31 @@ impl Player {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, SimpleObject)]
 pub struct Team {
-    // The team ID. Identifies the team in the FPL Json structures
+    /// The team ID. Identifies the team in the FPL Json structures
     pub id: u32,
 
-    // The name of the team
+    /// The name of the team
     pub name: String,
 
-    // The short name of the team
+    /// The short name of the team
     pub short_name: String,
 
-    // The code of the team. Identifies the team in at least the icon URLs
+    /// The code of the team. Identifies the team in at least the icon URLs
     pub code: u32,
 
-    // The URL pointing to a small image of this team's outfield shirt
+    /// The URL pointing to a small image of this team's outfield shirt
     pub shirt_url: String,
 
-    // The URL pointing to a small image of this team's goalkeeper shirt
+    /// The URL pointing to a small image of this team's goalkeeper shirt
     pub gk_shirt_url: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, ToSchema, Enum)]
+#[graphql(rename_items = "UPPERCASE")]
 pub enum Position {
-    // The number of this position as used by FPL (1 GK, 2 DEF, 3 MID, 4 FWD)
+    /// The number of this position as used by FPL (1 GK, 2 DEF, 3 MID, 4 FWD)
     GK = 1,
     DEF = 2,
     MID = 3,
@@ -171,26 +187,27 @@ impl Position {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, SimpleObject)]
 pub struct PointSource {
-    // The name of this point source (eg. Goals scored/Assists/Clean sheets)
+    /// The name of this point source (eg. Goals scored/Assists/Clean sheets)
     pub name: String,
 
-    // The total number of points of this source as determined by FPL,
-    // will vary by position and amount
+    /// The total number of points of this source as determined by FPL,
+    /// will vary by position and amount
     pub points_total: i32,
 
-    // The amount of times this point source has happened
+    /// The amount of times this point source has happened
     pub amount: i32,
 
-    // The fixture ID that this point comes from
+    /// The fixture ID that this point comes from
     pub fixture: u32,
 
-    // The identifying string for this point source type (used by FPL to calculate points)
+    /// The identifying string for this point source type (used by FPL to calculate points)
     pub stat: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, ToSchema, Enum)]
+#[graphql(rename_items = "UPPERCASE")]
 pub enum Scoring {
     H2H,
     CLASSIC,
@@ -206,7 +223,7 @@ impl Scoring {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, SimpleObject)]
 pub struct H2HMatch {
     pub gw: u32,
     pub league_entry_1: u32,
@@ -215,7 +232,7 @@ pub struct H2HMatch {
     pub finished: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, SimpleObject)]
 pub struct H2HInfo {
     pub points: i32,
     pub matches_drawn: u32,
@@ -225,7 +242,46 @@ pub struct H2HInfo {
     pub current_opponent: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, SimpleObject)]
+pub struct RankInfo {
+    /// The rank as computed by the official FPL site, updated when FPL processes the standings
+    pub official_rank: Option<u32>,
+
+    /// The rank at the start of the gameweek, by the total points before the gameweek
+    pub start_rank: u32,
+
+    /// The current rank by total points
+    pub live_rank: u32,
+
+    /// The current rank by total projected points
+    pub projected_rank: u32,
+
+    /// The movement of the live rank compared to the start of the gameweek
+    pub live_movement: RankMovement,
+
+    /// The movement of the projected rank compared to the start of the gameweek
+    pub projected_movement: RankMovement,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, ToSchema, Enum)]
+#[serde(rename_all = "lowercase")]
+pub enum RankMovement {
+    Up,
+    Down,
+    Same,
+}
+
+impl RankMovement {
+    pub fn from_ranks(start_rank: u32, rank: u32) -> RankMovement {
+        match rank.cmp(&start_rank) {
+            std::cmp::Ordering::Less => RankMovement::Up,
+            std::cmp::Ordering::Greater => RankMovement::Down,
+            std::cmp::Ordering::Equal => RankMovement::Same,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, ToSchema, Enum)]
 #[serde(rename_all = "lowercase")]
 pub enum InjuryStatus {
     Available, // "a" Green marked
@@ -249,27 +305,27 @@ impl InjuryStatus {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, ToSchema)]
 #[serde(rename_all = "lowercase")]
 #[serde(tag = "type")]
 pub enum PlayStatus {
-    // player is selected on the field and has either played or can't yet be benched.
+    /// player is selected on the field and has either played or can't yet be benched.
     #[serde(rename = "playing")]
     Playing,
 
-    // player is selected on the bench and is NOT guaranteed to be subbed in.
+    /// player is selected on the bench and is NOT guaranteed to be subbed in.
     #[serde(rename = "benched")]
     Benched,
 
-    // player is selected on the bench and is guaranteed to be subbed in.
+    /// player is selected on the bench and is guaranteed to be subbed in.
     #[serde(rename = "subbed_in")]
     SubbedIn { subbed_with: u32 },
 
-    // player is selected on the fiedl and is guaranteed to be subbed off.
+    /// player is selected on the fiedl and is guaranteed to be subbed off.
     #[serde(rename = "subbed_off")]
     SubbedOff { subbed_with: u32 },
 
-    // not yet able to project what the status is for the player.
+    /// not yet able to project what the status is for the player.
     #[serde(rename = "none")]
     Unknown,
 }
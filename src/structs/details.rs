@@ -1,7 +1,7 @@
 
 use serde::{Serialize,Deserialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Details {
     pub standings: Vec<TeamStandings>,
     pub league: LeagueInfo,
@@ -9,7 +9,7 @@ pub struct Details {
     pub league_entries: Vec<LeagueEntry>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TeamStandings {
     pub total: i32,
     pub event_total: Option<u32>, // Classic scoring only
@@ -25,7 +25,7 @@ pub struct TeamStandings {
     pub points_for: Option<i32>, // H2H scoring only
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct LeagueInfo {
     pub closed: Option<bool>,
     pub make_code_public: Option<bool>,
@@ -47,7 +47,7 @@ pub struct LeagueInfo {
     pub id: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct LeagueEntry {
     pub player_first_name: Option<String>,
     pub joined_time: Option<String>,
@@ -59,7 +59,7 @@ pub struct LeagueEntry {
     pub entry_name: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct H2HMatch {
     pub event: u32,
     pub league_entry_1: u32,
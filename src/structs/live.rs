@@ -1,13 +1,14 @@
 use std::collections::HashMap;
 use serde::{Serialize,Deserialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Live {
-    pub elements: HashMap<String, Element>,
+    // Keyed by player ID. The keys are strings in the json.
+    pub elements: HashMap<u32, Element>,
     pub fixtures: Vec<Fixture>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Element {
     //IMPORTAN TO NOTE ABOUT EXPLAIN IS THAT THE INSIDE VEC IS JUST DUMMY ELEMENT TO HOLD
     //EITHER A VECTOR OF POINTSOURCES AND WHAT FIXTURE WHEN ON INDEX 0 AND 1 RESPECTIVELY
@@ -16,14 +17,14 @@ pub struct Element {
     pub stats: Stats,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(untagged)]
 pub enum PointsOrFixture {
     Points(Vec<Point>),
     Fixture(u32),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Point {
     pub name: String,
     pub points: i32,
@@ -31,7 +32,7 @@ pub struct Point {
     pub stat: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Stats {
     pub minutes: i32,
     pub goals_scored: Option<i32>,
@@ -54,7 +55,7 @@ pub struct Stats {
     pub in_dreamteam: Option<bool>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Fixture {
     pub id: u32,
     pub started: bool,
@@ -72,14 +73,14 @@ pub struct Fixture {
     pub team_h: Option<i32>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct FixtureStats {
     pub s: String,           //type of stat (eg. red_cards, saves, bps, bonus)
     pub h: Vec<FixtureStat>, //home
     pub a: Vec<FixtureStat>, //away
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct FixtureStat {
     pub element: u32,
     pub value: i32,
@@ -1,13 +1,13 @@
 use serde::{Serialize,Deserialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TeamGw {
     pub picks: Vec<Pick>,
     pub subs: Vec<Substitution>,
     //pub entry_history: _, //empty {} in all available endpoints
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Pick {
     pub element: i32,
     pub position: i32,
@@ -16,7 +16,7 @@ pub struct Pick {
     pub multiplier: Option<i32>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Substitution {
     pub element_in: i32,
     pub element_out: i32,
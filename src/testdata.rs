@@ -0,0 +1,514 @@
+// Synthetic endpoint data for tests.
+//
+// Builds a complete and internally consistent set of FPL endpoints for a draft league without
+// touching the network or the recorded files in db/. Every league team owns 15 unique players,
+// all fixtures of the current GW are finished and every player has played 90 minutes.
+//
+// League team number `t` is 1-based. Its entry_id is `ENTRY_ID_OFFSET + t`, its league entry id
+// is `t`, and its picks are the players `(t - 1) * 15 + 1 ..= t * 15` in pick order.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+
+use crate::client::Endpoint;
+use crate::storage::endpoints::EndpointsData;
+use crate::storage::FplEndpoints;
+use crate::structs::{
+    live::{Point, PointsOrFixture},
+    Details, Game, Live, StaticInfo, TeamGw, TeamInfo,
+};
+
+pub const ENTRY_ID_OFFSET: u32 = 100;
+pub const CURRENT_GW: u32 = 3;
+pub const N_CLUBS: u32 = 20;
+pub const N_GAMEWEEKS: u32 = 38;
+pub const SQUAD_SIZE: u32 = 15;
+
+// Element types by pick position: GK, 4 DEF, 4 MID, 2 FWD and then the bench GK, DEF, MID, FWD
+const PICK_ELEMENT_TYPES: [u32; 15] = [1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 1, 2, 3, 4];
+
+pub fn entry_id(team: u32) -> u32 {
+    ENTRY_ID_OFFSET + team
+}
+
+pub fn player_id(team: u32, pick: u32) -> u32 {
+    (team - 1) * SQUAD_SIZE + pick
+}
+
+pub fn player_club(player_id: u32) -> u32 {
+    (player_id - 1) % N_CLUBS + 1
+}
+
+pub fn club_fixture(club: u32) -> u32 {
+    club.div_ceil(2)
+}
+
+fn default_points(player_id: u32) -> i32 {
+    2 + (player_id % 7) as i32
+}
+
+fn past_gw_points(team: u32, gw: u32) -> u32 {
+    40 + (team * 7 + gw * 3) % 30
+}
+
+// Builds endpoints for a league with `n_teams` teams, either with H2H or classic scoring.
+pub fn endpoints(n_teams: u32, h2h: bool) -> FplEndpoints {
+    endpoints_with_unowned(n_teams, h2h, 0)
+}
+
+// Same as `endpoints` but with `unowned` extra players in the Premier League that are not picked
+// by any league team, as in a real league.
+pub fn endpoints_with_unowned(n_teams: u32, h2h: bool, unowned: u32) -> FplEndpoints {
+    let n_players = n_teams * SQUAD_SIZE + unowned;
+
+    let mut data = EndpointsData {
+        details: details(n_teams, h2h),
+        game: game(),
+        live: Arc::new(live(n_players)),
+        static_info: Arc::new(static_info(n_players)),
+        teams_gws: HashMap::new(),
+        teams_infos: HashMap::new(),
+    };
+
+    for team in 1..=n_teams {
+        data.teams_gws.insert(entry_id(team), team_gw(team));
+        data.teams_infos.insert(entry_id(team), team_info(team));
+    }
+    sync_team_infos(&mut data);
+    FplEndpoints::new(data)
+}
+
+// The endpoints as the json bodies served by the api, for the current gameweek of the endpoints
+pub fn endpoint_bodies(endpoints: &FplEndpoints, league_id: u32) -> Vec<(Endpoint, String)> {
+    let gw = endpoints.game.current_event.unwrap_or(CURRENT_GW);
+    let mut bodies = vec![
+        (Endpoint::Game, to_json(&endpoints.game)),
+        (
+            Endpoint::Details { league: league_id },
+            to_json(&endpoints.details),
+        ),
+        (Endpoint::Static, to_json(&*endpoints.static_info)),
+        (Endpoint::Live { gw }, to_json(&*endpoints.live)),
+    ];
+    for (team, info) in endpoints.teams_infos.iter() {
+        bodies.push((Endpoint::TeamInfo { team: *team }, to_json(info)));
+    }
+    for (team, team_gw) in endpoints.teams_gws.iter() {
+        bodies.push((Endpoint::TeamGw { team: *team, gw }, to_json(team_gw)));
+    }
+    bodies
+}
+
+// Sets the GW points and minutes of a player and keeps the explain object and the BPS in sync
+pub fn set_player_points(endpoints: &mut FplEndpoints, player_id: u32, points: i32, minutes: i32) {
+    endpoints.modify(|data| set_player_points_in(data, player_id, points, minutes));
+}
+
+fn set_player_points_in(data: &mut EndpointsData, player_id: u32, points: i32, minutes: i32) {
+    let fixture_id = club_fixture(player_club(player_id));
+    let live = Arc::make_mut(&mut data.live);
+    let element = live
+        .elements
+        .get_mut(&player_id)
+        .expect("Test player does not exist");
+    element.stats.total_points = points;
+    element.stats.minutes = minutes;
+    element.stats.bps = points * 3;
+    element.explain = explain(points, minutes, fixture_id);
+
+    for fixture in live.fixtures.iter_mut() {
+        for stat in fixture.stats.iter_mut().filter(|s| s.s == "bps") {
+            for fs in stat.h.iter_mut().chain(stat.a.iter_mut()) {
+                if fs.element == player_id {
+                    fs.value = points * 3;
+                }
+            }
+        }
+    }
+    sync_team_infos(data);
+}
+
+// Adds a point source to the explain of a player, eg. a goal, on top of the minutes played
+pub fn add_point_source(
+    endpoints: &mut FplEndpoints,
+    player_id: u32,
+    stat: &str,
+    name: &str,
+    value: i32,
+    points: i32,
+) {
+    endpoints.modify(|data| {
+        let live = Arc::make_mut(&mut data.live);
+        let element = live
+            .elements
+            .get_mut(&player_id)
+            .expect("Test player does not exist");
+        element.stats.total_points += points;
+        if let Some(PointsOrFixture::Points(sources)) = element
+            .explain
+            .first_mut()
+            .and_then(|fixture| fixture.first_mut())
+        {
+            sources.push(Point {
+                name: String::from(name),
+                points,
+                value,
+                stat: String::from(stat),
+            });
+        }
+        sync_team_infos(data);
+    });
+}
+
+// Resets a fixture to not started, meaning all players of the fixture have 0 points and minutes
+pub fn set_fixture_not_started(endpoints: &mut FplEndpoints, fixture_id: u32) {
+    endpoints.modify(|data| {
+        let players: Vec<u32> = data
+            .live
+            .elements
+            .keys()
+            .copied()
+            .filter(|id| club_fixture(player_club(*id)) == fixture_id)
+            .collect();
+        for player_id in players {
+            set_player_points_in(data, player_id, 0, 0);
+        }
+        let fixture = Arc::make_mut(&mut data.live)
+            .fixtures
+            .iter_mut()
+            .find(|f| f.id == fixture_id)
+            .expect("Test fixture does not exist");
+        fixture.started = false;
+        fixture.finished = false;
+        fixture.finished_provisional = false;
+        fixture.minutes = 0;
+        fixture.team_h_score = None;
+        fixture.team_a_score = None;
+    });
+}
+
+// Recalculates the team info points so the season totals match the live points
+pub fn sync_team_infos(endpoints: &mut EndpointsData) {
+    let mut event_points: HashMap<u32, i32> = HashMap::new();
+    for (entry, team_gw) in endpoints.teams_gws.iter() {
+        let points = team_gw
+            .picks
+            .iter()
+            .filter(|p| p.position <= 11)
+            .map(|p| {
+                endpoints
+                    .live
+                    .elements
+                    .get(&(p.element as u32))
+                    .map(|e| e.stats.total_points)
+                    .unwrap_or(0)
+            })
+            .sum();
+        event_points.insert(*entry, points);
+    }
+    for (entry, info) in endpoints.teams_infos.iter_mut() {
+        let before_gw = info.entry.overall_points - info.entry.event_points;
+        info.entry.event_points = event_points[entry];
+        info.entry.overall_points = before_gw + event_points[entry];
+    }
+}
+
+fn explain(points: i32, minutes: i32, fixture_id: u32) -> Vec<Vec<PointsOrFixture>> {
+    let mut sources = Vec::new();
+    if minutes > 0 {
+        sources.push(Point {
+            name: String::from("Minutes played"),
+            points,
+            value: minutes,
+            stat: String::from("minutes"),
+        });
+    }
+    vec![vec![
+        PointsOrFixture::Points(sources),
+        PointsOrFixture::Fixture(fixture_id),
+    ]]
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("Test data could not be serialized")
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(value: Value) -> T {
+    serde_json::from_value(value).expect("Test data does not match the endpoint structs")
+}
+
+fn game() -> Game {
+    from_json(json!({
+        "current_event": CURRENT_GW,
+        "current_event_finished": false,
+        "next_event": CURRENT_GW + 1,
+    }))
+}
+
+// Round robin pairings using the circle method. Returns pairs of 1-based team numbers.
+fn gw_pairings(n_teams: u32, gw: u32) -> Vec<(u32, u32)> {
+    let n = n_teams - n_teams % 2;
+    let mut rotation: Vec<u32> = (2..=n).collect();
+    let shift = ((gw - 1) as usize) % rotation.len().max(1);
+    rotation.rotate_left(shift);
+    let mut circle = vec![1];
+    circle.extend(rotation);
+    (0..(n / 2) as usize)
+        .map(|i| (circle[i], circle[n as usize - 1 - i]))
+        .collect()
+}
+
+fn details(n_teams: u32, h2h: bool) -> Details {
+    let league_entries: Vec<Value> = (1..=n_teams)
+        .map(|t| {
+            json!({
+                "entry_id": entry_id(t),
+                "id": t,
+                "entry_name": format!("Team {}", t),
+                "player_first_name": "Owner",
+                "player_last_name": format!("{}", t),
+                "short_name": format!("T{}", t),
+            })
+        })
+        .collect();
+
+    let mut matches: Vec<Value> = Vec::new();
+    let mut won: HashMap<u32, u32> = HashMap::new();
+    let mut drawn: HashMap<u32, u32> = HashMap::new();
+    let mut lost: HashMap<u32, u32> = HashMap::new();
+    let mut points_for: HashMap<u32, i32> = HashMap::new();
+    let mut points_against: HashMap<u32, i32> = HashMap::new();
+    if h2h {
+        for gw in 1..=CURRENT_GW {
+            for (t1, t2) in gw_pairings(n_teams, gw) {
+                let finished = gw < CURRENT_GW;
+                let (p1, p2) = if finished {
+                    (past_gw_points(t1, gw), past_gw_points(t2, gw))
+                } else {
+                    (0, 0)
+                };
+                if finished {
+                    *points_for.entry(t1).or_default() += p1 as i32;
+                    *points_for.entry(t2).or_default() += p2 as i32;
+                    *points_against.entry(t1).or_default() += p2 as i32;
+                    *points_against.entry(t2).or_default() += p1 as i32;
+                    match p1.cmp(&p2) {
+                        std::cmp::Ordering::Greater => {
+                            *won.entry(t1).or_default() += 1;
+                            *lost.entry(t2).or_default() += 1;
+                        }
+                        std::cmp::Ordering::Less => {
+                            *won.entry(t2).or_default() += 1;
+                            *lost.entry(t1).or_default() += 1;
+                        }
+                        std::cmp::Ordering::Equal => {
+                            *drawn.entry(t1).or_default() += 1;
+                            *drawn.entry(t2).or_default() += 1;
+                        }
+                    }
+                }
+                matches.push(json!({
+                    "event": gw,
+                    "league_entry_1": t1,
+                    "league_entry_1_points": p1,
+                    "league_entry_2": t2,
+                    "league_entry_2_points": p2,
+                    "started": true,
+                    "finished": finished,
+                }));
+            }
+        }
+    }
+
+    let standings: Vec<Value> = (1..=n_teams)
+        .map(|t| {
+            if h2h {
+                let w = won.get(&t).copied().unwrap_or(0);
+                let d = drawn.get(&t).copied().unwrap_or(0);
+                json!({
+                    "league_entry": t,
+                    "total": w * 3 + d,
+                    "rank": t,
+                    "last_rank": t,
+                    "rank_sort": t,
+                    "matches_won": w,
+                    "matches_drawn": d,
+                    "matches_lost": lost.get(&t).copied().unwrap_or(0),
+                    "points_for": points_for.get(&t).copied().unwrap_or(0),
+                    "points_against": points_against.get(&t).copied().unwrap_or(0),
+                })
+            } else {
+                json!({
+                    "league_entry": t,
+                    "total": 500 + 10 * t,
+                    "event_total": 0,
+                    "rank": n_teams + 1 - t,
+                    "last_rank": n_teams + 1 - t,
+                    "rank_sort": n_teams + 1 - t,
+                })
+            }
+        })
+        .collect();
+
+    from_json(json!({
+        "league": {
+            "id": 1,
+            "name": "Test League",
+            "scoring": if h2h { "h" } else { "c" },
+        },
+        "league_entries": league_entries,
+        "standings": standings,
+        "matches": if h2h { Value::Array(matches) } else { Value::Null },
+    }))
+}
+
+fn live(n_players: u32) -> Live {
+    let mut elements = serde_json::Map::new();
+    for id in 1..=n_players {
+        let points = default_points(id);
+        elements.insert(
+            id.to_string(),
+            json!({
+                "explain": explain(points, 90, club_fixture(player_club(id))),
+                "stats": {
+                    "minutes": 90,
+                    "bps": points * 3,
+                    "total_points": points,
+                },
+            }),
+        );
+    }
+
+    let fixtures: Vec<Value> = (1..=N_CLUBS / 2)
+        .map(|fixture_id| {
+            let (home, away) = (fixture_id * 2 - 1, fixture_id * 2);
+            let bps_of = |club: u32| -> Vec<Value> {
+                (1..=n_players)
+                    .filter(|id| player_club(*id) == club)
+                    .map(|id| json!({"element": id, "value": default_points(id) * 3}))
+                    .collect()
+            };
+            json!({
+                "id": fixture_id,
+                "event": CURRENT_GW,
+                "started": true,
+                "finished": true,
+                "finished_provisional": true,
+                "minutes": 90,
+                "team_h": home,
+                "team_a": away,
+                "team_h_score": 1,
+                "team_a_score": 0,
+                "stats": [{"s": "bps", "h": bps_of(home), "a": bps_of(away)}],
+            })
+        })
+        .collect();
+
+    from_json(json!({ "elements": elements, "fixtures": fixtures }))
+}
+
+fn static_info(n_players: u32) -> StaticInfo {
+    let elements: Vec<Value> = (1..=n_players)
+        .map(|id| {
+            let pick = (id - 1) % SQUAD_SIZE;
+            json!({
+                "id": id,
+                "web_name": format!("Player{}", id),
+                "first_name": "First",
+                "second_name": format!("Player{}", id),
+                "team": player_club(id),
+                "element_type": PICK_ELEMENT_TYPES[pick as usize],
+                "status": "a",
+                "news": "",
+                "total_points": 10 * default_points(id),
+                "form": format!("{}.0", default_points(id)),
+                "ep_this": format!("{}.0", default_points(id)),
+                "ep_next": format!("{}.5", default_points(id)),
+            })
+        })
+        .collect();
+
+    let events: Vec<Value> = (1..=N_GAMEWEEKS)
+        .map(|gw| {
+            json!({
+                "id": gw,
+                "name": format!("Gameweek {}", gw),
+                "finished": gw < CURRENT_GW,
+                "deadline_time": format!("2030-08-{:02}T17:30:00Z", gw % 28 + 1),
+            })
+        })
+        .collect();
+
+    let teams: Vec<Value> = (1..=N_CLUBS)
+        .map(|club| {
+            json!({
+                "id": club,
+                "code": club + 100,
+                "name": format!("Club {}", club),
+                "short_name": format!("C{:02}", club),
+            })
+        })
+        .collect();
+
+    let mut upcoming = serde_json::Map::new();
+    for gw in (CURRENT_GW + 1)..=(CURRENT_GW + 3) {
+        let fixtures: Vec<Value> = (1..=N_CLUBS / 2)
+            .map(|f| {
+                json!({
+                    "id": gw * 100 + f,
+                    "event": gw,
+                    "team_h": f * 2,
+                    "team_a": f * 2 - 1,
+                    "started": false,
+                    "finished": false,
+                })
+            })
+            .collect();
+        upcoming.insert(gw.to_string(), Value::Array(fixtures));
+    }
+
+    from_json(json!({
+        "elements": elements,
+        "element_types": [],
+        "element_stats": [],
+        "events": {"current": CURRENT_GW, "next": CURRENT_GW + 1, "data": events},
+        "fixtures": upcoming,
+        "settings": {
+            "league": {"draft_reminder_hours": [], "public_entry_sizes": []},
+            "scoring": {},
+            "squad": {
+                "size": 15,
+                "play": 11,
+                "min_play_GKP": 1, "max_play_GKP": 1,
+                "min_play_DEF": 3, "max_play_DEF": 5,
+                "min_play_MID": 2, "max_play_MID": 5,
+                "min_play_FWD": 1, "max_play_FWD": 3,
+                "position_type_locks": {},
+            },
+            "transactions": {"waivers_before_deadline_hours_event": {}},
+            "ui": {},
+        },
+        "teams": teams,
+    }))
+}
+
+fn team_gw(team: u32) -> TeamGw {
+    let picks: Vec<Value> = (1..=SQUAD_SIZE)
+        .map(|pick| json!({"element": player_id(team, pick), "position": pick}))
+        .collect();
+    from_json(json!({ "picks": picks, "subs": [] }))
+}
+
+fn team_info(team: u32) -> TeamInfo {
+    from_json(json!({
+        "entry": {
+            "name": format!("Team {}", team),
+            "player_first_name": "Owner",
+            "player_last_name": format!("{}", team),
+            "league_set": [],
+            "event_points": 0,
+            "overall_points": 500 + 10 * team as i32,
+        }
+    }))
+}
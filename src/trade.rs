@@ -0,0 +1,379 @@
+// Analyzes proposed trades between two league teams.
+//
+// A trade is evaluated by comparing each team's squad before and after the trade on season
+// points, form, expected points and the difficulty of the upcoming fixtures. The fixture
+// difficulty is not the FPL fixture difficulty rating, which the draft api does not have, but a
+// proxy for it: the strength of the opponent, measured as the total points of all the players of
+// the opponent this season and bucketed into 1 (easiest) to 5 (hardest).
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::propcomp;
+use crate::storage::table::Position;
+use crate::storage::FplEndpoints;
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TradeProposal {
+    /// The entry id of the first team
+    pub entry_1: u32,
+
+    /// The entry id of the second team
+    pub entry_2: u32,
+
+    /// The players the first team gives to the second team
+    pub entry_1_gives: Vec<u32>,
+
+    /// The players the second team gives to the first team
+    pub entry_2_gives: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TradeAnalysis {
+    pub entry_1: TradeSide,
+    pub entry_2: TradeSide,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TradeSide {
+    pub team_code: u32,
+    pub team_name: String,
+
+    /// The players leaving the team
+    pub gives: Vec<TradePlayer>,
+
+    /// The players joining the team
+    pub receives: Vec<TradePlayer>,
+
+    /// The squad before the trade
+    pub before: SquadValue,
+
+    /// The squad after the trade
+    pub after: SquadValue,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct SquadValue {
+    /// Sum of the season points of all players in the squad
+    pub season_points: i32,
+
+    /// Sum of the form of all players in the squad
+    pub form: f64,
+
+    /// Sum of the expected points next gameweek of all players in the squad
+    pub expected_points: f64,
+
+    /// Average difficulty (1-5) of the upcoming fixtures of all players in the squad, see
+    /// `UpcomingFixture.difficulty`
+    pub fixture_difficulty: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TradePlayer {
+    pub id: u32,
+    pub display_name: String,
+    pub team_short_name: String,
+    pub team_pos: Position,
+    pub season_points: i32,
+    pub form: f64,
+    pub expected_points: f64,
+    pub upcoming_fixtures: Vec<UpcomingFixture>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct UpcomingFixture {
+    pub gw: u32,
+    pub opponent_short_name: String,
+    pub home: bool,
+
+    /// Proxy for the difficulty of the fixture from 1 (easiest) to 5 (hardest), not the FPL
+    /// fixture difficulty rating. The Premier League teams are ranked by the total season points
+    /// of their players and split into five equal buckets, the strongest teams are the hardest.
+    pub difficulty: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TradeError {
+    UnknownEntry(u32),
+    NotOwned { entry: u32, player: u32 },
+    Unbalanced(String),
+}
+
+impl fmt::Display for TradeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TradeError::UnknownEntry(entry) => write!(f, "Unknown league entry: {}", entry),
+            TradeError::NotOwned { entry, player } => {
+                write!(f, "Player {} is not owned by entry {}", player, entry)
+            }
+            TradeError::Unbalanced(msg) => write!(f, "Invalid trade: {}", msg),
+        }
+    }
+}
+
+pub fn analyze_trade(
+    endpoints: &FplEndpoints,
+    proposal: &TradeProposal,
+) -> Result<TradeAnalysis, TradeError> {
+    validate_trade(endpoints, proposal)?;
+
+    let difficulties = compute_club_difficulties(endpoints);
+    let squad_1 = get_squad(endpoints, proposal.entry_1)?;
+    let squad_2 = get_squad(endpoints, proposal.entry_2)?;
+
+    let entry_1 = compute_trade_side(
+        endpoints,
+        &difficulties,
+        proposal.entry_1,
+        &squad_1,
+        &proposal.entry_1_gives,
+        &proposal.entry_2_gives,
+    );
+    let entry_2 = compute_trade_side(
+        endpoints,
+        &difficulties,
+        proposal.entry_2,
+        &squad_2,
+        &proposal.entry_2_gives,
+        &proposal.entry_1_gives,
+    );
+    Ok(TradeAnalysis { entry_1, entry_2 })
+}
+
+// A trade must swap the same number of players of each position so both squads stay valid
+fn validate_trade(endpoints: &FplEndpoints, proposal: &TradeProposal) -> Result<(), TradeError> {
+    if proposal.entry_1 == proposal.entry_2 {
+        return Err(TradeError::Unbalanced(String::from(
+            "a team can't trade with itself",
+        )));
+    }
+    if proposal.entry_1_gives.is_empty() {
+        return Err(TradeError::Unbalanced(String::from(
+            "no players are traded",
+        )));
+    }
+
+    for (entry, gives) in [
+        (proposal.entry_1, &proposal.entry_1_gives),
+        (proposal.entry_2, &proposal.entry_2_gives),
+    ] {
+        let squad = get_squad(endpoints, entry)?;
+        if let Some(player) = gives.iter().find(|p| !squad.contains(p)) {
+            return Err(TradeError::NotOwned {
+                entry,
+                player: *player,
+            });
+        }
+        if gives.iter().collect::<HashSet<_>>().len() != gives.len() {
+            return Err(TradeError::Unbalanced(String::from(
+                "a player is traded more than once",
+            )));
+        }
+    }
+
+    let mut positions_1: Vec<u32> = proposal
+        .entry_1_gives
+        .iter()
+        .map(|p| propcomp::get_player_position(endpoints, *p))
+        .collect();
+    let mut positions_2: Vec<u32> = proposal
+        .entry_2_gives
+        .iter()
+        .map(|p| propcomp::get_player_position(endpoints, *p))
+        .collect();
+    positions_1.sort();
+    positions_2.sort();
+    if positions_1 != positions_2 {
+        return Err(TradeError::Unbalanced(String::from(
+            "both teams must trade the same number of players of each position",
+        )));
+    }
+    Ok(())
+}
+
+fn get_squad(endpoints: &FplEndpoints, entry: u32) -> Result<Vec<u32>, TradeError> {
+    endpoints
+        .teams_gws
+        .get(&entry)
+        .map(|team_gw| team_gw.picks.iter().map(|p| p.element as u32).collect())
+        .ok_or(TradeError::UnknownEntry(entry))
+}
+
+fn compute_trade_side(
+    endpoints: &FplEndpoints,
+    difficulties: &HashMap<u32, u32>,
+    entry: u32,
+    squad: &[u32],
+    gives: &[u32],
+    receives: &[u32],
+) -> TradeSide {
+    let squad_after: Vec<u32> = squad
+        .iter()
+        .filter(|p| !gives.contains(p))
+        .chain(receives.iter())
+        .copied()
+        .collect();
+
+    let to_players = |ids: &[u32]| -> Vec<TradePlayer> {
+        ids.iter()
+            .map(|id| compute_trade_player(endpoints, difficulties, *id))
+            .collect()
+    };
+
+    TradeSide {
+        team_code: entry,
+        team_name: propcomp::get_team_name(endpoints, entry),
+        gives: to_players(gives),
+        receives: to_players(receives),
+        before: compute_squad_value(&to_players(squad)),
+        after: compute_squad_value(&to_players(&squad_after)),
+    }
+}
+
+fn compute_squad_value(players: &[TradePlayer]) -> SquadValue {
+    let difficulties: Vec<u32> = players
+        .iter()
+        .flat_map(|p| p.upcoming_fixtures.iter().map(|f| f.difficulty))
+        .collect();
+    let fixture_difficulty = if difficulties.is_empty() {
+        0.0
+    } else {
+        difficulties.iter().sum::<u32>() as f64 / difficulties.len() as f64
+    };
+
+    SquadValue {
+        season_points: players.iter().map(|p| p.season_points).sum(),
+        form: players.iter().map(|p| p.form).sum(),
+        expected_points: players.iter().map(|p| p.expected_points).sum(),
+        fixture_difficulty,
+    }
+}
+
+fn compute_trade_player(
+    endpoints: &FplEndpoints,
+    difficulties: &HashMap<u32, u32>,
+    player_id: u32,
+) -> TradePlayer {
+    let team = propcomp::compute_player_team(endpoints, player_id);
+    let upcoming_fixtures = propcomp::get_team_upcoming_fixtures(endpoints, team.id)
+        .into_iter()
+        .filter_map(|f| {
+            let home = f.team_h == Some(team.id as i32);
+            let opponent = if home { f.team_a } else { f.team_h }? as u32;
+            let opponent_short_name = endpoints
+                .static_info
+                .teams
+                .iter()
+                .find(|t| t.id == opponent)
+                .map(|t| t.short_name.clone())
+                .unwrap_or_default();
+            Some(UpcomingFixture {
+                gw: f.event.unwrap_or(0) as u32,
+                opponent_short_name,
+                home,
+                difficulty: difficulties.get(&opponent).copied().unwrap_or(3),
+            })
+        })
+        .collect();
+
+    TradePlayer {
+        id: player_id,
+        display_name: propcomp::get_player_display_name(endpoints, player_id),
+        team_short_name: team.short_name,
+        team_pos: Position::from_number(propcomp::get_player_position(endpoints, player_id)),
+        season_points: propcomp::get_player_season_points(endpoints, player_id),
+        form: propcomp::get_player_form(endpoints, player_id),
+        expected_points: propcomp::get_player_expected_points(endpoints, player_id),
+        upcoming_fixtures,
+    }
+}
+
+// Buckets the Premier League teams into difficulties 1-5 by the total season points of their
+// players, where the strongest teams are the hardest opponents.
+fn compute_club_difficulties(endpoints: &FplEndpoints) -> HashMap<u32, u32> {
+    let mut club_points: HashMap<u32, i32> = endpoints
+        .static_info
+        .teams
+        .iter()
+        .map(|t| (t.id, 0))
+        .collect();
+    for element in endpoints.static_info.elements.iter() {
+        *club_points.entry(element.team).or_default() += element.total_points.unwrap_or(0);
+    }
+
+    let mut ranked: Vec<(u32, i32)> = club_points.into_iter().collect();
+    ranked.sort_by_key(|(id, points)| (*points, *id));
+    let n_clubs = ranked.len().max(1);
+    ranked
+        .into_iter()
+        .enumerate()
+        .map(|(i, (id, _))| (id, (i * 5 / n_clubs) as u32 + 1))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata;
+
+    #[test]
+    fn trade_swaps_players_between_squads() {
+        let endpoints = testdata::endpoints(2, false);
+        let given = testdata::player_id(1, 6);
+        let received = testdata::player_id(2, 7);
+        let proposal = TradeProposal {
+            entry_1: testdata::entry_id(1),
+            entry_2: testdata::entry_id(2),
+            entry_1_gives: vec![given],
+            entry_2_gives: vec![received],
+        };
+
+        let analysis = analyze_trade(&endpoints, &proposal).unwrap();
+
+        let given_points = propcomp::get_player_season_points(&endpoints, given);
+        let received_points = propcomp::get_player_season_points(&endpoints, received);
+        let side = &analysis.entry_1;
+        assert_eq!(side.gives[0].id, given);
+        assert_eq!(side.receives[0].id, received);
+        assert_eq!(
+            side.after.season_points - side.before.season_points,
+            received_points - given_points
+        );
+        assert_eq!(
+            analysis.entry_2.after.season_points - analysis.entry_2.before.season_points,
+            given_points - received_points
+        );
+        assert!(!side.gives[0].upcoming_fixtures.is_empty());
+        assert!(side.gives[0]
+            .upcoming_fixtures
+            .iter()
+            .all(|f| (1..=5).contains(&f.difficulty)));
+    }
+
+    #[test]
+    fn trade_must_be_valid() {
+        let endpoints = testdata::endpoints(2, false);
+        let mut proposal = TradeProposal {
+            entry_1: testdata::entry_id(1),
+            entry_2: testdata::entry_id(2),
+            entry_1_gives: vec![testdata::player_id(2, 6)],
+            entry_2_gives: vec![testdata::player_id(2, 7)],
+        };
+        assert_eq!(
+            analyze_trade(&endpoints, &proposal).unwrap_err(),
+            TradeError::NotOwned {
+                entry: testdata::entry_id(1),
+                player: testdata::player_id(2, 6)
+            }
+        );
+
+        // A goalkeeper for a midfielder
+        proposal.entry_1_gives = vec![testdata::player_id(1, 1)];
+        assert!(matches!(
+            analyze_trade(&endpoints, &proposal).unwrap_err(),
+            TradeError::Unbalanced(_)
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+// Terminal dashboard of the live league. Shows the league table, the players of the selected team
+// and the latest point events, and redraws whenever the fetcher and computer publish new data.
+//
+// Keys: up/down (or k/j) select a team, q or Esc quits.
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, Row, Table, TableState};
+use ratatui::Frame;
+use tokio::sync::{mpsc, watch};
+
+use crate::cli;
+use crate::events::{self, PointEvent};
+use crate::storage::table::RankMovement;
+use crate::storage::{FplEndpoints, LeagueTable};
+
+// The number of point events that are kept on screen
+const MAX_EVENTS: usize = 100;
+
+struct App {
+    table: Arc<LeagueTable>,
+    gw: u32,
+    selected: usize,
+    // The latest event first
+    events: VecDeque<PointEvent>,
+    last_fetch: Instant,
+}
+
+impl App {
+    // Adds the point events between the current table and the new one, unless a new gameweek
+    // has started
+    fn update_table(&mut self, table: Arc<LeagueTable>, gw: u32) {
+        if gw == self.gw {
+            for e in events::compute_point_events(gw, &self.table, &table) {
+                self.events.push_front(e);
+            }
+            self.events.truncate(MAX_EVENTS);
+        } else {
+            self.events.clear();
+        }
+        self.table = table;
+        self.gw = gw;
+        self.selected = self
+            .selected
+            .min(self.table.entries.len().saturating_sub(1));
+    }
+
+    fn select_next(&mut self) {
+        if self.selected + 1 < self.table.entries.len() {
+            self.selected += 1;
+        }
+    }
+
+    fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+pub async fn run(
+    mut endpoints_rx: watch::Receiver<Arc<FplEndpoints>>,
+    mut table_rx: watch::Receiver<Arc<LeagueTable>>,
+) -> std::io::Result<()> {
+    let mut app = App {
+        table: table_rx.borrow_and_update().clone(),
+        gw: current_gw(&endpoints_rx.borrow_and_update()),
+        selected: 0,
+        events: VecDeque::new(),
+        last_fetch: Instant::now(),
+    };
+
+    // Reading terminal events blocks, so it is done on its own thread. The thread stops at the
+    // first key press after the receiver has been dropped.
+    let (key_tx, mut key_rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || loop {
+        match event::read() {
+            Ok(e) => {
+                if key_tx.send(e).is_err() {
+                    break;
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to read terminal event: {}", e);
+                break;
+            }
+        }
+    });
+
+    let mut terminal = ratatui::init();
+    let mut tick = tokio::time::interval(Duration::from_secs(1));
+    let result = loop {
+        if let Err(e) = terminal.draw(|frame| draw(frame, &app)) {
+            break Err(e);
+        }
+        tokio::select! {
+            Ok(()) = endpoints_rx.changed() => {
+                app.last_fetch = Instant::now();
+            }
+            Ok(()) = table_rx.changed() => {
+                let table = table_rx.borrow_and_update().clone();
+                let gw = current_gw(&endpoints_rx.borrow());
+                app.update_table(table, gw);
+            }
+            Some(e) = key_rx.recv() => {
+                if let Event::Key(key) = e {
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => break Ok(()),
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            break Ok(())
+                        }
+                        KeyCode::Down | KeyCode::Char('j') => app.select_next(),
+                        KeyCode::Up | KeyCode::Char('k') => app.select_previous(),
+                        _ => {}
+                    }
+                }
+            }
+            _ = tick.tick() => {}
+        }
+    };
+    ratatui::restore();
+    result
+}
+
+fn current_gw(endpoints: &FplEndpoints) -> u32 {
+    endpoints.game.current_event.unwrap_or(1)
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let [main, events_area, status_area] = Layout::vertical([
+        Constraint::Min(10),
+        Constraint::Length(12),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [table_area, team_area] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(main);
+
+    let rows = app.table.entries.iter().enumerate().map(|(i, entry)| {
+        let movement = match entry.rank_info.as_ref().map(|r| r.live_movement) {
+            Some(RankMovement::Up) => "▲",
+            Some(RankMovement::Down) => "▼",
+            _ => " ",
+        };
+        Row::new(vec![
+            format!("{}{}", i + 1, movement),
+            entry.team_name.clone(),
+            entry.gw_points.to_string(),
+            entry.gw_projected_points.to_string(),
+            entry.total_projected_points.to_string(),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(4),
+            Constraint::Fill(1),
+            Constraint::Length(4),
+            Constraint::Length(5),
+            Constraint::Length(6),
+        ],
+    )
+    .header(Row::new(vec!["#", "Team", "GW", "Proj", "Total"]).add_modifier(Modifier::BOLD))
+    .row_highlight_style(Style::new().reversed())
+    .block(Block::bordered().title(format!(" {} - GW {} ", app.table.name, app.gw)));
+    let mut state = TableState::new().with_selected(Some(app.selected));
+    frame.render_stateful_widget(table, table_area, &mut state);
+
+    if let Some(entry) = app.table.entries.get(app.selected) {
+        let mut players: Vec<_> = entry.players.iter().collect();
+        players.sort_by_key(|p| p.pick_number);
+        let rows = players.into_iter().map(|p| {
+            let row = Row::new(vec![
+                format!("{:?}", p.team_pos),
+                p.display_name.clone(),
+                p.team.short_name.clone(),
+                p.points.to_string(),
+                p.projected_points.to_string(),
+                cli::play_status_text(&p.play_status),
+            ]);
+            if p.on_field {
+                row
+            } else {
+                row.dim()
+            }
+        });
+        let team = Table::new(
+            rows,
+            [
+                Constraint::Length(3),
+                Constraint::Fill(1),
+                Constraint::Length(4),
+                Constraint::Length(4),
+                Constraint::Length(5),
+                Constraint::Length(18),
+            ],
+        )
+        .header(
+            Row::new(vec!["Pos", "Player", "Club", "Pts", "Proj", "Status"])
+                .add_modifier(Modifier::BOLD),
+        )
+        .block(Block::bordered().title(format!(" {} ({}) ", entry.team_name, entry.owner_name)));
+        frame.render_widget(team, team_area);
+    }
+
+    let items = app.events.iter().map(|e| {
+        let item = ListItem::new(format!(
+            "{:+3}  {:<16} {:<20} {} {}",
+            e.points,
+            e.player_name,
+            e.team_name,
+            if e.amount < 0 { "lost" } else { "got" },
+            e.name
+        ));
+        if e.counting {
+            item
+        } else {
+            item.dim()
+        }
+    });
+    frame.render_widget(
+        List::new(items).block(Block::bordered().title(" Latest point events ")),
+        events_area,
+    );
+
+    frame.render_widget(
+        Line::from(format!(
+            " Last fetch {}s ago | up/down: select team | q: quit",
+            app.last_fetch.elapsed().as_secs()
+        ))
+        .dim(),
+        status_area,
+    );
+}
@@ -0,0 +1,634 @@
+// Outbound webhooks for notable events in the league, eg. a goal of a player in a league team.
+//
+// The notifier compares every new league table with the one before it, like the point events of
+// the tui, and posts the events to the webhooks of the config in the format each webhook expects.
+// The webhooks are read from the config for every table, so a reloaded config applies right away.
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::watch;
+
+use crate::events::{self, PointEvent};
+use crate::initializer::{AppConfig, WebhookConfig};
+use crate::lineup::LineupWarnings;
+use crate::metrics;
+use crate::storage::table::{Entry, InjuryStatus, Scoring};
+use crate::storage::LeagueTable;
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+// Failures that are likely temporary are posted again after the delay, up to the attempts
+const SEND_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+// Discord rejects messages longer than this
+const DISCORD_MAX_CHARS: usize = 2000;
+// The point sources posted as a player scoring. The bonus is only a point source of the FPL api
+// once it is confirmed, so the projected bonus during a match is not posted.
+const SCORING_STATS: [&str; 4] = ["goals_scored", "assists", "clean_sheets", "bonus"];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    // The event as json, with a message for humans
+    #[default]
+    Json,
+    Discord,
+    Slack,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventKind {
+    GwFinished,
+    PlayerScored,
+    H2hLeadChanged,
+    InjuryStatusChanged,
+    LineupWarnings,
+}
+
+impl WebhookEventKind {
+    pub fn all() -> Vec<WebhookEventKind> {
+        vec![
+            WebhookEventKind::GwFinished,
+            WebhookEventKind::PlayerScored,
+            WebhookEventKind::H2hLeadChanged,
+            WebhookEventKind::InjuryStatusChanged,
+            WebhookEventKind::LineupWarnings,
+        ]
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    // The gameweek is finished and the table is final
+    GwFinished {
+        gw: u32,
+        table: Vec<Standing>,
+    },
+    // A starter of a league team scored, assisted, kept a clean sheet or got bonus points, or
+    // FPL took it back, see events::PointEvent
+    PlayerScored(PointEvent),
+    // Another team leads a head-to-head match of the gameweek, no leader if the teams are level
+    H2hLeadChanged {
+        gw: u32,
+        teams: [MatchTeam; 2],
+        leader: Option<u32>,
+        previous_leader: Option<u32>,
+    },
+    // The injury status of a player in a league team changed
+    InjuryStatusChanged {
+        team_code: u32,
+        team_name: String,
+        player_id: u32,
+        player_name: String,
+        previous_status: InjuryStatus,
+        status: InjuryStatus,
+        news: Option<String>,
+    },
+    // The lineups with problems before the next deadline, see lineup.rs
+    LineupWarnings(LineupWarnings),
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Standing {
+    pub rank: usize,
+    pub team_code: u32,
+    pub team_name: String,
+    pub owner_name: String,
+    pub gw_points: i32,
+    pub total_points: i32,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MatchTeam {
+    pub team_code: u32,
+    pub team_name: String,
+    pub gw_points: i32,
+}
+
+impl WebhookEvent {
+    pub fn kind(&self) -> WebhookEventKind {
+        match self {
+            WebhookEvent::GwFinished { .. } => WebhookEventKind::GwFinished,
+            WebhookEvent::PlayerScored(_) => WebhookEventKind::PlayerScored,
+            WebhookEvent::H2hLeadChanged { .. } => WebhookEventKind::H2hLeadChanged,
+            WebhookEvent::InjuryStatusChanged { .. } => WebhookEventKind::InjuryStatusChanged,
+            WebhookEvent::LineupWarnings(_) => WebhookEventKind::LineupWarnings,
+        }
+    }
+
+    // The event in a sentence or two, for chat messages
+    pub fn message(&self) -> String {
+        match self {
+            WebhookEvent::GwFinished { gw, table } => {
+                let mut message = format!("GW {} is finished. The final table:", gw);
+                for s in table {
+                    message.push_str(&format!(
+                        "\n{}. {} ({}) {} points, {} in total",
+                        s.rank, s.team_name, s.owner_name, s.gw_points, s.total_points
+                    ));
+                }
+                message
+            }
+            WebhookEvent::PlayerScored(e) => format!(
+                "{} of {}: {} {:+}, {:+} points",
+                e.player_name, e.team_name, e.name, e.amount, e.points
+            ),
+            WebhookEvent::H2hLeadChanged {
+                gw, teams, leader, ..
+            } => {
+                let [a, b] = teams;
+                match leader {
+                    Some(code) => {
+                        let (leader, other) = if *code == a.team_code { (a, b) } else { (b, a) };
+                        format!(
+                            "GW {}: {} leads {} {}-{}",
+                            gw,
+                            leader.team_name,
+                            other.team_name,
+                            leader.gw_points,
+                            other.gw_points
+                        )
+                    }
+                    None => format!(
+                        "GW {}: {} and {} are level at {}-{}",
+                        gw, a.team_name, b.team_name, a.gw_points, b.gw_points
+                    ),
+                }
+            }
+            WebhookEvent::InjuryStatusChanged {
+                team_name,
+                player_name,
+                previous_status,
+                status,
+                news,
+                ..
+            } => {
+                let mut message = format!(
+                    "{} of {} is {:?}, was {:?}",
+                    player_name, team_name, status, previous_status
+                );
+                if let Some(news) = news.as_ref().filter(|n| !n.is_empty()) {
+                    message.push_str(&format!(": {}", news));
+                }
+                message
+            }
+            WebhookEvent::LineupWarnings(lineups) => {
+                let mut message = format!("Lineup warnings before the GW {} deadline:", lineups.gw);
+                for team in lineups.teams.iter() {
+                    message.push_str(&format!("\n{} ({}):", team.team_name, team.owner_name));
+                    for warning in team.warnings.iter() {
+                        message.push_str(&format!("\n- {}", warning.message()));
+                    }
+                }
+                message
+            }
+        }
+    }
+}
+
+// What the events are found from. The gameweek is the one the table was computed for, so it always
+// matches the points of the table.
+#[derive(Debug, Clone)]
+pub struct LeagueState {
+    pub gw: u32,
+    pub gw_finished: bool,
+    pub table: Arc<LeagueTable>,
+}
+
+impl LeagueState {
+    pub fn new(table: Arc<LeagueTable>) -> LeagueState {
+        LeagueState {
+            gw: table.gw,
+            gw_finished: table.gw_finished,
+            table,
+        }
+    }
+}
+
+// The events between two states of the league. Point events and lead changes are only found
+// within a gameweek.
+pub fn compute_webhook_events(previous: &LeagueState, current: &LeagueState) -> Vec<WebhookEvent> {
+    let mut events = Vec::new();
+    let same_gw = previous.gw == current.gw;
+    if same_gw {
+        events.extend(
+            events::compute_point_events(current.gw, &previous.table, &current.table)
+                .into_iter()
+                .filter(|e| e.counting && e.points != 0 && SCORING_STATS.contains(&e.stat.as_str()))
+                .map(WebhookEvent::PlayerScored),
+        );
+        if let Scoring::H2H = current.table.scoring {
+            events.extend(lead_changes(current.gw, &previous.table, &current.table));
+        }
+    }
+    events.extend(injury_status_changes(&previous.table, &current.table));
+    if same_gw && current.gw_finished && !previous.gw_finished {
+        events.push(WebhookEvent::GwFinished {
+            gw: current.gw,
+            table: standings(&current.table),
+        });
+    }
+    events
+}
+
+fn standings(table: &LeagueTable) -> Vec<Standing> {
+    table
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(i, e)| Standing {
+            rank: i + 1,
+            team_code: e.team_code,
+            team_name: e.team_name.clone(),
+            owner_name: e.owner_name.clone(),
+            gw_points: e.gw_points,
+            total_points: e.total_points,
+        })
+        .collect()
+}
+
+// The team leading a match, none if the teams are level
+fn leader(a: &Entry, b: &Entry) -> Option<u32> {
+    match a.gw_points.cmp(&b.gw_points) {
+        std::cmp::Ordering::Greater => Some(a.team_code),
+        std::cmp::Ordering::Less => Some(b.team_code),
+        std::cmp::Ordering::Equal => None,
+    }
+}
+
+fn lead_changes(gw: u32, previous: &LeagueTable, current: &LeagueTable) -> Vec<WebhookEvent> {
+    let previous_entries: HashMap<u32, &Entry> =
+        previous.entries.iter().map(|e| (e.team_code, e)).collect();
+    let current_entries: HashMap<u32, &Entry> =
+        current.entries.iter().map(|e| (e.team_code, e)).collect();
+
+    let mut changes = Vec::new();
+    for a in current.entries.iter() {
+        let Some(opponent) = a.h2h_info.as_ref().map(|h| h.current_opponent) else {
+            continue;
+        };
+        // Every match is seen from both teams, it is handled by the team with the lower code
+        let Some(b) = current_entries
+            .get(&opponent)
+            .filter(|b| a.team_code < b.team_code)
+        else {
+            continue;
+        };
+        let (Some(previous_a), Some(previous_b)) = (
+            previous_entries.get(&a.team_code),
+            previous_entries.get(&b.team_code),
+        ) else {
+            continue;
+        };
+        let previous_leader = leader(previous_a, previous_b);
+        let leader = leader(a, b);
+        if leader != previous_leader {
+            let team = |e: &Entry| MatchTeam {
+                team_code: e.team_code,
+                team_name: e.team_name.clone(),
+                gw_points: e.gw_points,
+            };
+            changes.push(WebhookEvent::H2hLeadChanged {
+                gw,
+                teams: [team(a), team(b)],
+                leader,
+                previous_leader,
+            });
+        }
+    }
+    changes
+}
+
+fn injury_status_changes(previous: &LeagueTable, current: &LeagueTable) -> Vec<WebhookEvent> {
+    let previous_status: HashMap<(u32, u32), InjuryStatus> = previous
+        .entries
+        .iter()
+        .flat_map(|e| {
+            e.players
+                .iter()
+                .map(move |p| ((e.team_code, p.id), p.status))
+        })
+        .collect();
+
+    let mut changes = Vec::new();
+    for entry in current.entries.iter() {
+        for player in entry.players.iter() {
+            match previous_status.get(&(entry.team_code, player.id)) {
+                Some(status) if *status != player.status => {
+                    changes.push(WebhookEvent::InjuryStatusChanged {
+                        team_code: entry.team_code,
+                        team_name: entry.team_name.clone(),
+                        player_id: player.id,
+                        player_name: player.display_name.clone(),
+                        previous_status: *status,
+                        status: player.status,
+                        news: player.news.clone(),
+                    })
+                }
+                _ => {}
+            }
+        }
+    }
+    changes
+}
+
+// The body posted to a webhook for an event
+pub fn payload(format: WebhookFormat, league_id: u32, event: &WebhookEvent) -> serde_json::Value {
+    let message = event.message();
+    match format {
+        WebhookFormat::Json => json!({
+            "league_id": league_id,
+            "message": message,
+            "event": event,
+        }),
+        WebhookFormat::Discord => json!({
+            "content": message.chars().take(DISCORD_MAX_CHARS).collect::<String>(),
+        }),
+        WebhookFormat::Slack => json!({ "text": message }),
+    }
+}
+
+pub fn http_client() -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder().timeout(SEND_TIMEOUT).build()
+}
+
+// Posts the events to every webhook that wants them. Failures are logged and counted.
+pub async fn send(
+    http: &reqwest::Client,
+    webhooks: &[WebhookConfig],
+    league_id: u32,
+    events: &[WebhookEvent],
+) {
+    for (i, webhook) in webhooks.iter().enumerate() {
+        for event in events.iter().filter(|e| webhook.events.contains(&e.kind())) {
+            let body = payload(webhook.format, league_id, event);
+            let format = format!("{:?}", webhook.format).to_lowercase();
+            match post(http, webhook, &body).await {
+                Ok(()) => {
+                    metrics::increment("fpl_webhook_deliveries_total", &[("format", &format)])
+                }
+                Err(e) => {
+                    // The url is secret, so the webhook is identified by its position in the config
+                    tracing::warn!("Failed to post {:?} to webhook {}: {}", event.kind(), i, e);
+                    metrics::increment("fpl_webhook_failures_total", &[("format", &format)]);
+                }
+            }
+        }
+    }
+}
+
+async fn post(
+    http: &reqwest::Client,
+    webhook: &WebhookConfig,
+    body: &serde_json::Value,
+) -> Result<(), String> {
+    let mut attempt = 1;
+    loop {
+        let (error, retry) = match http.post(webhook.url.expose()).json(body).send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => {
+                let status = response.status();
+                let retry = status.is_server_error() || status.as_u16() == 429;
+                (format!("HTTP {}", status), retry)
+            }
+            // Without the url, which the error contains
+            Err(e) => (e.without_url().to_string(), true),
+        };
+        if !retry || attempt >= SEND_ATTEMPTS {
+            return Err(error);
+        }
+        attempt += 1;
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+}
+
+// Posts the events of every new league table to the webhooks of the config
+pub async fn notifier(
+    config: watch::Receiver<Arc<AppConfig>>,
+    mut table_rx: watch::Receiver<Arc<LeagueTable>>,
+) {
+    let http = match http_client() {
+        Ok(http) => http,
+        Err(e) => {
+            tracing::error!(
+                "Could not create the webhook client, not sending webhooks: {}",
+                e
+            );
+            return;
+        }
+    };
+    let mut previous = LeagueState::new(table_rx.borrow_and_update().clone());
+    loop {
+        if table_rx.changed().await.is_err() {
+            tracing::error!("Table channel closed, stopping webhook notifier");
+            return;
+        }
+        let current = LeagueState::new(table_rx.borrow_and_update().clone());
+        // The tables of different leagues have nothing in common, the events start over
+        if current.table.code != previous.table.code {
+            tracing::info!(
+                "League changed from {} to {}, not posting events for the switch",
+                previous.table.code,
+                current.table.code
+            );
+            previous = current;
+            continue;
+        }
+        let events = compute_webhook_events(&previous, &current);
+        previous = current;
+
+        let webhooks = config.borrow().webhooks.clone();
+        if events.is_empty() || webhooks.is_empty() {
+            continue;
+        }
+        tracing::debug!(
+            "Posting {} events to {} webhooks",
+            events.len(),
+            webhooks.len()
+        );
+        send(&http, &webhooks, previous.table.code, &events).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use axum::{extract::State, routing::post, Json, Router};
+
+    use super::*;
+    use crate::computer;
+    use crate::initializer::Secret;
+    use crate::storage::FplEndpoints;
+    use crate::testdata;
+
+    fn state(endpoints: &FplEndpoints) -> LeagueState {
+        let table = computer::compute_new_league_table(endpoints).unwrap();
+        LeagueState::new(Arc::new(table))
+    }
+
+    #[test]
+    fn events_are_found_between_tables() {
+        let mut endpoints = testdata::endpoints(2, true);
+        let before = state(&endpoints);
+        let entries = &before.table.entries;
+        let trailing = match leader(&entries[0], &entries[1]) {
+            Some(code) if code == entries[0].team_code => &entries[1],
+            _ => &entries[0],
+        };
+        let trailing_team = trailing.team_code - testdata::ENTRY_ID_OFFSET;
+
+        testdata::set_player_points(
+            &mut endpoints,
+            testdata::player_id(trailing_team, 2),
+            100,
+            90,
+        );
+        // Only the goal of the starter is posted, not the minutes or the goal of the bench player
+        let scorer = testdata::player_id(trailing_team, 2);
+        testdata::add_point_source(&mut endpoints, scorer, "goals_scored", "Goals scored", 1, 6);
+        let bench = testdata::player_id(trailing_team, 13);
+        testdata::add_point_source(&mut endpoints, bench, "goals_scored", "Goals scored", 1, 6);
+        let doubtful = testdata::player_id(1, 5);
+        endpoints.modify(|data| {
+            let element = Arc::make_mut(&mut data.static_info)
+                .elements
+                .iter_mut()
+                .find(|e| e.id == Some(doubtful as i32))
+                .unwrap();
+            element.status = String::from("d");
+            element.news = Some(String::from("Knock - 50% chance of playing"));
+        });
+        let after = state(&endpoints);
+
+        let events = compute_webhook_events(&before, &after);
+        let kinds: Vec<WebhookEventKind> = events.iter().map(WebhookEvent::kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                WebhookEventKind::PlayerScored,
+                WebhookEventKind::H2hLeadChanged,
+                WebhookEventKind::InjuryStatusChanged,
+            ],
+            "{:?}",
+            events
+        );
+        assert_eq!(
+            events[0].message(),
+            format!(
+                "Player{} of {}: Goals scored +1, +6 points",
+                scorer, trailing.team_name
+            )
+        );
+        match &events[1] {
+            WebhookEvent::H2hLeadChanged { leader, .. } => {
+                assert_eq!(*leader, Some(trailing.team_code))
+            }
+            other => panic!("Expected a lead change, got {:?}", other),
+        }
+        assert!(events[2]
+            .message()
+            .ends_with("is Doubt, was Available: Knock - 50% chance of playing"));
+
+        endpoints.modify(|data| data.game.current_event_finished = Some(true));
+        let finished = state(&endpoints);
+        let events = compute_webhook_events(&after, &finished);
+        assert!(matches!(
+            events.as_slice(),
+            [WebhookEvent::GwFinished { gw: testdata::CURRENT_GW, table }] if table.len() == 2
+        ));
+        // A finished gameweek is only announced once
+        assert!(compute_webhook_events(&finished, &finished).is_empty());
+    }
+
+    type Received = Arc<Mutex<Vec<(String, serde_json::Value)>>>;
+
+    // A local webhook receiver, recording the path and body of every post
+    async fn receiver() -> (String, Received) {
+        let received = Received::default();
+        let record = |path: &'static str| {
+            move |State(received): State<Received>, Json(body): Json<serde_json::Value>| async move {
+                received.lock().unwrap().push((String::from(path), body));
+            }
+        };
+        let app = Router::new()
+            .route("/json", post(record("/json")))
+            .route("/discord", post(record("/discord")))
+            .route("/slack", post(record("/slack")))
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, received)
+    }
+
+    #[tokio::test]
+    async fn events_are_posted_in_the_format_of_each_webhook() {
+        let (url, received) = receiver().await;
+        let webhook = |path: &str, format, events| WebhookConfig {
+            url: Secret::new(format!("{}{}", url, path)),
+            format,
+            events,
+        };
+        let webhooks = vec![
+            webhook("/json", WebhookFormat::Json, WebhookEventKind::all()),
+            webhook(
+                "/discord",
+                WebhookFormat::Discord,
+                vec![WebhookEventKind::GwFinished],
+            ),
+            webhook("/slack", WebhookFormat::Slack, WebhookEventKind::all()),
+            webhook("/missing", WebhookFormat::Json, WebhookEventKind::all()),
+        ];
+        let endpoints = testdata::endpoints(2, false);
+        let table = state(&endpoints).table;
+        let events = vec![
+            WebhookEvent::InjuryStatusChanged {
+                team_code: 101,
+                team_name: String::from("Team 1"),
+                player_id: 5,
+                player_name: String::from("Player 5"),
+                previous_status: InjuryStatus::Available,
+                status: InjuryStatus::Unavailable,
+                news: None,
+            },
+            WebhookEvent::GwFinished {
+                gw: 3,
+                table: standings(&table),
+            },
+        ];
+
+        // The counter is shared with the other tests, so only its increase is checked
+        let failures = || metrics::get("fpl_webhook_failures_total", &[("format", "json")]);
+        let failures_before = failures();
+        let http = reqwest::Client::new();
+        send(&http, &webhooks, 7, &events).await;
+
+        let received = received.lock().unwrap();
+        let paths: Vec<&str> = received.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec!["/json", "/json", "/discord", "/slack", "/slack"]
+        );
+        let (_, json) = &received[0];
+        assert_eq!(json["league_id"], 7);
+        assert_eq!(json["event"]["event"], "injury_status_changed");
+        assert_eq!(json["event"]["status"], "unavailable");
+        assert_eq!(
+            json["message"],
+            "Player 5 of Team 1 is Unavailable, was Available"
+        );
+        let (_, discord) = &received[2];
+        assert!(discord["content"]
+            .as_str()
+            .unwrap()
+            .starts_with("GW 3 is finished. The final table:\n1. "));
+        let (_, slack) = &received[3];
+        assert_eq!(slack["text"], json["message"]);
+        assert!(failures() >= failures_before + 2);
+    }
+}